@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+/// Command-line flags. Everything is optional; with no flags ytd starts the
+/// interactive TUI.
+#[derive(Default)]
+pub struct Cli {
+    /// Hidden: download a known-good video to a temp dir and report whether
+    /// the yt-dlp setup works.
+    pub self_test: bool,
+    /// One line per state, for small tmux panes and status bars.
+    pub compact: bool,
+    /// Take the URL from the clipboard and go straight to the confirm
+    /// screen, into the last used playlist.
+    pub clip: bool,
+    /// Download these without the TUI, printing yt-dlp's output as lines.
+    /// May be given more than once.
+    pub urls: Vec<String>,
+    /// Playlist folder for `--url` downloads.
+    pub playlist: Option<String>,
+    /// With `--url`: print newline-delimited JSON events instead of text.
+    pub json: bool,
+    /// With a single `--url`: stream the media to stdout for a pipe instead
+    /// of saving it.
+    pub stdout: bool,
+    /// With a single `--url`: stay open and download its new items every
+    /// `watch_interval_minutes`.
+    pub watch: bool,
+    /// Write the whole download history to this file and exit: an M3U of
+    /// the files for `.m3u`/`.m3u8`, otherwise the URLs one per line.
+    pub export: Option<PathBuf>,
+    /// Create playlist folders in the working directory for this run,
+    /// whatever `output_base` says.
+    pub here: bool,
+}
+
+pub const USAGE: &str =
+    "Usage: ytd [--compact] [--clip] [--here]\n       ytd --url <URL>... [--playlist <name>] [--json] [--here]\n       ytd --url <URL> --stdout\n       ytd --url <URL> --watch [--playlist <name>] [--here]\n       ytd --export <file.m3u8|file.txt>";
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "--self-test" => cli.self_test = true,
+                "--compact" => cli.compact = true,
+                "--clip" => cli.clip = true,
+                "--url" => cli.urls.push(value()?),
+                "--playlist" => cli.playlist = Some(value()?),
+                "--json" => cli.json = true,
+                "--stdout" => cli.stdout = true,
+                "--watch" => cli.watch = true,
+                "--here" => cli.here = true,
+                "--export" => cli.export = Some(PathBuf::from(value()?)),
+                other => return Err(format!("Unknown argument: {}\n{}", other, USAGE)),
+            }
+        }
+        if (cli.playlist.is_some() || cli.json) && cli.urls.is_empty() {
+            return Err(format!(
+                "--playlist and --json only apply with --url\n{}",
+                USAGE
+            ));
+        }
+        if cli.stdout && (cli.urls.len() != 1 || cli.playlist.is_some() || cli.json) {
+            return Err(format!(
+                "--stdout takes exactly one --url, without --playlist or --json\n{}",
+                USAGE
+            ));
+        }
+        if cli.watch && (cli.urls.len() != 1 || cli.json || cli.stdout) {
+            return Err(format!(
+                "--watch takes exactly one --url, without --json or --stdout\n{}",
+                USAGE
+            ));
+        }
+        Ok(cli)
+    }
+}
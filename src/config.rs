@@ -0,0 +1,613 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Which yt-dlp to run.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum YtDlpSource {
+    System,
+    Managed,
+}
+
+/// The container `remux_video` repackages videos into.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoContainer {
+    Mkv,
+    Mp4,
+    Webm,
+}
+
+impl VideoContainer {
+    pub fn ext(self) -> &'static str {
+        match self {
+            VideoContainer::Mkv => "mkv",
+            VideoContainer::Mp4 => "mp4",
+            VideoContainer::Webm => "webm",
+        }
+    }
+}
+
+/// Where playlist folders go.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputBase {
+    /// `music_dir`, or `~/Music`.
+    Music,
+    /// The working directory, as with `--here`.
+    CurrentDir,
+    Custom(PathBuf),
+}
+
+/// User settings, read from `<config dir>/ytd/config.toml`. Every field has a
+/// default so a missing file or a partial one both work.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Playlists with more entries than this ask for confirmation first.
+    pub large_playlist_threshold: usize,
+    /// Longest playlist name the name field accepts, in characters. The
+    /// name becomes a directory, and very long ones run into path limits.
+    pub max_name_length: usize,
+    /// How many lines of yt-dlp output are kept for the log view. Older
+    /// lines are dropped.
+    pub max_output_lines: usize,
+    /// Where playlist folders are created. Defaults to `~/Music`.
+    pub music_dir: Option<PathBuf>,
+    /// `"music"` creates playlists in `music_dir`, `"current_dir"` in the
+    /// directory ytd was started from, and `{ custom = "/path" }` there.
+    pub output_base: OutputBase,
+    /// A playlist everything goes into, e.g. "Liked". When set, ytd starts
+    /// at the URL prompt; Shift+Tab there still picks another playlist.
+    pub default_playlist: Option<String>,
+    /// The yt-dlp `-f` selector downloads start with. Unset means AAC audio
+    /// in m4a.
+    pub format: Option<String>,
+    /// ffmpeg's binary or the folder it's in, passed as `--ffmpeg-location`
+    /// for installs that aren't on PATH.
+    pub ffmpeg_location: Option<PathBuf>,
+    /// `system` runs the yt-dlp on PATH; `managed` runs ytd's own copy,
+    /// downloaded on first start and updated from the diagnostics screen.
+    pub yt_dlp_source: YtDlpSource,
+    /// Where yt-dlp keeps its cache, passed as `--cache-dir`. Unset means
+    /// yt-dlp's own default.
+    pub cache_dir: Option<PathBuf>,
+    /// Keep the `t=` start time when cleaning up YouTube URLs, for clips
+    /// that should start partway in.
+    pub keep_url_timestamp: bool,
+    /// Preferred audio track language (e.g. "en") for videos with dubs.
+    pub audio_language: Option<String>,
+    /// Write a `<title>.chapters.txt` next to videos that have chapters.
+    pub chapters_sidecar: bool,
+    /// Split videos with chapters into one numbered track per chapter, for
+    /// DJ mixes and full albums. The whole file is kept alongside them.
+    /// Videos without chapters stay one file.
+    pub split_chapters: bool,
+    /// Write a `<title>.nfo` for media servers like Jellyfin, Kodi and Plex.
+    pub nfo_sidecar: bool,
+    /// Fetch the uploader's subtitles and keep them as a `<title>.lrc` of
+    /// synced lyrics. Videos without subtitles are skipped. Not made along
+    /// with `transcript_sidecar`, whose auto captions can't be told apart
+    /// from uploaded subtitles.
+    pub lyrics_sidecar: bool,
+    /// Write a `<title>.transcript.txt` of what's said, from the uploader's
+    /// subtitles or else the auto-generated captions, for reading lectures
+    /// and talks. Without an audio language set, captions in the video's
+    /// own language are used.
+    pub transcript_sidecar: bool,
+    /// Look up each track's lyrics by its artist and title tags at
+    /// `lyrics_endpoint` and embed them. Tracks the provider doesn't know
+    /// are left without. Needs ytd built with the `lyrics` feature.
+    pub fetch_lyrics: bool,
+    /// An LRCLIB-compatible lyrics API.
+    pub lyrics_endpoint: String,
+    /// Seconds a single lyrics request may take before it's given up on.
+    pub lyrics_timeout_secs: u64,
+    /// Besides each download, keep a small `<title>.preview.opus` at
+    /// `preview_bitrate`, encoded from the downloaded file.
+    pub preview_copy: bool,
+    /// ffmpeg bitrate for previews, e.g. `"64k"`.
+    pub preview_bitrate: String,
+    /// Pass `--embed-info-json` so the metadata travels inside the file.
+    pub embed_info_json: bool,
+    /// Set the file modification time to the upload date (`--mtime`).
+    pub mtime: bool,
+    /// In a multi-URL queue, keep going after a URL fails instead of
+    /// stopping there.
+    pub continue_on_error: bool,
+    /// Offer a username/password login on the confirm screen. Only the
+    /// username is remembered; the password is asked for every session.
+    pub login: bool,
+    pub username: Option<String>,
+    /// A Netscape-format cookies.txt passed as `--cookies`. Remembered
+    /// between runs.
+    pub cookies_file: Option<PathBuf>,
+    /// Download into a staging directory and only move the files into the
+    /// playlist folder once everything succeeded.
+    pub staged_downloads: bool,
+    /// How many results a search asks YouTube for.
+    pub search_results: usize,
+    /// Sort downloads into subfolders using `library_template` instead of
+    /// dropping them straight into the playlist folder.
+    pub library_mode: bool,
+    /// yt-dlp output template used in library mode, relative to the
+    /// playlist folder.
+    pub library_template: String,
+    /// After downloading, move the tracks the run downloaded into folders
+    /// named by `tag_folders_template` from their embedded tags, which for
+    /// music are more reliable than YouTube's metadata. Tracks without the
+    /// tags go to "Unknown Artist" and the like; earlier downloads stay put.
+    pub organize_by_tags: bool,
+    /// Folders for `organize_by_tags`, relative to the playlist folder,
+    /// with `{tag}` for any tag, e.g. `"{album_artist}/{album}"`.
+    pub tag_folders_template: String,
+    /// Sort downloads into `<year>/<month>/` folders by upload date, using
+    /// `date_template`. Library mode wins when both are on.
+    pub date_folders: bool,
+    /// yt-dlp output template for date folders, relative to the playlist
+    /// folder. Videos without an upload or release date fall back to the
+    /// day they were downloaded.
+    pub date_template: String,
+    /// Written into each file's comment tag, e.g. "Downloaded with ytd on
+    /// {date} from {url}". Replaces the video's URL, which yt-dlp would
+    /// otherwise put there; only applies when metadata is embedded.
+    pub tag_comment: Option<String>,
+    /// Tag every file with the playlist name as its album, so the folder
+    /// shows up as one album in music players.
+    pub album_from_playlist: bool,
+    /// With `album_from_playlist`, keep the album a video already has
+    /// (music uploads usually do) and only fill in the rest.
+    pub keep_source_album: bool,
+    /// Genre tag for every download, e.g. "Jazz". Can be changed for one
+    /// download on the Confirm screen; ignored if it has line breaks or
+    /// other control characters.
+    pub genre: Option<String>,
+    /// Skip videos longer than this, as `HH:MM:SS`, `MM:SS` or seconds,
+    /// e.g. to leave out the hour-long mixes in a playlist of songs.
+    /// Videos of unknown length are kept.
+    pub max_duration: Option<String>,
+    /// Before downloading a single video, look for files in the playlist
+    /// with a similar name and warn about them.
+    pub duplicate_check: bool,
+    /// Keep one download archive for the whole music directory, so a video
+    /// already saved into any playlist is skipped instead of saved again.
+    /// Only downloads made with this on are known to it.
+    pub global_dedup: bool,
+    /// Offer to drop to a lower quality when a playlist item downloads
+    /// slower than `slow_speed_kib` KiB/s. Not offered with `--url`.
+    pub adaptive_quality: bool,
+    pub slow_speed_kib: u64,
+    /// Shell command run after every successful download, with
+    /// `YTD_PLAYLIST`, `YTD_DIR`, `YTD_FILE_COUNT` (files this run
+    /// downloaded) and `YTD_URL` set. It runs with your privileges, so anyone
+    /// who can edit this file can run commands as you.
+    pub post_hook: Option<String>,
+    /// With `--watch`, minutes between checks for new items.
+    pub watch_interval_minutes: u64,
+    /// Player for the Done screen's play actions, e.g. "mpv --no-video"; the
+    /// files are appended. Without one the system's default app is used.
+    pub player_command: Option<String>,
+    /// Prefer opus/vorbis audio in webm/ogg over AAC in m4a, so files come
+    /// out as `.opus` or `.ogg` rather than `.m4a`.
+    pub prefer_free_formats: bool,
+    /// Download the highest-bitrate audio there is, in whatever format it
+    /// comes in (`-f bestaudio --audio-quality 0`), instead of the best m4a.
+    /// A format picked by hand still wins.
+    pub max_quality_audio: bool,
+    /// Cut silence from the start and end of each track. Re-encodes the
+    /// audio, in the codec the track already has.
+    pub trim_silence: bool,
+    /// Repackage video downloads into `video_container` without
+    /// re-encoding them (`--remux-video`), for a library in one container.
+    pub remux_video: bool,
+    /// `"mkv"`, `"mp4"` or `"webm"`. mkv holds any codec; the others can
+    /// only take the codecs they support.
+    pub video_container: VideoContainer,
+    /// Fail a download when a fragment can't be fetched. Off means yt-dlp's
+    /// default of skipping it, which can leave a gap in the file.
+    pub abort_on_missing_fragments: bool,
+    /// Seconds to wait between the requests yt-dlp makes while looking up a
+    /// video (`--sleep-requests`), to stay clear of YouTube's rate limit.
+    pub sleep_requests: Option<f64>,
+    /// Seconds to wait before each download (`--sleep-interval`), which with
+    /// one run per playlist item means between items.
+    pub sleep_interval: Option<f64>,
+    /// When the format asked for doesn't exist for a video, list the ones it
+    /// has instead of failing.
+    pub ask_format_on_failure: bool,
+    /// Pick a format before every single-video download, instead of
+    /// starting with the default.
+    pub always_ask_format: bool,
+    /// When a video is blocked here, try again with an audio-only format
+    /// instead of failing. The Done screen says when that happened.
+    pub audio_fallback: bool,
+    /// Scroll with the mouse wheel and click list items. While on, the
+    /// terminal's own click-and-drag text selection usually needs Shift.
+    pub mouse: bool,
+    /// Start each line of the captured yt-dlp output with the time since
+    /// the download started, e.g. `[+12.3s]`, to see which step is slow.
+    pub timestamp_log: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            large_playlist_threshold: 50,
+            max_name_length: 100,
+            max_output_lines: 1000,
+            music_dir: None,
+            output_base: OutputBase::Music,
+            default_playlist: None,
+            format: None,
+            ffmpeg_location: None,
+            yt_dlp_source: YtDlpSource::System,
+            cache_dir: None,
+            keep_url_timestamp: false,
+            audio_language: None,
+            chapters_sidecar: false,
+            split_chapters: false,
+            nfo_sidecar: false,
+            lyrics_sidecar: false,
+            transcript_sidecar: false,
+            fetch_lyrics: false,
+            lyrics_endpoint: "https://lrclib.net/api/get".to_string(),
+            lyrics_timeout_secs: 10,
+            preview_copy: false,
+            preview_bitrate: "64k".to_string(),
+            embed_info_json: false,
+            mtime: true,
+            continue_on_error: true,
+            login: false,
+            username: None,
+            cookies_file: None,
+            staged_downloads: false,
+            search_results: 5,
+            library_mode: false,
+            library_template: "%(uploader)s/%(album,playlist_title|Singles)s/%(title)s.%(ext)s"
+                .to_string(),
+            organize_by_tags: false,
+            tag_folders_template: "{artist}/{album}".to_string(),
+            date_folders: false,
+            date_template: "%(upload_date,release_date,epoch>%Y|Unknown)s/%(upload_date,release_date,epoch>%m|Unknown)s/%(title)s.%(ext)s"
+                .to_string(),
+            tag_comment: None,
+            album_from_playlist: false,
+            keep_source_album: false,
+            genre: None,
+            max_duration: None,
+            duplicate_check: false,
+            global_dedup: false,
+            adaptive_quality: false,
+            slow_speed_kib: 150,
+            post_hook: None,
+            watch_interval_minutes: 60,
+            player_command: None,
+            prefer_free_formats: false,
+            max_quality_audio: false,
+            trim_silence: false,
+            remux_video: false,
+            video_container: VideoContainer::Mkv,
+            abort_on_missing_fragments: false,
+            sleep_requests: None,
+            sleep_interval: None,
+            ask_format_on_failure: true,
+            always_ask_format: false,
+            audio_fallback: false,
+            mouse: false,
+            timestamp_log: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("ytd").join("config.toml"))
+    }
+
+    /// Loads the config file, falling back to defaults when it doesn't exist.
+    /// A file that exists but can't be parsed is an error rather than being
+    /// silently ignored.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| format!("Invalid config file {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = Self::path() else {
+            return Err("Could not determine the config directory".to_string());
+        };
+        let contents = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&path, contents)
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+
+    /// No config file yet, i.e. ytd has never been set up here.
+    pub fn is_first_run() -> bool {
+        Self::path().is_some_and(|p| !p.exists())
+    }
+
+    /// The ffmpeg to run ourselves: the configured binary, `ffmpeg` inside
+    /// the configured folder, or whatever is on PATH.
+    pub fn ffmpeg_program(&self) -> PathBuf {
+        match &self.ffmpeg_location {
+            Some(dir) if dir.is_dir() => {
+                dir.join(format!("ffmpeg{}", std::env::consts::EXE_SUFFIX))
+            }
+            Some(path) => path.clone(),
+            None => PathBuf::from("ffmpeg"),
+        }
+    }
+
+    pub fn music_dir(&self) -> Result<PathBuf, String> {
+        self.music_dir_with(dirs::home_dir)
+    }
+
+    /// Resolves the base music directory, following `output_base`.
+    /// `home_dir` is injected so the no-home case can be tested.
+    pub fn music_dir_with(
+        &self,
+        home_dir: impl FnOnce() -> Option<PathBuf>,
+    ) -> Result<PathBuf, String> {
+        match &self.output_base {
+            OutputBase::Music => {}
+            OutputBase::CurrentDir => {
+                return std::env::current_dir()
+                    .map_err(|e| format!("Could not determine the current directory: {}", e))
+            }
+            OutputBase::Custom(dir) => return Ok(dir.clone()),
+        }
+        if let Some(dir) = &self.music_dir {
+            return Ok(dir.clone());
+        }
+        home_dir().map(|home| home.join("Music")).ok_or_else(|| {
+            "Could not determine home directory; set music_dir in config".to_string()
+        })
+    }
+}
+
+/// An on/off setting shown on the settings screen.
+#[derive(Clone, Copy)]
+pub enum Setting {
+    ChaptersSidecar,
+    SplitChapters,
+    NfoSidecar,
+    LyricsSidecar,
+    TranscriptSidecar,
+    #[cfg(feature = "lyrics")]
+    FetchLyrics,
+    PreviewCopy,
+    EmbedInfoJson,
+    AlbumFromPlaylist,
+    KeepSourceAlbum,
+    Mtime,
+    ContinueOnError,
+    Login,
+    StagedDownloads,
+    LibraryMode,
+    OrganizeByTags,
+    DateFolders,
+    DuplicateCheck,
+    GlobalDedup,
+    AdaptiveQuality,
+    PreferFreeFormats,
+    MaxQualityAudio,
+    TrimSilence,
+    RemuxVideo,
+    AbortOnMissingFragments,
+    AskFormatOnFailure,
+    AlwaysAskFormat,
+    AudioFallback,
+    Mouse,
+    TimestampLog,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting::ChaptersSidecar,
+    Setting::SplitChapters,
+    Setting::NfoSidecar,
+    Setting::LyricsSidecar,
+    Setting::TranscriptSidecar,
+    #[cfg(feature = "lyrics")]
+    Setting::FetchLyrics,
+    Setting::PreviewCopy,
+    Setting::EmbedInfoJson,
+    Setting::AlbumFromPlaylist,
+    Setting::KeepSourceAlbum,
+    Setting::Mtime,
+    Setting::ContinueOnError,
+    Setting::Login,
+    Setting::StagedDownloads,
+    Setting::LibraryMode,
+    Setting::OrganizeByTags,
+    Setting::DateFolders,
+    Setting::DuplicateCheck,
+    Setting::GlobalDedup,
+    Setting::AdaptiveQuality,
+    Setting::PreferFreeFormats,
+    Setting::MaxQualityAudio,
+    Setting::TrimSilence,
+    Setting::RemuxVideo,
+    Setting::AbortOnMissingFragments,
+    Setting::AskFormatOnFailure,
+    Setting::AlwaysAskFormat,
+    Setting::AudioFallback,
+    Setting::Mouse,
+    Setting::TimestampLog,
+];
+
+impl Setting {
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::ChaptersSidecar => "Write chapters sidecar (.chapters.txt)",
+            Setting::SplitChapters => "Split videos into one track per chapter",
+            Setting::NfoSidecar => "Write media server metadata (.nfo)",
+            Setting::LyricsSidecar => "Write synced lyrics from subtitles (.lrc)",
+            Setting::TranscriptSidecar => "Write a plain-text transcript (.transcript.txt)",
+            #[cfg(feature = "lyrics")]
+            Setting::FetchLyrics => "Look up lyrics online and embed them",
+            Setting::PreviewCopy => "Also keep a small preview (.preview.opus)",
+            Setting::EmbedInfoJson => "Embed info JSON (mkv/mka only)",
+            Setting::AlbumFromPlaylist => "Tag files with the playlist name as album",
+            Setting::KeepSourceAlbum => "...but keep a video's own album if it has one",
+            Setting::Mtime => "Set file time to upload date",
+            Setting::ContinueOnError => "Continue the queue after a failed URL",
+            Setting::Login => "Log in with username/password",
+            Setting::StagedDownloads => "Stage downloads, move into Music on success",
+            Setting::LibraryMode => "Library mode: <uploader>/<album>/<title>",
+            Setting::OrganizeByTags => "Sort tracks into <artist>/<album> by their tags",
+            Setting::DateFolders => "Date folders: <year>/<month>/<title>",
+            Setting::DuplicateCheck => "Warn about similar files already downloaded",
+            Setting::GlobalDedup => "Skip videos already downloaded into any playlist",
+            Setting::AdaptiveQuality => "Offer lower quality on a slow connection",
+            Setting::PreferFreeFormats => "Prefer free formats (opus/ogg instead of m4a)",
+            Setting::MaxQualityAudio => "Max quality audio: highest bitrate, any format",
+            Setting::TrimSilence => "Trim silence at the start and end of tracks",
+            Setting::RemuxVideo => "Remux videos into video_container (no re-encode)",
+            Setting::AbortOnMissingFragments => "Fail instead of skipping missing fragments",
+            Setting::AskFormatOnFailure => "Offer other formats when the default is missing",
+            Setting::AlwaysAskFormat => "Always pick a format before downloading a video",
+            Setting::AudioFallback => "Retry blocked videos as audio only",
+            Setting::Mouse => "Mouse: wheel scrolls, click selects (Shift to select text)",
+            Setting::TimestampLog => "Timestamp each line of the log",
+        }
+    }
+
+    pub fn get(self, config: &Config) -> bool {
+        match self {
+            Setting::ChaptersSidecar => config.chapters_sidecar,
+            Setting::SplitChapters => config.split_chapters,
+            Setting::NfoSidecar => config.nfo_sidecar,
+            Setting::LyricsSidecar => config.lyrics_sidecar,
+            Setting::TranscriptSidecar => config.transcript_sidecar,
+            #[cfg(feature = "lyrics")]
+            Setting::FetchLyrics => config.fetch_lyrics,
+            Setting::PreviewCopy => config.preview_copy,
+            Setting::EmbedInfoJson => config.embed_info_json,
+            Setting::AlbumFromPlaylist => config.album_from_playlist,
+            Setting::KeepSourceAlbum => config.keep_source_album,
+            Setting::Mtime => config.mtime,
+            Setting::ContinueOnError => config.continue_on_error,
+            Setting::Login => config.login,
+            Setting::StagedDownloads => config.staged_downloads,
+            Setting::LibraryMode => config.library_mode,
+            Setting::OrganizeByTags => config.organize_by_tags,
+            Setting::DateFolders => config.date_folders,
+            Setting::DuplicateCheck => config.duplicate_check,
+            Setting::GlobalDedup => config.global_dedup,
+            Setting::AdaptiveQuality => config.adaptive_quality,
+            Setting::PreferFreeFormats => config.prefer_free_formats,
+            Setting::MaxQualityAudio => config.max_quality_audio,
+            Setting::TrimSilence => config.trim_silence,
+            Setting::RemuxVideo => config.remux_video,
+            Setting::AbortOnMissingFragments => config.abort_on_missing_fragments,
+            Setting::AskFormatOnFailure => config.ask_format_on_failure,
+            Setting::AlwaysAskFormat => config.always_ask_format,
+            Setting::AudioFallback => config.audio_fallback,
+            Setting::Mouse => config.mouse,
+            Setting::TimestampLog => config.timestamp_log,
+        }
+    }
+
+    pub fn toggle(self, config: &mut Config) {
+        let value = match self {
+            Setting::ChaptersSidecar => &mut config.chapters_sidecar,
+            Setting::SplitChapters => &mut config.split_chapters,
+            Setting::NfoSidecar => &mut config.nfo_sidecar,
+            Setting::LyricsSidecar => &mut config.lyrics_sidecar,
+            Setting::TranscriptSidecar => &mut config.transcript_sidecar,
+            #[cfg(feature = "lyrics")]
+            Setting::FetchLyrics => &mut config.fetch_lyrics,
+            Setting::PreviewCopy => &mut config.preview_copy,
+            Setting::EmbedInfoJson => &mut config.embed_info_json,
+            Setting::AlbumFromPlaylist => &mut config.album_from_playlist,
+            Setting::KeepSourceAlbum => &mut config.keep_source_album,
+            Setting::Mtime => &mut config.mtime,
+            Setting::ContinueOnError => &mut config.continue_on_error,
+            Setting::Login => &mut config.login,
+            Setting::StagedDownloads => &mut config.staged_downloads,
+            Setting::LibraryMode => &mut config.library_mode,
+            Setting::OrganizeByTags => &mut config.organize_by_tags,
+            Setting::DateFolders => &mut config.date_folders,
+            Setting::DuplicateCheck => &mut config.duplicate_check,
+            Setting::GlobalDedup => &mut config.global_dedup,
+            Setting::AdaptiveQuality => &mut config.adaptive_quality,
+            Setting::PreferFreeFormats => &mut config.prefer_free_formats,
+            Setting::MaxQualityAudio => &mut config.max_quality_audio,
+            Setting::TrimSilence => &mut config.trim_silence,
+            Setting::RemuxVideo => &mut config.remux_video,
+            Setting::AbortOnMissingFragments => &mut config.abort_on_missing_fragments,
+            Setting::AskFormatOnFailure => &mut config.ask_format_on_failure,
+            Setting::AlwaysAskFormat => &mut config.always_ask_format,
+            Setting::AudioFallback => &mut config.audio_fallback,
+            Setting::Mouse => &mut config.mouse,
+            Setting::TimestampLog => &mut config.timestamp_log,
+        };
+        *value = !*value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn music_dir_defaults_to_home_music() {
+        let config = Config::default();
+        let dir = config.music_dir_with(|| Some(PathBuf::from("/home/me")));
+        assert_eq!(dir, Ok(PathBuf::from("/home/me/Music")));
+    }
+
+    #[test]
+    fn music_dir_without_home_is_an_error() {
+        let config = Config::default();
+        assert!(config.music_dir_with(|| None).is_err());
+    }
+
+    #[test]
+    fn configured_music_dir_does_not_need_home() {
+        let config = Config {
+            music_dir: Some(PathBuf::from("/srv/music")),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.music_dir_with(|| None),
+            Ok(PathBuf::from("/srv/music"))
+        );
+    }
+
+    #[test]
+    fn output_base_overrides_the_music_dir() {
+        let mut config = Config {
+            music_dir: Some(PathBuf::from("/srv/music")),
+            output_base: OutputBase::Custom(PathBuf::from("/media/podcasts")),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.music_dir_with(|| None),
+            Ok(PathBuf::from("/media/podcasts"))
+        );
+        config.output_base = OutputBase::CurrentDir;
+        assert_eq!(
+            config.music_dir_with(|| None),
+            std::env::current_dir().map_err(|e| e.to_string())
+        );
+
+        let parsed: Config = toml::from_str("output_base = { custom = \"/mnt/usb\" }").unwrap();
+        assert_eq!(
+            parsed.output_base,
+            OutputBase::Custom(PathBuf::from("/mnt/usb"))
+        );
+        let parsed: Config = toml::from_str("output_base = \"current_dir\"").unwrap();
+        assert_eq!(parsed.output_base, OutputBase::CurrentDir);
+    }
+}
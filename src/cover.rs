@@ -0,0 +1,54 @@
+//! Embedding a user-supplied image as the cover art of downloaded tracks,
+//! instead of the video thumbnail.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Checks that `image` exists and is something ffmpeg can attach as an m4a
+/// cover (JPEG or PNG).
+pub fn validate_cover_image(image: &Path) -> Result<(), String> {
+    if !image.is_file() {
+        return Err(format!("{} does not exist", image.display()));
+    }
+    let ext = image
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !["jpg", "jpeg", "png"].contains(&ext.as_str()) {
+        return Err("Cover must be a .jpg or .png image".to_string());
+    }
+    Ok(())
+}
+
+/// Replaces the cover of `file` with `image`, using the `ffmpeg` binary
+/// given. ffmpeg writes a copy next to the file, which then replaces the
+/// original, so a failure leaves the original untouched.
+pub fn embed_cover(ffmpeg: &Path, file: &Path, image: &Path) -> io::Result<()> {
+    let ext = file.extension().unwrap_or_default().to_string_lossy();
+    let tmp = file.with_extension(format!("cover.{}", ext));
+    let status = Command::new(ffmpeg)
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(file)
+        .arg("-i")
+        .arg(image)
+        .args([
+            "-map",
+            "0:a",
+            "-map",
+            "1",
+            "-c",
+            "copy",
+            "-disposition:v:0",
+            "attached_pic",
+        ])
+        .arg(&tmp)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(io::Error::other(format!("ffmpeg exited with {}", status)));
+    }
+    std::fs::rename(&tmp, file)
+}
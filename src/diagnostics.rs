@@ -0,0 +1,86 @@
+//! What a maintainer asks for in a bug report: tool versions, the config,
+//! where files go and what system this is.
+
+use crate::config::{Config, YtDlpSource};
+use crate::managed::yt_dlp;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub struct Diagnostics {
+    pub ytd: String,
+    pub yt_dlp: String,
+    pub ffmpeg: String,
+    pub platform: String,
+    pub config_path: String,
+    pub music_dir: String,
+    /// The config as it was parsed, defaults filled in.
+    pub config: String,
+}
+
+impl Diagnostics {
+    /// Plain text for pasting into an issue.
+    pub fn to_text(&self) -> String {
+        format!(
+            "ytd {}\nyt-dlp: {}\nffmpeg: {}\nPlatform: {}\nConfig file: {}\nMusic directory: {}\n\n{}",
+            self.ytd,
+            self.yt_dlp,
+            self.ffmpeg,
+            self.platform,
+            self.config_path,
+            self.music_dir,
+            self.config.trim_end()
+        )
+    }
+}
+
+/// The first line `program <flag>` prints, or why there isn't one.
+fn version_line(program: impl AsRef<OsStr>, flag: &str) -> String {
+    match Command::new(program)
+        .arg(flag)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_string(),
+        Ok(output) => format!("failed ({})", output.status),
+        Err(e) => format!("not found ({})", e),
+    }
+}
+
+/// Runs the version commands and reads back the config. Takes about as
+/// long as starting yt-dlp once.
+pub fn gather_diagnostics(config: &Config, music_dir: &Path) -> Diagnostics {
+    let config_path = match Config::path() {
+        Some(path) if path.exists() => path.display().to_string(),
+        Some(path) => format!("{} (not created yet)", path.display()),
+        None => "no config directory".to_string(),
+    };
+    Diagnostics {
+        ytd: env!("CARGO_PKG_VERSION").to_string(),
+        yt_dlp: match config.yt_dlp_source {
+            YtDlpSource::System => version_line(yt_dlp(), "--version"),
+            YtDlpSource::Managed => format!(
+                "{} (managed, {})",
+                version_line(yt_dlp(), "--version"),
+                yt_dlp().display()
+            ),
+        },
+        ffmpeg: version_line(config.ffmpeg_program(), "-version"),
+        platform: format!(
+            "{} {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            std::env::consts::FAMILY
+        ),
+        config_path,
+        music_dir: music_dir.display().to_string(),
+        config: toml::to_string_pretty(config)
+            .unwrap_or_else(|e| format!("Could not show the config: {}", e)),
+    }
+}
@@ -0,0 +1,1283 @@
+//! Running yt-dlp as a child process and collecting what it prints.
+
+use crate::managed::yt_dlp;
+use crate::paths::strip_verbatim;
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub fn is_playlist_url(url: &str) -> bool {
+    ["list=", "/playlist", "/@", "/channel/", "/c/", "/user/"]
+        .iter()
+        .any(|marker| url.contains(marker))
+}
+
+/// Whether yt-dlp lists `url` newest first. Channels and their uploads
+/// playlists (`UU...`) do; ordinary playlists keep the order they were
+/// put together in, usually oldest first.
+pub fn lists_newest_first(url: &str) -> bool {
+    url.contains("list=UU") || (!url.contains("list=") && is_playlist_url(url))
+}
+
+/// The `--playlist-items` selection for the newest `n` entries of `url`.
+pub fn latest_items_selector(url: &str, n: usize) -> String {
+    if lists_newest_first(url) {
+        format!(":{}", n)
+    } else {
+        format!("-{}:", n)
+    }
+}
+
+/// Query parameters a YouTube URL keeps through `normalize_url`; the rest
+/// (`si`, `feature`, `pp`, `index`, ...) are sharing and tracking noise.
+const KEPT_PARAMS: [&str; 2] = ["v", "list"];
+
+/// Strips tracking parameters from YouTube URLs so downloads and history
+/// see one URL per video. `youtu.be` links become `watch?v=` links. The
+/// `t` start time is kept only with `keep_timestamp`. Other sites' URLs
+/// are returned as they are, since their parameters may matter.
+pub fn normalize_url(url: &str, keep_timestamp: bool) -> String {
+    let url = url.trim();
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let rest = rest.split('#').next().unwrap_or(rest);
+    let (location, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (host, path) = location.split_at(location.find('/').unwrap_or(location.len()));
+    let host = host.to_ascii_lowercase();
+
+    let mut params: Vec<(&str, &str)> = query
+        .split('&')
+        .filter_map(|p| p.split_once('=').or(Some((p, ""))))
+        .filter(|(key, _)| KEPT_PARAMS.contains(key) || (keep_timestamp && *key == "t"))
+        .collect();
+    let (host, path) = match host.as_str() {
+        "youtu.be" | "www.youtu.be" => {
+            let id = path.trim_start_matches('/');
+            if id.is_empty() {
+                return url.to_string();
+            }
+            params.insert(0, ("v", id));
+            ("www.youtube.com", "/watch")
+        }
+        "youtube.com" | "www.youtube.com" | "m.youtube.com" | "music.youtube.com" => {
+            (host.as_str(), path)
+        }
+        _ => return url.to_string(),
+    };
+
+    let mut out = format!("{}://{}{}", scheme, host, path);
+    for (i, (key, value)) in params.iter().enumerate() {
+        out.push(if i == 0 { '?' } else { '&' });
+        out.push_str(key);
+        out.push('=');
+        out.push_str(value);
+    }
+    out
+}
+
+/// The ID in a YouTube watch, youtu.be or shorts URL.
+pub fn video_id(url: &str) -> Option<String> {
+    let url = normalize_url(url, false);
+    let (_, rest) = url.split_once("://")?;
+    let (location, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (host, path) = location.split_once('/')?;
+    if !host.ends_with("youtube.com") {
+        return None;
+    }
+    let id = match path.strip_prefix("shorts/") {
+        Some(id) => id.trim_end_matches('/'),
+        None if path == "watch" => query.split('&').find_map(|p| p.strip_prefix("v="))?,
+        None => return None,
+    };
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// A plausible http(s) URL: a scheme, a dotted host and no whitespace.
+/// yt-dlp has the final say; this only keeps random clipboard text out.
+pub fn is_url(text: &str) -> bool {
+    let Some(rest) = text
+        .strip_prefix("https://")
+        .or_else(|| text.strip_prefix("http://"))
+    else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    host.contains('.') && !host.starts_with('.') && !text.contains(char::is_whitespace)
+}
+
+/// Runs `yt-dlp --rm-cache-dir`, on `cache_dir` if one is configured.
+pub fn clear_cache(cache_dir: Option<&Path>) -> Result<(), String> {
+    let mut command = Command::new(yt_dlp());
+    if let Some(dir) = cache_dir {
+        command.arg("--cache-dir").arg(dir);
+    }
+    let output = command
+        .arg("--rm-cache-dir")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Could not run yt-dlp: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(match stderr.lines().last() {
+            Some(line) => line.trim().to_string(),
+            None => format!("yt-dlp --rm-cache-dir failed ({})", output.status),
+        })
+    }
+}
+
+/// Asks yt-dlp how many entries a playlist has without downloading anything.
+pub fn count_playlist_items(url: &str) -> Option<usize> {
+    let output = Command::new(yt_dlp())
+        .args(["--flat-playlist", "--print", "id", url])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().filter(|l| !l.trim().is_empty()).count())
+}
+
+/// The title of a single video, without downloading it.
+pub fn fetch_title(url: &str) -> Option<String> {
+    let output = Command::new(yt_dlp())
+        .args(["--print", "title", "--no-playlist", url])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .next()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// One hit from a `ytsearchN:` query.
+#[derive(Clone)]
+pub struct SearchResult {
+    pub title: String,
+    pub id: String,
+}
+
+impl SearchResult {
+    pub fn url(&self) -> String {
+        format!("https://www.youtube.com/watch?v={}", self.id)
+    }
+}
+
+/// Searches YouTube for `query`, returning up to `n` results. On failure the
+/// error is yt-dlp's last stderr line.
+pub fn search_youtube(query: &str, n: usize) -> Result<Vec<SearchResult>, String> {
+    let output = Command::new(yt_dlp())
+        .args(["--flat-playlist", "--print", "%(title)s\t%(id)s"])
+        .arg(format!("ytsearch{}:{}", n, query))
+        .output()
+        .map_err(|e| format!("Could not run yt-dlp: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr
+            .lines()
+            .rfind(|l| !l.trim().is_empty())
+            .unwrap_or("Search failed")
+            .to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| {
+            let (title, id) = l.rsplit_once('\t')?;
+            Some(SearchResult {
+                title: title.to_string(),
+                id: id.trim().to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Whether a video is an ongoing or scheduled live stream.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LiveStatus {
+    NotLive,
+    Live,
+    /// A premiere or stream that hasn't started yet.
+    Upcoming,
+}
+
+/// Reads `live_status` from `--dump-json`. `--ignore-no-formats-error`
+/// keeps yt-dlp from failing on streams that haven't started.
+pub fn fetch_live_status(url: &str) -> Option<LiveStatus> {
+    let output = Command::new(yt_dlp())
+        .args([
+            "--dump-json",
+            "--no-playlist",
+            "--ignore-no-formats-error",
+            url,
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    Some(match info["live_status"].as_str() {
+        Some("is_live") => LiveStatus::Live,
+        Some("is_upcoming") => LiveStatus::Upcoming,
+        Some(_) => LiveStatus::NotLive,
+        None if info["is_live"].as_bool() == Some(true) => LiveStatus::Live,
+        None => LiveStatus::NotLive,
+    })
+}
+
+/// What a download of a video or playlist will roughly weigh, summed from
+/// yt-dlp's per-item `filesize` or `filesize_approx`.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct SizeEstimate {
+    pub bytes: u64,
+    pub items: usize,
+    /// Items yt-dlp had no size for, left out of `bytes`.
+    pub unknown: usize,
+}
+
+/// Sums the output of `--print "%(filesize,filesize_approx)s"`, one line per
+/// item, `NA` where the size isn't known.
+pub fn parse_size_estimate(output: &str) -> SizeEstimate {
+    let mut estimate = SizeEstimate::default();
+    for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        estimate.items += 1;
+        match line.parse::<f64>() {
+            Ok(size) if size >= 0.0 => estimate.bytes += size as u64,
+            _ => estimate.unknown += 1,
+        }
+    }
+    estimate
+}
+
+/// Asks yt-dlp, without downloading anything, how big `url` is in
+/// `format`. Slow for long playlists, since every item is looked up. No
+/// items at all means yt-dlp couldn't tell.
+pub fn fetch_size_estimate(url: &str, format: &str) -> SizeEstimate {
+    let Ok(output) = Command::new(yt_dlp())
+        .args([
+            "--simulate",
+            "--ignore-errors",
+            "--no-warnings",
+            "-f",
+            format,
+            "--print",
+            "%(filesize,filesize_approx)s",
+            url,
+        ])
+        .stderr(Stdio::null())
+        .output()
+    else {
+        return SizeEstimate::default();
+    };
+    parse_size_estimate(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The latest `[download]  42.3% of 3.45MiB at 2.30MiB/s ETA 00:01` line,
+/// picked apart. Live recordings have no total, so they report how much
+/// has been recorded instead of a percentage.
+#[derive(Clone, Default, PartialEq)]
+pub struct Progress {
+    pub percent: Option<f64>,
+    pub speed: Option<String>,
+    pub eta: Option<String>,
+    /// Length of a live recording so far, e.g. `00:12:34`.
+    pub elapsed: Option<String>,
+    /// Bytes of a live recording so far, e.g. `12.34MiB`.
+    pub downloaded: Option<String>,
+}
+
+pub fn parse_progress_line(line: &str) -> Option<Progress> {
+    let Some(rest) = line.strip_prefix("[download]") else {
+        return parse_ffmpeg_progress(line);
+    };
+    let tokens: Vec<&str> = rest.split_whitespace().collect();
+    let after = |word: &str| {
+        tokens
+            .iter()
+            .position(|t| *t == word)
+            .and_then(|i| tokens.get(i + 1))
+            .map(|t| t.trim_start_matches('~').to_string())
+    };
+    let speed = after("at").filter(|s| s != "Unknown");
+    let Some(percent) = tokens
+        .first()?
+        .strip_suffix('%')
+        .and_then(|p| p.parse::<f64>().ok())
+    else {
+        // A live fragment download: `[download]  12.34MiB at 1.20MiB/s (00:01:23)`.
+        let downloaded = tokens.first().filter(|t| t.ends_with("iB"))?;
+        let elapsed = tokens
+            .iter()
+            .find_map(|t| t.strip_prefix('(')?.strip_suffix(')'))
+            .filter(|t| t.contains(':'));
+        return Some(Progress {
+            speed,
+            elapsed: elapsed.map(String::from),
+            downloaded: Some(downloaded.to_string()),
+            ..Progress::default()
+        });
+    };
+    Some(Progress {
+        percent: Some(percent),
+        speed,
+        eta: after("ETA").filter(|s| s != "Unknown"),
+        ..Progress::default()
+    })
+}
+
+/// ffmpeg's `size=  1024kB time=00:01:23.45 bitrate=...` status line, which
+/// is what yt-dlp shows while it records a live HLS stream.
+fn parse_ffmpeg_progress(line: &str) -> Option<Progress> {
+    let field = |name: &str| {
+        let start = line.find(name)? + name.len();
+        line[start..].split_whitespace().next().map(String::from)
+    };
+    let time = field("time=")?;
+    Some(Progress {
+        elapsed: Some(time.split('.').next().unwrap_or(&time).to_string()),
+        downloaded: field("size="),
+        ..Progress::default()
+    })
+}
+
+/// A cause of failure recognised in yt-dlp's output, with advice that's more
+/// useful than "check your connection".
+#[derive(Clone, Copy, PartialEq)]
+pub enum FailureKind {
+    /// YouTube's bot check or an age/membership gate.
+    SignInRequired,
+    /// The `-f` selector matched none of the video's formats.
+    FormatUnavailable,
+    /// Blocked in this country, or otherwise held back by the uploader.
+    Restricted,
+    /// yt-dlp couldn't find ffmpeg for post-processing.
+    FfmpegMissing,
+    /// The thumbnail couldn't be embedded in the downloaded container.
+    ThumbnailEmbed,
+    /// YouTube answered 429 Too Many Requests.
+    RateLimited,
+    /// Signature or nsig extraction failed, which a stale cached player
+    /// often causes.
+    StaleCache,
+    /// `--remux-video` couldn't fit the video's codecs into the container.
+    RemuxFailed,
+    /// A path, or a file name in it, was longer than the system allows.
+    PathTooLong,
+    /// yt-dlp itself fell over: a Python traceback, or killed by a signal
+    /// ytd didn't send. A bug in yt-dlp rather than anything about the video.
+    YtDlpCrashed,
+}
+
+impl FailureKind {
+    pub fn hint(self) -> &'static str {
+        match self {
+            FailureKind::SignInRequired => {
+                "YouTube wants a signed-in session. Export your browser's cookies.txt and set it with k on the confirm screen."
+            }
+            FailureKind::FormatUnavailable => {
+                "The chosen format isn't available for this video. Pick another with f on the confirm screen."
+            }
+            FailureKind::FfmpegMissing => {
+                "yt-dlp couldn't find ffmpeg. Install it, or set ffmpeg_location in the config file to the ffmpeg binary or its folder."
+            }
+            FailureKind::StaleCache => {
+                "yt-dlp couldn't decode YouTube's player, often because of a stale cache. Press C to clear yt-dlp's cache, then R to try again. If it keeps failing, update yt-dlp."
+            }
+            FailureKind::RateLimited => {
+                "YouTube is rate limiting requests. Wait a while before trying again, or set sleep_requests and sleep_interval in the config file to slow down."
+            }
+            FailureKind::ThumbnailEmbed => {
+                "The thumbnail couldn't be embedded in this format (opus, ogg and flac need mutagen: pip install mutagen). Pick an m4a format with f on the confirm screen."
+            }
+            FailureKind::RemuxFailed => {
+                "The video's codecs can't go into that container without re-encoding. Set video_container to mkv, which holds any codec, turn off remuxing under s on the confirm screen, or re-encode it yourself with yt-dlp's --recode-video (slow)."
+            }
+            FailureKind::PathTooLong => {
+                "The file's path is longer than the system allows. Use a shorter playlist name, output template or music_dir, or add --restrict-filenames --trim-filenames 100 to the playlist's yt-dlp.conf."
+            }
+            FailureKind::YtDlpCrashed => {
+                "yt-dlp encountered an internal error — try updating it. Press D for diagnostics, then u to update ytd's own copy (a system yt-dlp is updated the way it was installed)."
+            }
+            FailureKind::Restricted => {
+                "The video is blocked here. Its audio-only formats sometimes aren't: turn on the audio fallback under s on the confirm screen."
+            }
+        }
+    }
+}
+
+pub fn classify_error(output: &str) -> Option<FailureKind> {
+    let signed_out = [
+        "Sign in to confirm",
+        "Use --cookies",
+        "members-only",
+        "Join this channel",
+    ];
+    if signed_out.iter().any(|m| output.contains(m)) {
+        return Some(FailureKind::SignInRequired);
+    }
+    if output.contains("ffmpeg not found") || output.contains("--ffmpeg-location") {
+        return Some(FailureKind::FfmpegMissing);
+    }
+    if output.contains("HTTP Error 429") || output.contains("Too Many Requests") {
+        return Some(FailureKind::RateLimited);
+    }
+    let stale_cache = [
+        "Signature extraction failed",
+        "nsig extraction failed",
+        "Unable to extract nsig",
+    ];
+    if stale_cache.iter().any(|m| output.contains(m)) {
+        return Some(FailureKind::StaleCache);
+    }
+    let thumbnail = [
+        "Supported filetypes for thumbnail embedding",
+        "module mutagen was not found",
+    ];
+    if thumbnail.iter().any(|m| output.contains(m)) {
+        return Some(FailureKind::ThumbnailEmbed);
+    }
+    if output.contains("[VideoRemuxer]") && output.contains("Conversion failed") {
+        return Some(FailureKind::RemuxFailed);
+    }
+    let too_long = [
+        "File name too long",
+        // ERROR_FILENAME_EXCED_RANGE, and ERROR_PATH_NOT_FOUND, which is
+        // what Windows says when a path past MAX_PATH has to be created.
+        "[WinError 206]",
+        "[WinError 3]",
+    ];
+    if too_long.iter().any(|m| output.contains(m)) {
+        return Some(FailureKind::PathTooLong);
+    }
+    if output.contains("Requested format is not available") {
+        return Some(FailureKind::FormatUnavailable);
+    }
+    let restricted = [
+        "not made this video available in your country",
+        "blocked it in your country",
+        "not available in your country",
+        "geo restriction",
+    ];
+    if restricted.iter().any(|m| output.contains(m)) {
+        return Some(FailureKind::Restricted);
+    }
+    // Last, because `--verbose` prints a traceback along with ordinary
+    // errors that the checks above explain better.
+    if output.contains("Traceback (most recent call last)") || output.contains(CRASH_MARKER) {
+        return Some(FailureKind::YtDlpCrashed);
+    }
+    None
+}
+
+/// Starts the line the worker logs when yt-dlp dies of a signal, so
+/// `classify_error` can tell a crash from a failed download.
+pub const CRASH_MARKER: &str = "yt-dlp was terminated abnormally";
+
+/// How `status` says yt-dlp died, when it didn't exit on its own: the
+/// signal on Unix, the NTSTATUS crash code (0xC0000005 and the like) on
+/// Windows. SIGINT doesn't count; that's ytd stopping a recording.
+pub fn abnormal_exit(status: ExitStatus) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        const SIGINT: i32 = 2;
+        status
+            .signal()
+            .filter(|&signal| signal != SIGINT)
+            .map(|signal| format!("signal {}", signal))
+    }
+    #[cfg(not(unix))]
+    {
+        status
+            .code()
+            .map(|code| code as u32)
+            .filter(|&code| code >= 0xC000_0000)
+            .map(|code| format!("status {:#010X}", code))
+    }
+}
+
+/// `2.30MiB/s` -> bytes per second.
+pub fn parse_speed(speed: &str) -> Option<f64> {
+    let speed = speed.strip_suffix("/s")?;
+    let split = speed.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = speed.split_at(split);
+    let scale = match unit {
+        "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    number.parse::<f64>().ok().map(|n| n * scale)
+}
+
+/// What a download is doing, for consumers that want structure rather than
+/// yt-dlp's text.
+pub enum DownloadEvent {
+    Progress(Progress),
+    /// yt-dlp started writing this file.
+    File(PathBuf),
+}
+
+/// The most recent lines yt-dlp printed. Older lines are dropped once
+/// `max_lines` is reached, so a long session doesn't grow without bound.
+pub struct OutputLog {
+    /// Each line with its number in `total` order.
+    lines: VecDeque<(usize, String)>,
+    max_lines: usize,
+    /// Lines stored since the last `clear`, dropped ones included. A
+    /// redrawn line counts again, so readers that print as they go see it.
+    total: usize,
+    /// The numbers of the kept `ERROR:` and `WARNING:` lines, oldest first.
+    problems: VecDeque<(usize, Severity)>,
+}
+
+/// How bad a line of yt-dlp's output says things are.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn of(line: &str) -> Option<Severity> {
+        let line = strip_timestamp(line);
+        if line.starts_with("ERROR:") {
+            Some(Severity::Error)
+        } else if line.starts_with("WARNING:") {
+            Some(Severity::Warning)
+        } else {
+            None
+        }
+    }
+}
+
+impl OutputLog {
+    pub fn new(max_lines: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            max_lines: max_lines.max(1),
+            total: 0,
+            problems: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: impl Into<String>) {
+        if self.lines.len() == self.max_lines {
+            if let Some((dropped, _)) = self.lines.pop_front() {
+                if self.problems.front().is_some_and(|&(n, _)| n == dropped) {
+                    self.problems.pop_front();
+                }
+            }
+        }
+        let line = line.into();
+        if let Some(severity) = Severity::of(&line) {
+            self.problems.push_back((self.total, severity));
+        }
+        self.lines.push_back((self.total, line));
+        self.total += 1;
+    }
+
+    /// Replaces the last line if `is_previous` says it's the one being
+    /// redrawn. Returns whether it did.
+    fn replace_last(&mut self, is_previous: impl Fn(&str) -> bool, line: &str) -> bool {
+        match self.lines.back_mut() {
+            Some(last) if is_previous(&last.1) => {
+                if self.problems.back().is_some_and(|&(n, _)| n == last.0) {
+                    self.problems.pop_back();
+                }
+                if let Some(severity) = Severity::of(line) {
+                    self.problems.push_back((self.total, severity));
+                }
+                *last = (self.total, line.to_string());
+                self.total += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The number of the most recent kept `ERROR:` line.
+    pub fn latest_error(&self) -> Option<usize> {
+        self.problems
+            .iter()
+            .rev()
+            .find(|&&(_, severity)| severity == Severity::Error)
+            .map(|&(n, _)| n)
+    }
+
+    /// The most recent kept error line, or failing that warning, to show
+    /// while the tail has moved on.
+    pub fn latest_problem(&self) -> Option<(Severity, &str)> {
+        let number = self
+            .latest_error()
+            .or(self.problems.back().map(|&(n, _)| n))?;
+        let severity = self.problems.iter().find(|&&(n, _)| n == number)?.1;
+        let line = self.lines.iter().find(|(n, _)| *n == number)?;
+        Some((severity, line.1.as_str()))
+    }
+
+    /// How many kept lines came after line `number`, or `None` once it's
+    /// been dropped.
+    pub fn lines_after(&self, number: usize) -> Option<usize> {
+        let at = self.lines.iter().rposition(|(n, _)| *n == number)?;
+        Some(self.lines.len() - 1 - at)
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.lines.back().map(|(_, line)| line.as_str())
+    }
+
+    /// The kept lines, newline-terminated.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (_, line) in &self.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Lines stored after the first `seen` of `total`, for printing output
+    /// as it arrives. Lines already dropped from the buffer are skipped.
+    pub fn since(&self, seen: usize) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .filter(move |(number, _)| *number >= seen)
+            .map(|(_, line)| line.as_str())
+    }
+}
+
+/// The shared state yt-dlp's output is fed into. Cloned into each reader
+/// thread; the UI thread reads the same `Arc`s.
+#[derive(Clone)]
+pub struct OutputSink {
+    pub output: Arc<Mutex<OutputLog>>,
+    /// Number of `[download] Destination:` lines seen, i.e. files yt-dlp has
+    /// started writing.
+    pub files_started: Arc<AtomicUsize>,
+    /// Videos yt-dlp passed over because they were downloaded before, going
+    /// by the file on disk or a download archive.
+    pub skipped: Arc<AtomicUsize>,
+    /// Every file a `[...] Destination:` line named, i.e. the files this run
+    /// wrote, including the intermediates post-processing replaced.
+    pub created: Arc<Mutex<Vec<PathBuf>>>,
+    /// IDs of the videos yt-dlp went on to download, going by
+    /// `[info] <id>: Downloading 1 format(s)`, which a download archive
+    /// gets them added to.
+    pub video_ids: Arc<Mutex<Vec<String>>>,
+    /// Fragments yt-dlp gave up on and left out, so a file may have gaps.
+    pub fragments_skipped: Arc<AtomicUsize>,
+    /// Videos `--match-filter` passed over for being longer than the
+    /// maximum duration.
+    pub too_long: Arc<AtomicUsize>,
+    pub progress: Arc<Mutex<Progress>>,
+    /// Replaced with `********` in every stored line, so a password yt-dlp
+    /// echoes back never reaches the output pane.
+    pub redact: Option<String>,
+    /// Receives a `DownloadEvent` for each progress and destination line.
+    pub events: Option<Sender<DownloadEvent>>,
+    /// Download speeds seen, in bytes per second. The worker clears this
+    /// between playlist items to judge each item's speed on its own.
+    pub speeds: Arc<Mutex<Vec<f64>>>,
+    /// When the download started, with `timestamp_log` on: stored lines get
+    /// a `[+12.3s]` prefix, though they're parsed without it.
+    pub started: Option<Instant>,
+}
+
+/// A stored output line without its `timestamp_log` prefix.
+pub fn strip_timestamp(line: &str) -> &str {
+    line.strip_prefix("[+")
+        .and_then(|rest| rest.split_once("s] "))
+        .filter(|(seconds, _)| seconds.parse::<f64>().is_ok())
+        .map_or(line, |(_, text)| text)
+}
+
+impl OutputSink {
+    pub fn push_line(&self, line: &str) {
+        self.store(line, None);
+    }
+
+    /// Like `push_line`, but replaces `previous` if it is still the last
+    /// stored line, the way a `\r` redraws a terminal line.
+    pub fn overwrite_line(&self, previous: &str, line: &str) {
+        self.store(line, Some(previous));
+    }
+
+    fn redact(&self, line: &str) -> String {
+        match &self.redact {
+            Some(secret) if !secret.is_empty() => line.replace(secret.as_str(), "********"),
+            _ => line.to_string(),
+        }
+    }
+
+    fn store(&self, line: &str, previous: Option<&str>) {
+        let line = self.redact(line);
+        let line = line.as_str();
+        if let Some(path) = line.strip_prefix("[download] Destination:") {
+            self.files_started.fetch_add(1, Ordering::SeqCst);
+            self.send(DownloadEvent::File(PathBuf::from(strip_verbatim(
+                path.trim(),
+            ))));
+        }
+        // Splitting by chapters names its tracks as
+        // "[SplitChapters] Chapter 001; Destination: ...".
+        if let Some((_, path)) = line.strip_prefix('[').and_then(|l| {
+            l.split_once("] Destination: ")
+                .or_else(|| l.split_once("; Destination: "))
+        }) {
+            self.created
+                .lock()
+                .unwrap()
+                .push(PathBuf::from(strip_verbatim(path.trim())));
+        }
+        // "[info] Writing video metadata as JSON to: ...", or "playlist
+        // metadata"; "is already present" means an earlier run's, left alone.
+        if let Some((_, path)) = line
+            .strip_prefix("[info] Writing ")
+            .and_then(|l| l.split_once(" metadata as JSON to: "))
+        {
+            self.created
+                .lock()
+                .unwrap()
+                .push(PathBuf::from(strip_verbatim(path.trim())));
+        }
+        // Uploaded subtitles and auto captions alike; one already present
+        // is reported as "Video subtitle ... is already present" instead.
+        if let Some(path) = line.strip_prefix("[info] Writing video subtitles to: ") {
+            self.created
+                .lock()
+                .unwrap()
+                .push(PathBuf::from(strip_verbatim(path.trim())));
+        }
+        if let Some((id, rest)) = line
+            .strip_prefix("[info] ")
+            .and_then(|l| l.split_once(": Downloading "))
+        {
+            if rest.contains(" format(s)") && !id.contains(' ') {
+                self.video_ids.lock().unwrap().push(id.to_string());
+            }
+        }
+        if line.starts_with("[download]")
+            && (line.ends_with("has already been downloaded")
+                || line.ends_with("has already been recorded in the archive"))
+        {
+            self.skipped.fetch_add(1, Ordering::SeqCst);
+        }
+        // "[download] Title does not pass filter (duration <? 600), skipping ..";
+        // the duration is the only filter ytd sets.
+        if line.starts_with("[download]") && line.contains(" does not pass filter ") {
+            self.too_long.fetch_add(1, Ordering::SeqCst);
+        }
+        if line.contains("fragment not found; Skipping fragment") {
+            self.fragments_skipped.fetch_add(1, Ordering::SeqCst);
+        }
+        if let Some(progress) = parse_progress_line(line) {
+            if let Some(speed) = progress.speed.as_deref().and_then(parse_speed) {
+                self.speeds.lock().unwrap().push(speed);
+            }
+            *self.progress.lock().unwrap() = progress.clone();
+            self.send(DownloadEvent::Progress(progress));
+        }
+        let stored = match self.started {
+            Some(started) => format!("[+{:.1}s] {}", started.elapsed().as_secs_f64(), line),
+            None => line.to_string(),
+        };
+        let mut out = self.output.lock().unwrap();
+        // Only if the other stream hasn't written since.
+        let replaced = previous.is_some_and(|p| {
+            let p = self.redact(p);
+            out.replace_last(|last| strip_timestamp(last) == p, &stored)
+        });
+        if !replaced {
+            out.push(stored);
+        }
+    }
+}
+
+impl OutputSink {
+    fn send(&self, event: DownloadEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+}
+
+/// Removes terminal escape sequences (colours, cursor movement, window
+/// titles) from a line of output. A `\r` inside the line resets it, as on a
+/// terminal, so only the text after the last one is kept.
+pub fn strip_ansi(line: &str) -> String {
+    let line = line
+        .rsplit('\r')
+        .find(|segment| !segment.is_empty())
+        .unwrap_or("");
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters, then a final byte in @..~.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: runs to BEL or ESC \.
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // Anything else is a two-character escape.
+            _ => {}
+        }
+    }
+    out
+}
+
+fn spawn_output_reader<R: Read + Send + 'static>(
+    source: R,
+    sink: OutputSink,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        // Progress and ffmpeg's status line are redrawn with `\r`, so a
+        // line ended by `\r` is replaced by whatever comes next.
+        let mut line = Vec::new();
+        let mut redrawn: Option<String> = None;
+        let emit = |line: &[u8], redrawn: &Option<String>| {
+            let text = strip_ansi(&String::from_utf8_lossy(line));
+            match redrawn {
+                Some(previous) => sink.overwrite_line(previous, &text),
+                None => sink.push_line(&text),
+            }
+            text
+        };
+        for byte in BufReader::new(source).bytes().map_while(Result::ok) {
+            if byte != b'\n' && byte != b'\r' {
+                line.push(byte);
+                continue;
+            }
+            if !line.is_empty() {
+                redrawn = Some(emit(&line, &redrawn));
+                line.clear();
+            }
+            if byte == b'\n' {
+                redrawn = None;
+            }
+        }
+        if !line.is_empty() {
+            emit(&line, &redrawn);
+        }
+    })
+}
+
+/// Pauses or continues a running child.
+///
+/// On Unix this sends SIGSTOP/SIGCONT, so the download picks up exactly where
+/// it stopped. Only yt-dlp itself is stopped; an ffmpeg it already started
+/// for post-processing keeps running. Windows has no equivalent signal, so
+/// pausing there kills the child instead, and the worker re-runs the item on
+/// resume, relying on yt-dlp continuing its `.part` file.
+pub fn set_child_paused(child: &mut Child, paused: bool) -> io::Result<()> {
+    if cfg!(unix) {
+        let signal = if paused { "-STOP" } else { "-CONT" };
+        let status = Command::new("kill")
+            .args([signal, &child.id().to_string()])
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("kill {} failed", signal)));
+        }
+        Ok(())
+    } else if paused {
+        child.kill()
+    } else {
+        Ok(())
+    }
+}
+
+/// Asks a child to stop the way Ctrl+C would. yt-dlp takes SIGINT during a
+/// live recording as "stop here" and still finishes the file. Windows has
+/// no such signal, so there the child is killed.
+pub fn interrupt_child(child: &mut Child) -> io::Result<()> {
+    if cfg!(unix) {
+        let status = Command::new("kill")
+            .args(["-INT", &child.id().to_string()])
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other("kill -INT failed"));
+        }
+        Ok(())
+    } else {
+        child.kill()
+    }
+}
+
+/// Runs a single yt-dlp invocation to completion, streaming its output into
+/// `sink`. The child is parked in `child_slot` while it runs so the UI
+/// thread can kill it.
+pub fn run_yt_dlp(
+    args: &[String],
+    sink: &OutputSink,
+    child_slot: &Arc<Mutex<Option<Child>>>,
+) -> io::Result<ExitStatus> {
+    let mut child = Command::new(yt_dlp())
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let readers: Vec<_> = [
+        child
+            .stdout
+            .take()
+            .map(|s| spawn_output_reader(s, sink.clone())),
+        child
+            .stderr
+            .take()
+            .map(|s| spawn_output_reader(s, sink.clone())),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    *child_slot.lock().unwrap() = Some(child);
+    let status = loop {
+        if let Some(c) = child_slot.lock().unwrap().as_mut() {
+            match c.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) => {}
+                Err(_) => break ExitStatus::default(),
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    child_slot.lock().unwrap().take();
+
+    for r in readers {
+        let _ = r.join();
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink() -> OutputSink {
+        OutputSink {
+            output: Arc::new(Mutex::new(OutputLog::new(1000))),
+            files_started: Arc::new(AtomicUsize::new(0)),
+            skipped: Arc::new(AtomicUsize::new(0)),
+            created: Arc::new(Mutex::new(Vec::new())),
+            video_ids: Arc::new(Mutex::new(Vec::new())),
+            fragments_skipped: Arc::new(AtomicUsize::new(0)),
+            too_long: Arc::new(AtomicUsize::new(0)),
+            progress: Arc::new(Mutex::new(Progress::default())),
+            redact: None,
+            events: None,
+            speeds: Arc::new(Mutex::new(Vec::new())),
+            started: None,
+        }
+    }
+
+    #[test]
+    fn video_id_reads_watch_short_and_shorts_urls() {
+        let id = Some("dQw4w9WgXcQ".to_string());
+        assert_eq!(video_id("https://youtu.be/dQw4w9WgXcQ?si=abc"), id);
+        assert_eq!(
+            video_id("https://www.youtube.com/watch?list=PL1&v=dQw4w9WgXcQ"),
+            id
+        );
+        assert_eq!(video_id("https://youtube.com/shorts/dQw4w9WgXcQ"), id);
+        assert_eq!(video_id("https://www.youtube.com/playlist?list=PL1"), None);
+        assert_eq!(video_id("https://example.com/watch?v=dQw4w9WgXcQ"), None);
+    }
+
+    #[test]
+    fn normalize_url_expands_short_links() {
+        assert_eq!(
+            normalize_url("https://youtu.be/dQw4w9WgXcQ?si=abc123", false),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            normalize_url("https://youtu.be/dQw4w9WgXcQ?si=abc123&t=42", true),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"
+        );
+    }
+
+    #[test]
+    fn normalize_url_strips_tracking_from_watch_urls() {
+        assert_eq!(
+            normalize_url(
+                "https://www.youtube.com/watch?v=abc&feature=share&si=xyz&t=1m5s#comments",
+                false
+            ),
+            "https://www.youtube.com/watch?v=abc"
+        );
+        assert_eq!(
+            normalize_url(
+                "https://m.youtube.com/watch?feature=youtu.be&v=abc&t=65",
+                true
+            ),
+            "https://m.youtube.com/watch?v=abc&t=65"
+        );
+    }
+
+    #[test]
+    fn normalize_url_keeps_playlists() {
+        assert_eq!(
+            normalize_url(
+                "https://www.youtube.com/playlist?list=PLabc&si=xyz&pp=iAQB",
+                false
+            ),
+            "https://www.youtube.com/playlist?list=PLabc"
+        );
+        assert_eq!(
+            normalize_url(
+                "https://www.youtube.com/watch?v=abc&list=PLabc&index=3",
+                false
+            ),
+            "https://www.youtube.com/watch?v=abc&list=PLabc"
+        );
+    }
+
+    #[test]
+    fn normalize_url_leaves_other_sites_alone() {
+        let url = "https://example.com/track?id=5&si=keep";
+        assert_eq!(normalize_url(url, false), url);
+        assert_eq!(
+            normalize_url("https://www.youtube.com/@someone/videos", false),
+            "https://www.youtube.com/@someone/videos"
+        );
+    }
+
+    #[test]
+    fn size_estimate_sums_known_sizes() {
+        assert_eq!(
+            parse_size_estimate("1000\nNA\n2500.0\n\n"),
+            SizeEstimate {
+                bytes: 3500,
+                items: 3,
+                unknown: 1
+            }
+        );
+        assert_eq!(parse_size_estimate(""), SizeEstimate::default());
+    }
+
+    #[test]
+    fn latest_items_follow_the_listing_order() {
+        assert_eq!(
+            latest_items_selector("https://www.youtube.com/@someone/videos", 10),
+            ":10"
+        );
+        assert_eq!(
+            latest_items_selector("https://www.youtube.com/playlist?list=UUabc", 5),
+            ":5"
+        );
+        assert_eq!(
+            latest_items_selector("https://www.youtube.com/playlist?list=PLabc", 10),
+            "-10:"
+        );
+        // A video opened from a playlist still means the playlist.
+        assert_eq!(
+            latest_items_selector("https://www.youtube.com/watch?v=x&list=PLabc", 3),
+            "-3:"
+        );
+    }
+
+    #[test]
+    fn strip_ansi_removes_colours() {
+        assert_eq!(
+            strip_ansi("\x1b[0;94m[download]\x1b[0m  42.3% of \x1b[0;96m3.45MiB\x1b[0m"),
+            "[download]  42.3% of 3.45MiB"
+        );
+        assert_eq!(
+            strip_ansi("\x1b[0;31mERROR:\x1b[0m [youtube] abc: Video unavailable"),
+            "ERROR: [youtube] abc: Video unavailable"
+        );
+    }
+
+    #[test]
+    fn strip_ansi_removes_cursor_and_title_sequences() {
+        assert_eq!(
+            strip_ansi("\x1b[K\x1b[1A[download] done"),
+            "[download] done"
+        );
+        assert_eq!(strip_ansi("\x1b]0;yt-dlp 12%\x07text"), "text");
+        assert_eq!(strip_ansi("\x1b]0;title\x1b\\text"), "text");
+    }
+
+    #[test]
+    fn strip_ansi_keeps_text_after_carriage_return() {
+        assert_eq!(
+            strip_ansi("[download]  10.0% of 1MiB\r[download]  20.0% of 1MiB"),
+            "[download]  20.0% of 1MiB"
+        );
+        assert_eq!(strip_ansi("finished\r"), "finished");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn output_reader_updates_progress_per_carriage_return() {
+        let sink = sink();
+        let output = b"[youtube] abc: Downloading webpage\n\
+            [download]  10.0% of 1MiB\r[download]  55.5% of 1MiB\r[download] 100% of 1MiB\n\
+            [ExtractAudio] Destination: a.m4a\n";
+        spawn_output_reader(&output[..], sink.clone())
+            .join()
+            .unwrap();
+        assert_eq!(
+            sink.output.lock().unwrap().text(),
+            "[youtube] abc: Downloading webpage\n[download] 100% of 1MiB\n[ExtractAudio] Destination: a.m4a\n"
+        );
+        assert_eq!(sink.progress.lock().unwrap().percent, Some(100.0));
+    }
+
+    #[test]
+    fn timestamps_prefix_stored_lines_but_not_parsing() {
+        let sink = OutputSink {
+            started: Some(Instant::now()),
+            ..sink()
+        };
+        sink.push_line("[download] Destination: /music/Song.webm");
+        sink.push_line("[download]  10.0% of 1MiB");
+        sink.overwrite_line("[download]  10.0% of 1MiB", "[download]  20.0% of 1MiB");
+        assert_eq!(sink.files_started.load(Ordering::SeqCst), 1);
+        assert_eq!(sink.progress.lock().unwrap().percent, Some(20.0));
+        let text = sink.output.lock().unwrap().text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("[+0.0s] [download] Destination:"));
+        assert_eq!(strip_timestamp(lines[1]), "[download]  20.0% of 1MiB");
+        assert_eq!(strip_timestamp("[+x] text"), "[+x] text");
+    }
+
+    #[test]
+    fn written_info_json_files_count_as_created() {
+        let sink = sink();
+        sink.push_line("[info] Writing video metadata as JSON to: /music/Song.info.json");
+        sink.push_line("[info] Video metadata is already present");
+        sink.push_line("[info] Writing video subtitles to: /music/Song.en.vtt");
+        sink.push_line("[info] Video subtitle de.vtt is already present");
+        sink.push_line("[download] Destination: /music/Song.webm");
+        assert_eq!(
+            *sink.created.lock().unwrap(),
+            [
+                PathBuf::from("/music/Song.info.json"),
+                PathBuf::from("/music/Song.en.vtt"),
+                PathBuf::from("/music/Song.webm")
+            ]
+        );
+    }
+
+    #[test]
+    fn downloaded_video_ids_are_recorded() {
+        let sink = sink();
+        sink.push_line("[info] dQw4w9WgXcQ: Downloading 1 format(s): 251");
+        sink.push_line("[info] Writing video metadata as JSON to: /music/Song.info.json");
+        sink.push_line("[download] Other has already been recorded in the archive");
+        assert_eq!(*sink.video_ids.lock().unwrap(), ["dQw4w9WgXcQ"]);
+    }
+
+    #[test]
+    fn videos_over_the_duration_filter_are_counted() {
+        let sink = sink();
+        sink.push_line(
+            "[download] Full Album Mix does not pass filter (duration <? 600), skipping ..",
+        );
+        sink.push_line("[download] Destination: /music/Song.webm");
+        assert_eq!(sink.too_long.load(Ordering::SeqCst), 1);
+        assert_eq!(sink.files_started.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn overwrite_line_replaces_only_the_redrawn_line() {
+        let sink = sink();
+        sink.push_line("[youtube] abc: Downloading webpage");
+        sink.push_line("[download]  10.0% of 1MiB");
+        sink.overwrite_line("[download]  10.0% of 1MiB", "[download]  20.0% of 1MiB");
+        assert_eq!(
+            sink.output.lock().unwrap().text(),
+            "[youtube] abc: Downloading webpage\n[download]  20.0% of 1MiB\n"
+        );
+
+        // Another line got in between, so nothing is replaced.
+        sink.push_line("WARNING: from stderr");
+        sink.overwrite_line("[download]  20.0% of 1MiB", "[download]  30.0% of 1MiB");
+        assert!(sink.output.lock().unwrap().text().ends_with(
+            "[download]  20.0% of 1MiB\nWARNING: from stderr\n[download]  30.0% of 1MiB\n"
+        ));
+    }
+
+    #[test]
+    fn output_log_keeps_only_the_last_lines() {
+        let mut log = OutputLog::new(3);
+        for line in ["a", "b", "c", "d", "e"] {
+            log.push(line);
+        }
+        assert_eq!(log.text(), "c\nd\ne\n");
+        assert_eq!(log.total(), 5);
+        assert_eq!(log.since(3).collect::<Vec<_>>(), ["d", "e"]);
+        // Already dropped lines are skipped rather than repeated.
+        assert_eq!(log.since(0).collect::<Vec<_>>(), ["c", "d", "e"]);
+
+        assert!(log.replace_last(|l| l == "e", "f"));
+        assert_eq!(log.since(5).collect::<Vec<_>>(), ["f"]);
+        assert!(!log.replace_last(|l| l == "e", "g"));
+    }
+
+    #[test]
+    fn output_log_remembers_where_the_errors_are() {
+        let mut log = OutputLog::new(3);
+        log.push("[download] Destination: a.m4a");
+        log.push("ERROR: [youtube] abc: Video unavailable");
+        log.push("[+3.0s] WARNING: [youtube] Falling back to generic n function");
+        assert_eq!(log.latest_error(), Some(1));
+        assert_eq!(
+            log.latest_problem(),
+            Some((Severity::Error, "ERROR: [youtube] abc: Video unavailable"))
+        );
+        log.push("[download]  50.0% of 3.00MiB");
+        assert_eq!(log.lines_after(1), Some(2));
+
+        // Once the error is dropped the warning is the one to show.
+        log.push("[download] 100% of 3.00MiB");
+        assert_eq!(log.latest_error(), None);
+        assert_eq!(log.lines_after(1), None);
+        assert_eq!(
+            log.latest_problem().map(|(s, _)| s),
+            Some(Severity::Warning)
+        );
+
+        assert!(log.replace_last(|l| l.contains("100%"), "ERROR: Postprocessing failed"));
+        assert_eq!(log.latest_error(), Some(5));
+    }
+}
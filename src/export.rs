@@ -0,0 +1,128 @@
+//! Writing downloads out for other tools: an M3U of the files, or the
+//! source URLs one per line, which `ytd --url` takes back in.
+
+use crate::history::HistoryEntry;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    /// `#EXTM3U` and the downloaded files' paths.
+    M3u,
+    /// The URLs that downloaded successfully.
+    UrlList,
+}
+
+impl ExportFormat {
+    /// M3U for `.m3u`/`.m3u8`, a URL list for anything else.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8") => {
+                ExportFormat::M3u
+            }
+            _ => ExportFormat::UrlList,
+        }
+    }
+}
+
+/// How an export went.
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    /// Lines written, not counting the M3U header.
+    pub written: usize,
+    /// Files the history names that are gone, left out of an M3U.
+    pub missing: Vec<PathBuf>,
+}
+
+/// Writes `entries` to `out`, each file or URL once, oldest first.
+pub fn export_playlist(
+    entries: &[HistoryEntry],
+    format: ExportFormat,
+    out: &Path,
+) -> io::Result<ExportSummary> {
+    let mut summary = ExportSummary::default();
+    let mut lines: Vec<String> = Vec::new();
+    let mut add = |line: String| {
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    };
+    match format {
+        ExportFormat::M3u => {
+            for file in entries.iter().flat_map(|e| &e.files) {
+                if file.is_file() {
+                    add(file.display().to_string());
+                } else if !summary.missing.contains(file) {
+                    summary.missing.push(file.clone());
+                }
+            }
+        }
+        ExportFormat::UrlList => {
+            for entry in entries.iter().filter(|e| e.success) {
+                add(entry.url.clone());
+            }
+        }
+    }
+    summary.written = lines.len();
+
+    let mut contents = String::new();
+    if format == ExportFormat::M3u {
+        contents.push_str("#EXTM3U\n");
+    }
+    for line in lines {
+        contents.push_str(&line);
+        contents.push('\n');
+    }
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(out, contents)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, success: bool, files: Vec<PathBuf>) -> HistoryEntry {
+        HistoryEntry {
+            url: url.to_string(),
+            playlist: "Mix".to_string(),
+            timestamp: 0,
+            success,
+            files,
+        }
+    }
+
+    #[test]
+    fn exports_files_and_urls_once_each_skipping_missing_files() {
+        let dir = std::env::temp_dir().join(format!("ytd-export-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let song = dir.join("Song.m4a");
+        std::fs::write(&song, b"audio").unwrap();
+        let gone = dir.join("Gone.m4a");
+        let entries = [
+            entry("https://a", true, vec![song.clone(), gone.clone()]),
+            entry("https://b", false, Vec::new()),
+            entry("https://a", true, vec![song.clone()]),
+        ];
+
+        let m3u = dir.join("Mix.m3u8");
+        assert_eq!(ExportFormat::for_path(&m3u), ExportFormat::M3u);
+        let summary = export_playlist(&entries, ExportFormat::M3u, &m3u).unwrap();
+        assert_eq!(summary.written, 1);
+        assert_eq!(summary.missing, vec![gone]);
+        assert_eq!(
+            std::fs::read_to_string(&m3u).unwrap(),
+            format!("#EXTM3U\n{}\n", song.display())
+        );
+
+        let urls = dir.join("urls.txt");
+        assert_eq!(ExportFormat::for_path(&urls), ExportFormat::UrlList);
+        export_playlist(&entries, ExportFormat::UrlList, &urls).unwrap();
+        assert_eq!(std::fs::read_to_string(&urls).unwrap(), "https://a\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
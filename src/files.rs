@@ -0,0 +1,554 @@
+//! Finding, cleaning up and moving the files a download left on disk.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What yt-dlp's `--extract-audio` can leave behind, depending on the
+/// source format and whether free formats are preferred.
+const AUDIO_EXTENSIONS: &[&str] = &["m4a", "opus", "ogg", "webm", "mp3", "aac", "flac", "wav"];
+
+pub fn is_audio_file(path: &Path) -> bool {
+    has_extension(path, AUDIO_EXTENSIONS)
+}
+
+/// What a video download ends up as: mp4 from the merge, or the source
+/// container for a single-file format.
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "webm", "mov"];
+
+pub fn is_video_file(path: &Path) -> bool {
+    has_extension(path, VIDEO_EXTENSIONS)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Characters that are invalid, or misbehave, in a file name on one of the
+/// common filesystems.
+pub fn is_reserved_char(c: char) -> bool {
+    matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control()
+}
+
+/// `name` with reserved characters replaced by `_`, for file names made up
+/// from a playlist name.
+pub fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if is_reserved_char(c) { '_' } else { c })
+        .collect()
+}
+
+/// Every regular file under `dir`, at any depth. Symlinked directories are
+/// not followed, so the walk stays inside `dir`.
+pub fn walk_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => pending.push(entry.path()),
+                Ok(t) if t.is_file() => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+    files
+}
+
+/// Whether `path` is one of yt-dlp's working files: a `.part` download
+/// (or one of its fragments), a `.ytdl` resume state, or a `.temp`
+/// file a post-processor writes before renaming it.
+fn is_partial(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.ends_with(".part")
+        || name.contains(".part-Frag")
+        || name.ends_with(".ytdl")
+        || name.ends_with(".temp")
+        || name.contains(".temp.")
+}
+
+/// Deletes what an interrupted yt-dlp leaves under `dir`: its working files
+/// plus any of `created` (this run's files) that are still zero bytes long.
+/// Other empty files are left alone, since the run didn't make them.
+/// Returns how many files were removed.
+pub fn cleanup_partials(dir: &Path, created: &[PathBuf]) -> usize {
+    walk_files(dir)
+        .into_iter()
+        .filter(|path| {
+            is_partial(path)
+                || (created.contains(path) && std::fs::metadata(path).is_ok_and(|m| m.len() == 0))
+        })
+        .filter(|path| std::fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// Moves one file, falling back to copy + delete when `rename` can't cross
+/// filesystems (the staging dir usually lives on a tmpfs).
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Moves every file under `staging` to the same relative path under
+/// `target`, creating directories as needed, and removes `staging`
+/// afterwards. A file already at the destination is never replaced: the
+/// staged one stays in `staging`, which is then kept, and is returned.
+/// yt-dlp's working files, say from an earlier cancelled run, never move.
+pub fn move_staged_files(staging: &Path, target: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut kept = Vec::new();
+    for from in walk_files(staging) {
+        if is_partial(&from) {
+            continue;
+        }
+        let relative = from.strip_prefix(staging).map_err(io::Error::other)?;
+        let to = target.join(relative);
+        if to.exists() {
+            kept.push(from);
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_file(&from, &to)?;
+    }
+    if kept.is_empty() {
+        std::fs::remove_dir_all(staging)?;
+    }
+    Ok(kept)
+}
+
+/// What ytd writes next to a track, which moves along with it.
+const COMPANION_SUFFIXES: &[&str] = &[
+    ".lrc",
+    ".transcript.txt",
+    ".nfo",
+    ".chapters.txt",
+    ".preview.opus",
+];
+
+fn is_companion(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    COMPANION_SUFFIXES
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+/// A tag value made safe to be one folder name on any system.
+fn folder_name(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| {
+            if c.is_control() || r#"/\:*?"<>|"#.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    // Windows drops trailing dots and spaces, and `..` would climb out.
+    cleaned.trim().trim_end_matches(['.', ' ']).to_string()
+}
+
+/// `template` with each `{tag}` replaced by the file's tag. A missing tag
+/// becomes "Unknown Artist", "Unknown Album" and so on.
+fn tag_folder(template: &str, tags: &HashMap<String, String>) -> PathBuf {
+    let mut folder = PathBuf::new();
+    for part in template.split('/') {
+        let mut name = String::new();
+        let mut rest = part;
+        while let Some((before, after)) = rest.split_once('{') {
+            let Some((key, after)) = after.split_once('}') else {
+                break;
+            };
+            name.push_str(before);
+            let key = key.trim().to_lowercase();
+            let value = tags
+                .get(&key)
+                .map(|v| folder_name(v))
+                .filter(|v| !v.is_empty());
+            name.push_str(&value.unwrap_or_else(|| {
+                let words: Vec<String> = key
+                    .split('_')
+                    .map(|w| {
+                        let mut chars = w.chars();
+                        chars
+                            .next()
+                            .map(|c| c.to_uppercase().chain(chars).collect())
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                format!("Unknown {}", words.join(" "))
+            }));
+            rest = after;
+        }
+        name.push_str(rest);
+        if !name.trim().is_empty() {
+            folder.push(name.trim());
+        }
+    }
+    folder
+}
+
+/// Moves the audio files among `files`, which are under `dir`, into folders
+/// of `dir` named by `template` from their own tags (as `tags_of` reads
+/// them), e.g. `{artist}/{album}`, with their lyrics, NFO and other
+/// companions. Anything else in `dir` is left alone. A file already in
+/// place, or whose tags can't be read, stays where it is, as does one whose
+/// new name is taken. Folders left empty are removed. Returns what moved
+/// where; only fails outright when tags can't be read at all, i.e. ffmpeg
+/// is missing.
+pub fn organize_by_tags(
+    dir: &Path,
+    files: &[PathBuf],
+    template: &str,
+    tags_of: impl Fn(&Path) -> io::Result<HashMap<String, String>>,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moves = Vec::new();
+    let mut tracks: Vec<PathBuf> = files
+        .iter()
+        .filter(|p| p.starts_with(dir) && is_audio_file(p) && !is_companion(p))
+        .cloned()
+        .collect();
+    tracks.sort();
+    for from in tracks {
+        let tags = match tags_of(&from) {
+            Ok(tags) => tags,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(e),
+            Err(_) => continue,
+        };
+        let (Some(name), Some(stem)) = (from.file_name(), from.file_stem()) else {
+            continue;
+        };
+        let to = dir.join(tag_folder(template, &tags)).join(name);
+        if to == from || to.exists() {
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_file(&from, &to)?;
+        let stem = stem.to_string_lossy().into_owned();
+        moves.push((from.clone(), to.clone()));
+        for suffix in COMPANION_SUFFIXES {
+            let companion = from.with_file_name(format!("{}{}", stem, suffix));
+            let moved = to.with_file_name(format!("{}{}", stem, suffix));
+            if companion.is_file() && !moved.exists() {
+                move_file(&companion, &moved)?;
+                moves.push((companion, moved));
+            }
+        }
+        let mut parent = from.parent();
+        while let Some(folder) = parent.filter(|p| *p != dir) {
+            // Only succeeds once the folder is empty.
+            if std::fs::remove_dir(folder).is_err() {
+                break;
+            }
+            parent = folder.parent();
+        }
+    }
+    Ok(moves)
+}
+
+/// Deletes `files`, skipping any that are already gone, then takes
+/// `video_ids` out of the download `archive`, if there is one, so the
+/// videos aren't skipped as already downloaded next time. Stops at the
+/// first file that can't be removed, leaving the archive as it was.
+pub fn undo_download(
+    files: &[PathBuf],
+    archive: Option<&Path>,
+    video_ids: &[String],
+) -> io::Result<()> {
+    for file in files {
+        match std::fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    let Some(archive) = archive.filter(|_| !video_ids.is_empty()) else {
+        return Ok(());
+    };
+    let text = match std::fs::read_to_string(archive) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        result => result?,
+    };
+    // Lines are "<extractor> <id>".
+    let kept: String = text
+        .lines()
+        .filter(|line| {
+            line.split_once(' ')
+                .is_none_or(|(_, id)| !video_ids.iter().any(|v| v == id))
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+    if kept != text {
+        std::fs::write(archive, kept)?;
+    }
+    Ok(())
+}
+
+/// Lowercase words with punctuation dropped, so "Song (Official Video)"
+/// and "song - official video" compare equal.
+fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(ca != cb))
+                .min(row[j] + 1)
+                .min(above + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Whether a yt-dlp `--download-archive` file lists this YouTube video.
+pub fn archive_contains(archive: &Path, video_id: &str) -> bool {
+    std::fs::read_to_string(archive).is_ok_and(|text| {
+        text.lines()
+            .any(|line| line.split_once(' ') == Some(("youtube", video_id)))
+    })
+}
+
+/// Files directly in `dir` whose name, minus the extension, is close to
+/// `title`: within a fifth of its length in edits, and at least two.
+pub fn find_similar(dir: &Path, title: &str) -> Vec<String> {
+    let wanted = normalize_title(title);
+    let threshold = (wanted.chars().count() / 5).max(2);
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut similar: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.') && !name.ends_with(".part"))
+        .filter(|name| {
+            let stem = Path::new(name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            levenshtein(&normalize_title(&stem), &wanted) <= threshold
+        })
+        .collect();
+    similar.sort();
+    similar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ytd-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn relative_files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = walk_files(dir)
+            .iter()
+            .map(|p| p.strip_prefix(dir).unwrap().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn files_are_organized_by_their_tags() {
+        let dir = scratch_dir("organize-test");
+        std::fs::create_dir_all(dir.join("Uploader/Singles")).unwrap();
+        for name in [
+            "Uploader/Singles/Kids.m4a",
+            "Uploader/Singles/Kids.lrc",
+            "Uploader/Singles/Kids.preview.opus",
+            "Untagged.opus",
+            "Back in Black.m4a",
+            "Broken.m4a",
+            "cover.jpg",
+        ] {
+            std::fs::write(dir.join(name), b"x").unwrap();
+        }
+        let tags_of = |path: &Path| -> io::Result<HashMap<String, String>> {
+            let tags: &[(&str, &str)] = match path.file_stem().unwrap().to_str().unwrap() {
+                "Kids" => &[("artist", "MGMT"), ("album", "Oracular Spectacular")],
+                "Back in Black" => &[("artist", "AC/DC"), ("album", "..")],
+                "Broken" => return Err(io::Error::other("unreadable")),
+                _ => &[],
+            };
+            Ok(tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect())
+        };
+
+        // Untagged.opus is from an earlier run, so it isn't touched.
+        let created: Vec<PathBuf> = [
+            "Uploader/Singles/Kids.m4a",
+            "Back in Black.m4a",
+            "Broken.m4a",
+            "cover.jpg",
+        ]
+        .iter()
+        .map(|name| dir.join(name))
+        .collect();
+        let moves = organize_by_tags(&dir, &created, "{artist}/{album}", tags_of).unwrap();
+        assert_eq!(moves.len(), 4);
+        assert_eq!(
+            relative_files(&dir),
+            [
+                "AC_DC/Unknown Album/Back in Black.m4a",
+                "Broken.m4a",
+                "MGMT/Oracular Spectacular/Kids.lrc",
+                "MGMT/Oracular Spectacular/Kids.m4a",
+                "MGMT/Oracular Spectacular/Kids.preview.opus",
+                "Untagged.opus",
+                "cover.jpg",
+            ]
+        );
+        assert!(!dir.join("Uploader").exists());
+        // Running it again has nothing left to move.
+        let moved: Vec<PathBuf> = moves.into_iter().map(|(_, to)| to).collect();
+        assert!(organize_by_tags(&dir, &moved, "{artist}/{album}", tags_of)
+            .unwrap()
+            .is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn undo_takes_the_videos_out_of_the_archive() {
+        let dir = scratch_dir("undo-test");
+        let song = dir.join("Song.m4a");
+        let archive = dir.join("archive.txt");
+        std::fs::write(&song, b"audio").unwrap();
+        std::fs::write(&archive, "youtube old\nyoutube new\n").unwrap();
+
+        undo_download(
+            std::slice::from_ref(&song),
+            Some(&archive),
+            &["new".to_string()],
+        )
+        .unwrap();
+        assert!(!song.exists());
+        assert_eq!(std::fs::read_to_string(&archive).unwrap(), "youtube old\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn safe_file_name_replaces_reserved_characters() {
+        assert_eq!(safe_file_name("Rock/Metal: Live?"), "Rock_Metal_ Live_");
+        assert_eq!(safe_file_name("Chill Mix"), "Chill Mix");
+    }
+
+    #[test]
+    fn cleanup_partials_removes_only_leftovers() {
+        let dir = scratch_dir("cleanup-test");
+        std::fs::write(dir.join("Song.m4a"), b"audio").unwrap();
+        std::fs::write(dir.join("Other.m4a.part"), b"half").unwrap();
+        std::fs::write(dir.join("Other.m4a.ytdl"), b"state").unwrap();
+        std::fs::write(dir.join("Other.temp.m4a"), b"tagging").unwrap();
+        std::fs::write(dir.join("Empty.m4a"), b"").unwrap();
+        std::fs::write(dir.join("Placeholder"), b"").unwrap();
+        std::fs::create_dir_all(dir.join("sub.part")).unwrap();
+
+        assert_eq!(cleanup_partials(&dir, &[dir.join("Empty.m4a")]), 4);
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["Placeholder", "Song.m4a", "sub.part"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cleanup_partials_on_missing_dir_removes_nothing() {
+        assert_eq!(
+            cleanup_partials(Path::new("/nonexistent/ytd-cleanup"), &[]),
+            0
+        );
+    }
+
+    #[test]
+    fn move_staged_files_keeps_nested_paths() {
+        let staging = scratch_dir("staging-test");
+        let target = scratch_dir("staging-target");
+        std::fs::create_dir_all(staging.join("Artist/Album")).unwrap();
+        std::fs::write(staging.join("Artist/Album/Song.m4a"), b"audio").unwrap();
+        std::fs::write(staging.join("Loose.m4a"), b"audio").unwrap();
+
+        move_staged_files(&staging, &target).unwrap();
+        assert!(!staging.exists());
+        assert_eq!(
+            relative_files(&target),
+            ["Artist/Album/Song.m4a", "Loose.m4a"]
+        );
+
+        std::fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn move_staged_files_never_replaces_a_file() {
+        let staging = scratch_dir("staging-collide");
+        let target = scratch_dir("staging-collide-target");
+        std::fs::write(staging.join("Song.m4a"), b"new").unwrap();
+        std::fs::write(staging.join("Other.m4a"), b"new").unwrap();
+        std::fs::write(target.join("Song.m4a"), b"edited").unwrap();
+
+        let kept = move_staged_files(&staging, &target).unwrap();
+        assert_eq!(kept, [staging.join("Song.m4a")]);
+        assert_eq!(std::fs::read(target.join("Song.m4a")).unwrap(), b"edited");
+        assert_eq!(std::fs::read(target.join("Other.m4a")).unwrap(), b"new");
+        assert_eq!(relative_files(&staging), ["Song.m4a"]);
+
+        std::fs::remove_dir_all(&staging).unwrap();
+        std::fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn move_staged_files_leaves_partials_behind() {
+        let staging = scratch_dir("staging-partials");
+        let target = scratch_dir("staging-partials-target");
+        std::fs::write(staging.join("Song.m4a"), b"audio").unwrap();
+        std::fs::write(staging.join("Cancelled.webm.part"), b"half").unwrap();
+        std::fs::write(staging.join("Cancelled.webm.ytdl"), b"state").unwrap();
+
+        assert!(move_staged_files(&staging, &target).unwrap().is_empty());
+        assert!(!staging.exists());
+        assert_eq!(relative_files(&target), ["Song.m4a"]);
+
+        std::fs::remove_dir_all(&target).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+//! The formats a video is available in, read from yt-dlp's `-F` table and
+//! turned into labels a person can pick from.
+
+use crate::managed::yt_dlp;
+use std::process::Command;
+
+#[derive(Clone)]
+pub struct FormatOption {
+    /// What goes after `-f`.
+    pub id: String,
+    pub ext: String,
+    /// Video height in pixels; `None` for audio-only formats.
+    pub height: Option<u32>,
+    /// Total bitrate in kbit/s.
+    pub bitrate: Option<u32>,
+    /// As printed by yt-dlp, e.g. `27.02MiB` or `~1.00MiB`.
+    pub size: Option<String>,
+    pub video_only: bool,
+}
+
+impl FormatOption {
+    /// "1080p MP4 (120.5MiB)" or "Audio only — 129kbps m4a (~3.02MiB)".
+    pub fn label(&self) -> String {
+        let size = self.size.as_deref().unwrap_or("size unknown");
+        match self.height {
+            Some(h) => format!(
+                "{}p {}{} ({})",
+                h,
+                self.ext.to_uppercase(),
+                if self.video_only { ", no audio" } else { "" },
+                size
+            ),
+            None => match self.bitrate {
+                Some(b) => format!("Audio only — {}kbps {} ({})", b, self.ext, size),
+                None => format!("Audio only — {} ({})", self.ext, size),
+            },
+        }
+    }
+}
+
+fn parse_kbps(token: &str) -> Option<u32> {
+    token
+        .strip_suffix('k')?
+        .parse::<f64>()
+        .ok()
+        .map(|k| k as u32)
+}
+
+/// Parses the table `yt-dlp -F` prints. Storyboards are dropped. Video
+/// formats come first, tallest first, then audio by bitrate.
+pub fn parse_format_table(table: &str) -> Vec<FormatOption> {
+    let mut formats: Vec<FormatOption> = table
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('│');
+            let head: Vec<&str> = columns.next()?.split_whitespace().collect();
+            let transfer: Vec<&str> = columns.next()?.split_whitespace().collect();
+            let codecs = columns.next()?;
+            let (&id, &ext) = (head.first()?, head.get(1)?);
+            if id == "ID" || ext == "mhtml" {
+                return None;
+            }
+
+            let height = head
+                .get(2)
+                .and_then(|r| r.split_once('x'))
+                .and_then(|(_, h)| h.parse().ok());
+            // Sizes may be prefixed with `~` or `≈ ` when yt-dlp only has an
+            // estimate.
+            let size_at = transfer.iter().position(|t| t.ends_with("iB"));
+            let size = size_at.map(|i| {
+                let approx = i > 0 && transfer[i - 1] == "≈";
+                let size = transfer[i].trim_start_matches('~');
+                if approx || transfer[i].starts_with('~') {
+                    format!("~{}", size)
+                } else {
+                    size.to_string()
+                }
+            });
+            let bitrate = transfer
+                .iter()
+                .skip(size_at.map_or(0, |i| i + 1))
+                .find_map(|t| parse_kbps(t));
+
+            Some(FormatOption {
+                id: id.to_string(),
+                ext: ext.to_string(),
+                height,
+                bitrate,
+                size,
+                video_only: codecs.contains("video only"),
+            })
+        })
+        .collect();
+
+    formats.sort_by(|a, b| match (a.height, b.height) {
+        (Some(x), Some(y)) => y.cmp(&x).then(b.bitrate.cmp(&a.bitrate)),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => b.bitrate.cmp(&a.bitrate),
+    });
+    formats
+}
+
+/// Runs `yt-dlp -F` on a single video.
+pub fn list_formats(url: &str) -> Result<Vec<FormatOption>, String> {
+    let output = Command::new(yt_dlp())
+        .args(["-F", url])
+        .output()
+        .map_err(|e| format!("Could not run yt-dlp: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr
+            .lines()
+            .rfind(|l| !l.trim().is_empty())
+            .unwrap_or("Could not list formats")
+            .to_string());
+    }
+    Ok(parse_format_table(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "\
+[info] Available formats for dQw4w9WgXcQ:
+ID  EXT   RESOLUTION FPS CH │   FILESIZE   TBR PROTO │ VCODEC         VBR ACODEC      ABR ASR MORE INFO
+────────────────────────────────────────────────────────────────────────────────────────────────────────
+sb3 mhtml 48x27        0    │                  mhtml │ images                                 storyboard
+233 mp4   audio only        │                  m3u8  │ audio only         unknown             [en] Default
+249 webm  audio only      2 │    1.18MiB   46k https │ audio only         opus        46k 48k [en] low, webm_dash
+251 webm  audio only      2 │    3.28MiB  129k https │ audio only         opus       129k 48k [en] medium, webm_dash
+18  mp4   640x360     25  2 │ ≈  5.92MiB  234k https │ avc1.42001E        mp4a.40.2       44k [en] 360p
+137 mp4   1920x1080   25    │   77.56MiB 3041k https │ avc1.640028  3041k video only              1080p, mp4_dash
+";
+
+    #[test]
+    fn format_table_is_parsed_and_sorted() {
+        let formats = parse_format_table(TABLE);
+        let ids: Vec<&str> = formats.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["137", "18", "251", "249", "233"]);
+
+        assert_eq!(formats[0].height, Some(1080));
+        assert!(formats[0].video_only);
+        assert_eq!(formats[0].label(), "1080p MP4, no audio (77.56MiB)");
+        assert_eq!(formats[1].label(), "360p MP4 (~5.92MiB)");
+        assert_eq!(formats[2].bitrate, Some(129));
+        assert_eq!(formats[2].label(), "Audio only — 129kbps webm (3.28MiB)");
+        assert_eq!(formats[4].label(), "Audio only — mp4 (size unknown)");
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// One finished download run.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub playlist: String,
+    /// Unix timestamp of when the run finished.
+    pub timestamp: i64,
+    pub success: bool,
+    /// The audio files this run of the URL created, where they ended up.
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+}
+
+impl HistoryEntry {
+    pub fn finished_at(&self) -> Option<DateTime<Local>> {
+        DateTime::from_timestamp(self.timestamp, 0).map(|t| t.with_timezone(&Local))
+    }
+}
+
+/// Past runs, stored as JSON in `<data dir>/ytd/history.json`, oldest first.
+#[derive(Default, Serialize, Deserialize)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+    /// Playlists whose last run was cancelled partway, and the item it was
+    /// on when it stopped.
+    #[serde(default)]
+    pub resume_points: HashMap<String, usize>,
+}
+
+impl History {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|d| d.join("ytd").join("history.json"))
+    }
+
+    /// Loads the history file. A missing or unreadable history just means
+    /// starting fresh; it's not worth refusing to start over.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    pub fn record(&mut self, entry: HistoryEntry) {
+        self.entries.push(entry);
+        let _ = self.save();
+    }
+
+    /// When `url` was last downloaded successfully.
+    pub fn last_run(&self, url: &str) -> Option<DateTime<Local>> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.success && e.url == url)
+            .and_then(HistoryEntry::finished_at)
+    }
+
+    /// The item a cancelled run of `url` stopped at.
+    pub fn resume_point(&self, url: &str) -> Option<usize> {
+        self.resume_points.get(url).copied()
+    }
+
+    pub fn set_resume_point(&mut self, url: &str, item: usize) {
+        self.resume_points.insert(url.to_string(), item);
+        let _ = self.save();
+    }
+
+    pub fn clear_resume_point(&mut self, url: &str) {
+        if self.resume_points.remove(url).is_some() {
+            let _ = self.save();
+        }
+    }
+
+    /// The playlist the most recent run went into.
+    pub fn last_playlist(&self) -> Option<&str> {
+        self.entries.last().map(|e| e.playlist.as_str())
+    }
+}
@@ -0,0 +1,95 @@
+//! A per-playlist lockfile so two ytd instances don't download into the
+//! same folder at once.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const LOCK_FILE: &str = ".ytd.lock";
+
+/// Holds `<dir>/.ytd.lock` and removes it when dropped.
+pub struct LockGuard {
+    path: PathBuf,
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn pid_alive(pid: u32) -> bool {
+    if Path::new("/proc").is_dir() {
+        return Path::new(&format!("/proc/{}", pid)).exists();
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// How long a lock without a PID in it counts as held.
+const UNWRITTEN_GRACE: Duration = Duration::from_secs(5);
+
+/// Whether the file at `path` was modified within [`UNWRITTEN_GRACE`].
+fn is_fresh(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|t| t.elapsed().map_or(true, |age| age < UNWRITTEN_GRACE))
+}
+
+/// Takes the lock for `dir`, writing our PID into it. A lock left behind by
+/// a process that no longer exists is reclaimed.
+pub fn acquire_lock(dir: &Path) -> Result<LockGuard, String> {
+    let path = dir.join(LOCK_FILE);
+    for _ in 0..2 {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                write!(file, "{}", std::process::id())
+                    .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+                return Ok(LockGuard { path });
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let holder = std::fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|s| s.trim().parse::<u32>().ok());
+                let held = match holder {
+                    Some(pid) => pid != std::process::id() && pid_alive(pid),
+                    // No PID yet: another instance may have just created it
+                    // and not written one, so it only goes stale with age.
+                    None => is_fresh(&path),
+                };
+                if held {
+                    return Err("Another download is in progress for this playlist".to_string());
+                }
+                // Stale: its owner is gone (or it's unreadable), so take it over.
+                let _ = std::fs::remove_file(&path);
+            }
+            Err(e) => return Err(format!("Could not create {}: {}", path.display(), e)),
+        }
+    }
+    Err(format!("Could not take the lock {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freshly_created_lock_without_a_pid_is_held() {
+        let dir = std::env::temp_dir().join(format!("ytd-lock-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(LOCK_FILE), b"").unwrap();
+
+        assert!(acquire_lock(&dir).is_err());
+        assert!(dir.join(LOCK_FILE).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+//! Lyrics looked up by artist and title from a web API and embedded into
+//! the downloaded tracks' lyrics tag. Built with the `lyrics` feature; like
+//! the managed yt-dlp, requests go through `curl`.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// An LRCLIB-compatible endpoint: `GET <endpoint>?artist_name=..&track_name=..`
+/// answering with JSON that has a `plainLyrics` field, or 404.
+pub struct Provider {
+    pub endpoint: String,
+    /// For the whole request, so a slow provider can't hold up the run.
+    pub timeout: Duration,
+}
+
+impl Provider {
+    /// What curl may fetch, redirects included: only HTTPS, unless the
+    /// endpoint itself is plain HTTP, e.g. a provider on the local network.
+    fn protocols(&self) -> &'static str {
+        if self.endpoint.starts_with("http://") {
+            "=http,https"
+        } else {
+            "=https"
+        }
+    }
+
+    /// The plain lyrics of the song, or `None` when the provider doesn't
+    /// have them, the song is instrumental, or the request failed or was
+    /// rate limited. Either way the track is just left without.
+    pub fn fetch_lyrics(&self, artist: &str, title: &str) -> Option<String> {
+        let output = Command::new("curl")
+            .args(["--silent", "--location", "--proto", self.protocols()])
+            .args(["--user-agent", concat!("ytd/", env!("CARGO_PKG_VERSION"))])
+            .arg("--max-time")
+            .arg(self.timeout.as_secs().max(1).to_string())
+            .args(["--get", "--data-urlencode"])
+            .arg(format!("artist_name={}", artist))
+            .arg("--data-urlencode")
+            .arg(format!("track_name={}", title))
+            .args(["--write-out", "\n%{http_code}"])
+            .arg(&self.endpoint)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        let response = String::from_utf8_lossy(&output.stdout);
+        let (body, status) = response.rsplit_once('\n')?;
+        // 404 is no lyrics, 429 is slow down; both mean skip this track.
+        if status.trim() != "200" {
+            return None;
+        }
+        plain_lyrics(body)
+    }
+}
+
+fn plain_lyrics(body: &str) -> Option<String> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    if json["instrumental"].as_bool() == Some(true) {
+        return None;
+    }
+    json["plainLyrics"]
+        .as_str()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+}
+
+/// Writes `lyrics` into the lyrics tag of `file` (`©lyr` in m4a, `LYRICS`
+/// in opus and ogg). Like `embed_cover`, ffmpeg writes a copy that then
+/// replaces the original, so a failure leaves the file as it was.
+pub fn embed_lyrics(ffmpeg: &Path, file: &Path, lyrics: &str) -> io::Result<()> {
+    let ext = file.extension().unwrap_or_default().to_string_lossy();
+    let tmp = file.with_extension(format!("lyrics.{}", ext));
+    let status = Command::new(ffmpeg)
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(file)
+        .args(["-map", "0", "-c", "copy", "-metadata"])
+        .arg(format!("lyrics={}", lyrics))
+        .arg(&tmp)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(io::Error::other(format!("ffmpeg exited with {}", status)));
+    }
+    std::fs::rename(&tmp, file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lyrics_responses_are_parsed() {
+        let found = r#"{"plainLyrics":"  line one\nline two\n","instrumental":false}"#;
+        assert_eq!(plain_lyrics(found).as_deref(), Some("line one\nline two"));
+        assert_eq!(
+            plain_lyrics(r#"{"plainLyrics":null,"instrumental":true}"#),
+            None
+        );
+        assert_eq!(plain_lyrics(r#"{"plainLyrics":""}"#), None);
+        assert_eq!(plain_lyrics("<html>Too Many Requests</html>"), None);
+    }
+
+    #[test]
+    fn only_a_plain_http_endpoint_allows_plain_http() {
+        let provider = |endpoint: &str| Provider {
+            endpoint: endpoint.to_string(),
+            timeout: Duration::from_secs(10),
+        };
+        assert_eq!(provider("https://lrclib.net/api/get").protocols(), "=https");
+        assert_eq!(
+            provider("http://192.168.1.5:3000/api/get").protocols(),
+            "=http,https"
+        );
+    }
+}
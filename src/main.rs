@@ -1,303 +1,5525 @@
+mod cli;
+mod clipboard;
+mod config;
+mod cover;
+mod diagnostics;
+mod download;
+mod export;
+mod files;
+mod formats;
+mod history;
+mod lock;
+#[cfg(feature = "lyrics")]
+mod lyrics;
+mod managed;
+mod pager;
+mod paths;
+mod player;
+mod preview;
+mod probe;
+mod sidecars;
+mod trim;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
+use cli::Cli;
+use config::{Config, YtDlpSource, SETTINGS};
+use cover::{embed_cover, validate_cover_image};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use diagnostics::{gather_diagnostics, Diagnostics};
+use download::{
+    abnormal_exit, classify_error, clear_cache, count_playlist_items, fetch_live_status,
+    fetch_size_estimate, fetch_title, interrupt_child, is_playlist_url, is_url,
+    latest_items_selector, lists_newest_first, normalize_url, run_yt_dlp, search_youtube,
+    set_child_paused, strip_timestamp, video_id, DownloadEvent, FailureKind, LiveStatus, OutputLog,
+    OutputSink, Progress, SearchResult, Severity, SizeEstimate, CRASH_MARKER,
+};
+use export::{export_playlist, ExportFormat, ExportSummary};
+use files::{
+    archive_contains, cleanup_partials, find_similar, is_audio_file, is_reserved_char,
+    is_video_file, move_staged_files, organize_by_tags, safe_file_name, undo_download, walk_files,
+};
+use formats::{list_formats, FormatOption};
+use history::{History, HistoryEntry};
+use lock::{acquire_lock, LockGuard};
+use managed::yt_dlp;
+use pager::Pager;
+use paths::{estimate_output_path, long_path_template, path_length_warning};
+use preview::{is_preview, make_preview};
+use probe::{audio_info, read_tags, AudioInfo};
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style},
-    widgets::{Block, BorderType, Paragraph},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Gauge, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
-use std::io;
-use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
+use signal_hook::consts::SIGTERM;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
+use std::time::{Duration, Instant};
+use trim::trim_silence;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 enum AppState {
     InputPlaylistName,
     InputUrl,
+    SearchResults,
+    Confirm,
+    EditField(Field),
+    Settings,
     Downloading,
     Done,
+    Rename,
+    Formats,
+    Queue,
     Error,
+    Setup(SetupStep),
+    /// Confirmed, waiting for the chosen start time.
+    Scheduled,
+    /// `--watch`: waiting for the next check of the playlist.
+    Watching,
+    /// Versions and paths for a bug report, from the first screen.
+    Diagnostics,
 }
 
-struct App {
-    state: AppState,
-    playlist_name: String,
-    url: String,
-    error_message: String,
-    files_downloaded: Vec<String>,
-    download_output: Arc<Mutex<String>>,
-    download_output_final: String,
-    download_done: Arc<AtomicBool>,
-    download_success: Arc<AtomicBool>,
+/// The pages of the first-run wizard, in order.
+#[derive(Clone, Copy, PartialEq)]
+enum SetupStep {
+    MusicDir,
+    Format,
+    Tools,
 }
 
-impl App {
-    fn new() -> Self {
-        Self {
-            state: AppState::InputPlaylistName,
-            playlist_name: String::new(),
-            url: String::new(),
-            error_message: String::new(),
-            files_downloaded: Vec::new(),
-            download_output: Arc::new(Mutex::new(String::new())),
-            download_output_final: String::new(),
-            download_done: Arc::new(AtomicBool::new(false)),
-            download_success: Arc::new(AtomicBool::new(false)),
+/// The wizard's format choices: label, `-f` selector (`None` for the
+/// built-in default) and whether free formats are preferred.
+const SETUP_FORMATS: &[(&str, Option<&str>, bool)] = &[
+    ("M4A (AAC): plays everywhere", None, false),
+    ("Opus: smaller files, free format", None, true),
+    ("Best audio, whatever the format", Some("ba/b"), false),
+];
+
+/// Whether `program` runs, judged by `program <flag>` exiting successfully.
+fn command_available(program: impl AsRef<std::ffi::OsStr>, flag: &str) -> bool {
+    Command::new(program)
+        .arg(flag)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Warns before anything is downloaded when ffmpeg can't be run, since
+/// yt-dlp only notices after the download, at post-processing.
+fn ffmpeg_warning(config: &Config) -> Option<String> {
+    if command_available(config.ffmpeg_program(), "-version") {
+        return None;
+    }
+    Some(match &config.ffmpeg_location {
+        Some(path) => format!(
+            "ffmpeg_location {} doesn't run; audio conversion will fail",
+            path.display()
+        ),
+        None => {
+            "ffmpeg isn't on PATH; install it or set ffmpeg_location in the config file".to_string()
+        }
+    })
+}
+
+/// A single-line value edited from the confirm screen.
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    DateAfter,
+    AudioLanguage,
+    Username,
+    Password,
+    MaxDownloads,
+    LatestItems,
+    StartAt,
+    CoverImage,
+    CookiesFile,
+    Genre,
+    MaxDuration,
+}
+
+impl Field {
+    fn label(self) -> &'static str {
+        match self {
+            Field::DateAfter => "Only videos uploaded on or after (YYYYMMDD)",
+            Field::AudioLanguage => "Preferred audio language (e.g. en, pt-BR)",
+            Field::Username => "Username",
+            Field::Password => "Password (not saved)",
+            Field::MaxDownloads => "Stop after this many downloads",
+            Field::LatestItems => "Only the newest N items of a playlist or channel",
+            Field::StartAt => "Start at (HH:MM, 24-hour clock)",
+            Field::CoverImage => "Cover image for every track (.jpg/.png)",
+            Field::CookiesFile => "cookies.txt for signed-in downloads (saved)",
+            Field::Genre => "Genre tag for every track (e.g. Jazz)",
+            Field::MaxDuration => "Skip videos longer than (HH:MM:SS or seconds)",
         }
     }
 
-    fn start_download(&mut self) {
-        let music_dir = dirs::home_dir()
-            .unwrap_or_default()
-            .join("Music")
-            .join(&self.playlist_name);
+    /// Fields whose input is drawn as `*` instead of the typed text.
+    fn is_masked(self) -> bool {
+        matches!(self, Field::Password)
+    }
+}
 
-        let _ = std::fs::create_dir_all(&music_dir);
+/// A list fetched from yt-dlp in the background: search results, a video's
+/// formats, or possible duplicates of it.
+#[derive(Clone)]
+enum Lookup<T> {
+    Pending,
+    Ready(Vec<T>),
+    Failed(String),
+}
 
-        let url = self.url.clone();
-        let output_path = music_dir.display().to_string();
-        let output_ref = self.download_output.clone();
-        let done_ref = self.download_done.clone();
-        let success_ref = self.download_success.clone();
+impl<T> Lookup<T> {
+    fn len(&self) -> usize {
+        match self {
+            Lookup::Ready(items) => items.len(),
+            _ => 0,
+        }
+    }
+}
 
-        let output_clone = output_ref.clone();
+/// Where a list was drawn in the last frame and how far it was scrolled,
+/// so a click can be mapped back to an item.
+#[derive(Clone, Copy)]
+struct ListArea {
+    area: Rect,
+    offset: usize,
+}
 
-        thread::spawn(move || {
-            let mut child = Command::new("yt-dlp")
-                .args([
-                    "-f",
-                    "ba[ext=m4a]",
-                    "--extract-audio",
-                    "--embed-thumbnail",
-                    "--add-metadata",
-                    "--convert-thumbnails",
-                    "jpg",
-                    "--output",
-                    &format!("{}/%(title)s.%(ext)s", output_path),
-                    &url,
-                ])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn();
-
-            match child {
-                Ok(ref mut c) => {
-                    let stdout = c.stdout.take();
-                    let stderr = c.stderr.take();
-
-                    let out1 = output_clone.clone();
-                    let t1 = stdout.map(|s| {
-                        thread::spawn(move || {
-                            let reader = BufReader::new(s);
-                            for line in reader.lines() {
-                                if let Ok(l) = line {
-                                    let mut out = out1.lock().unwrap();
-                                    out.push_str(&l);
-                                    out.push('\n');
-                                }
-                            }
-                        })
-                    });
+impl ListArea {
+    /// The item under a click at `column`, `row`, inside the list's border.
+    fn item_at(self, column: u16, row: u16) -> Option<usize> {
+        let inner = self.area.inner(Margin::new(1, 1));
+        inner
+            .contains(Position::new(column, row))
+            .then(|| self.offset + (row - inner.y) as usize)
+    }
+}
 
-                    let out2 = output_clone.clone();
-                    let t2 = stderr.map(|s| {
-                        thread::spawn(move || {
-                            let reader = BufReader::new(s);
-                            for line in reader.lines() {
-                                if let Ok(l) = line {
-                                    let mut out = out2.lock().unwrap();
-                                    out.push_str(&l);
-                                    out.push('\n');
-                                }
-                            }
-                        })
-                    });
+/// The worker asking whether to continue a slow playlist at lower quality.
+#[derive(Clone, Copy, PartialEq)]
+enum QualityPrompt {
+    Idle,
+    Asking,
+    Accepted,
+    Declined,
+}
 
-                    if let Some(t) = t1 {
-                        let _ = t.join();
-                    }
-                    if let Some(t) = t2 {
-                        let _ = t.join();
-                    }
+/// How the worker's log line starts while it trims silence, which the status
+/// line looks for.
+const TRIMMING_SILENCE: &str = "Trimming silence from";
 
-                    let status = c.wait().unwrap_or_default();
-                    success_ref.store(status.success(), Ordering::SeqCst);
-                }
-                Err(e) => {
-                    let mut out = output_clone.lock().unwrap();
-                    out.push_str(&format!("Failed to spawn: {}", e));
-                    success_ref.store(false, Ordering::SeqCst);
-                }
-            }
+/// How the worker's log line starts for each preview it makes.
+const MAKING_PREVIEW: &str = "Making a preview of";
 
-            done_ref.store(true, Ordering::SeqCst);
-        });
+/// What max quality audio asks for: the highest bitrate of any format.
+const MAX_QUALITY_FORMAT: &str = "bestaudio";
+
+/// What a slow connection drops to: small m4a audio if there is one.
+const LOW_QUALITY_FORMAT: &str = "ba[ext=m4a][abr<=96]/worstaudio[ext=m4a]/worstaudio";
+
+/// The same for video downloads: 480p at most, still with its audio.
+const LOW_QUALITY_VIDEO_FORMAT: &str = "bv*[height<=480]+ba/b[height<=480]/wv*+ba/w";
+
+/// A background result tagged with the URL it's for, so a stale answer for a
+/// URL that has since been edited is ignored.
+type PerUrl<T> = Arc<Mutex<Option<(String, T)>>>;
+
+/// A finished URL: whether it succeeded, and which of `created` it wrote.
+type UrlResult = (String, bool, std::ops::Range<usize>);
+
+/// A one-off background job: `Some(None)` while it runs, then its message.
+type BackgroundJob = Arc<Mutex<Option<Option<Result<String, String>>>>>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ItemCount {
+    Counting,
+    Known(usize),
+    Unknown,
+}
+
+/// The yt-dlp knobs a download is started with.
+#[derive(Clone)]
+struct DownloadOptions {
+    format: String,
+    limit_rate: Option<String>,
+    embed_thumbnail: bool,
+    add_metadata: bool,
+    /// Passed as `--dateafter`, in yt-dlp's YYYYMMDD form.
+    date_after: Option<String>,
+    audio_language: Option<String>,
+    /// Leave a `.info.json` per video for the sidecar writers to read.
+    write_info_json: bool,
+    /// Fetch subtitles as WebVTT for the lyrics sidecar.
+    write_subs: bool,
+    /// With `write_subs`, fall back to auto-generated captions.
+    write_auto_subs: bool,
+    embed_info_json: bool,
+    mtime: bool,
+    ffmpeg_location: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    /// `--download-archive`: skip videos it lists, and add new ones.
+    download_archive: Option<PathBuf>,
+    /// `--match-filter`: skip videos longer than this many seconds.
+    max_duration: Option<u64>,
+    /// `--max-downloads`: stop after this many successful downloads.
+    max_downloads: Option<usize>,
+    /// Only the newest this many entries of a playlist or channel, picked
+    /// with `--playlist-items`.
+    latest_items: Option<usize>,
+    /// The expanded `tag_comment`, embedded along with `--add-metadata`.
+    comment: Option<String>,
+    /// Album tag for every file, normally the playlist name.
+    album: Option<String>,
+    /// Only use `album` for videos that don't have one of their own.
+    keep_source_album: bool,
+    /// Genre tag for every file.
+    genre: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    cookies: Option<PathBuf>,
+    prefer_free_formats: bool,
+    /// `--audio-quality 0`, so if the audio has to be converted at all it
+    /// is at the best quality.
+    max_quality_audio: bool,
+    /// `--live-from-start`: record a live stream from its beginning rather
+    /// than from now.
+    live_from_start: bool,
+    /// `--wait-for-video`: wait for a scheduled stream or premiere to start.
+    wait_for_video: bool,
+    /// A `yt-dlp.conf` kept in the playlist folder, passed with
+    /// `--config-location`.
+    config_location: Option<PathBuf>,
+    /// Cut leading and trailing silence from the tracks once downloaded.
+    trim_silence: bool,
+    /// `--split-chapters`: one track per chapter instead of one file.
+    split_chapters: bool,
+    /// Keep the video: no `--extract-audio`, and separate streams merged
+    /// into an mp4.
+    video: bool,
+    /// `--remux-video`: with `video`, repackage into this container.
+    remux_video: Option<String>,
+    /// `--playlist-start`: pick an interrupted playlist up at this item.
+    playlist_start: Option<usize>,
+    /// `--sleep-requests` and `--sleep-interval`, in seconds.
+    sleep_requests: Option<f64>,
+    sleep_interval: Option<f64>,
+    /// Fail a download when a fragment can't be fetched, instead of
+    /// leaving a gap.
+    abort_on_missing_fragments: bool,
+}
+
+/// The download archive shared by all playlists when global dedup is on.
+const GLOBAL_ARCHIVE: &str = ".ytd-global-archive.txt";
+
+/// The playlist's own download archive, so each `--watch` check only
+/// fetches what's new.
+const WATCH_ARCHIVE: &str = ".ytd-archive.txt";
+
+/// How many past checks the watch screen keeps.
+const WATCH_LOG_LINES: usize = 50;
+
+/// How many of the folders organize by tags filled the Done screen lists.
+const ORGANIZED_FOLDERS_SHOWN: usize = 5;
+
+/// The `--watch` loop: when the next check is due and how each went.
+struct Watch {
+    next_check: Instant,
+    results: Vec<Line<'static>>,
+}
+
+/// `-f` for the Confirm screen's video toggle: the best video with the best
+/// audio, or the best single file if there's nothing to merge.
+const VIDEO_FORMAT: &str = "bv*+ba/b";
+
+/// `-f` for a retry after the video was blocked: any audio-only format.
+const AUDIO_FALLBACK_FORMAT: &str = "ba";
+
+/// What the last download was started with, so it can be run again as it
+/// was from the Done or Error screen.
+#[derive(Clone)]
+struct LastRun {
+    playlist_name: String,
+    url_queue: Vec<String>,
+    options: DownloadOptions,
+    format_label: Option<String>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            format: "ba[ext=m4a]".to_string(),
+            limit_rate: None,
+            embed_thumbnail: true,
+            add_metadata: true,
+            date_after: None,
+            audio_language: None,
+            write_info_json: false,
+            write_subs: false,
+            write_auto_subs: false,
+            embed_info_json: false,
+            mtime: true,
+            ffmpeg_location: None,
+            cache_dir: None,
+            download_archive: None,
+            max_duration: None,
+            max_downloads: None,
+            latest_items: None,
+            comment: None,
+            album: None,
+            keep_source_album: false,
+            genre: None,
+            username: None,
+            password: None,
+            cookies: None,
+            prefer_free_formats: false,
+            max_quality_audio: false,
+            live_from_start: false,
+            wait_for_video: false,
+            config_location: None,
+            trim_silence: false,
+            video: false,
+            remux_video: None,
+            split_chapters: false,
+            playlist_start: None,
+            sleep_requests: None,
+            sleep_interval: None,
+            abort_on_missing_fragments: false,
+        }
     }
+}
 
-    fn check_download(&mut self) -> bool {
-        if self.download_done.load(Ordering::SeqCst) {
-            self.download_output_final = {
-                let out = self.download_output.lock().unwrap();
-                out.clone()
-            };
+/// The `-f` selector. With a preferred language every alternative is tried
+/// with a `[language=..]` filter first, then the plain alternatives, so a video
+/// without that dub still downloads its default track.
+fn format_selector(opts: &DownloadOptions) -> String {
+    match &opts.audio_language {
+        Some(lang) => {
+            let preferred: Vec<String> = opts
+                .format
+                .split('/')
+                .map(|alt| format!("{}[language={}]", alt, lang))
+                .collect();
+            format!("{}/{}", preferred.join("/"), opts.format)
+        }
+        None => opts.format.clone(),
+    }
+}
 
-            if self.download_success.load(Ordering::SeqCst) {
-                let music_dir = dirs::home_dir()
-                    .unwrap_or_default()
-                    .join("Music")
-                    .join(&self.playlist_name);
+/// Language codes are letters with optional `-` separated subtags; anything
+/// else would corrupt the format selector.
+fn is_valid_language(lang: &str) -> bool {
+    (2..=12).contains(&lang.len())
+        && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && lang.starts_with(|c: char| c.is_ascii_alphabetic())
+}
 
-                self.files_downloaded = std::fs::read_dir(&music_dir)
-                    .ok()
-                    .map(|d| {
-                        d.filter_map(|e| e.ok())
-                            .filter(|e| e.path().extension().map_or(false, |ext| ext == "m4a"))
-                            .filter_map(|e| e.file_name().into_string().ok())
-                            .collect()
-                    })
-                    .unwrap_or_default();
+/// Whether yt-dlp can embed a thumbnail in what each `[ext=..]` leaves
+/// after `--extract-audio`. ogg, opus and flac go through mutagen, which
+/// usually comes with yt-dlp; wav and bare aac have nowhere to put one.
+const THUMBNAIL_EMBEDDING: &[(&str, bool)] = &[
+    ("m4a", true),
+    ("mp4", true),
+    ("mp3", true),
+    ("mka", true),
+    ("webm", true),
+    ("opus", true),
+    ("ogg", true),
+    ("flac", true),
+    ("aac", false),
+    ("wav", false),
+];
 
-                self.state = AppState::Done;
+/// The extension the selector asks for, if it rules out a thumbnail. Only
+/// the first alternative counts: it's the one that usually matches.
+fn thumbnail_unsupported_ext(selector: &str) -> Option<&str> {
+    let first = selector.split('/').next()?;
+    let (_, rest) = first.split_once("[ext=")?;
+    let ext = rest.split(']').next()?;
+    THUMBNAIL_EMBEDDING
+        .iter()
+        .any(|&(e, supported)| e == ext && !supported)
+        .then_some(ext)
+}
+
+/// `text` as the FROM side of `--parse-metadata`: it's read as a template,
+/// so `%` and the `:` separating FROM from TO have to be escaped.
+fn literal_template(text: &str) -> String {
+    let escaped = text.replace('%', "%%").replace(':', "\\:");
+    // A lone word like "Jazz" would be taken as the name of a field; an
+    // empty field with an empty default makes it a template again.
+    if escaped.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
+        format!("{}%(ytd_none|)s", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Checks a genre can go into `--parse-metadata` and the file's tags.
+fn validate_genre(genre: &str) -> Result<(), String> {
+    if genre.trim().is_empty() {
+        return Err("Genre can't be empty".to_string());
+    }
+    if genre.chars().any(char::is_control) {
+        return Err("Genre can't contain line breaks or control characters".to_string());
+    }
+    if genre.chars().count() > 64 {
+        return Err("Genre can be at most 64 characters".to_string());
+    }
+    Ok(())
+}
+
+fn build_yt_dlp_args(opts: &DownloadOptions, output_template: &str, url: &str) -> Vec<String> {
+    let mut args = vec!["-f".to_string(), format_selector(opts)];
+    if let Some(container) = opts.remux_video.as_ref().filter(|_| opts.video) {
+        // Merged into whatever fits the streams, then repackaged.
+        args.push("--remux-video".to_string());
+        args.push(container.clone());
+    } else if opts.video {
+        args.extend(["--merge-output-format", "mp4"].map(String::from));
+    } else {
+        args.push("--extract-audio".to_string());
+        if opts.max_quality_audio {
+            args.extend(["--audio-quality", "0"].map(String::from));
+        }
+    }
+    if opts.prefer_free_formats {
+        args.push("--prefer-free-formats".to_string());
+    }
+    if opts.embed_thumbnail {
+        args.extend(["--embed-thumbnail", "--convert-thumbnails", "jpg"].map(String::from));
+    }
+    if opts.add_metadata {
+        args.push("--add-metadata".to_string());
+        if let Some(comment) = &opts.comment {
+            // `meta_comment` is what --add-metadata writes to the comment
+            // tag.
+            args.push("--parse-metadata".to_string());
+            args.push(format!("{}:%(meta_comment)s", literal_template(comment)));
+        }
+        if let Some(album) = &opts.album {
+            args.push("--parse-metadata".to_string());
+            if opts.keep_source_album {
+                // Park the name in a field of its own, then fall back to it
+                // only where the video has no album.
+                args.push(format!("{}:%(ytd_album)s", literal_template(album)));
+                args.push("--parse-metadata".to_string());
+                args.push("%(album,ytd_album)s:%(meta_album)s".to_string());
             } else {
-                self.error_message = "Download failed. Check your connection and URL.".to_string();
-                self.state = AppState::Error;
+                args.push(format!("{}:%(meta_album)s", literal_template(album)));
             }
-            return true;
         }
-        false
+        if let Some(genre) = &opts.genre {
+            args.push("--parse-metadata".to_string());
+            args.push(format!("{}:%(meta_genre)s", literal_template(genre)));
+        }
+    }
+    if let Some(rate) = &opts.limit_rate {
+        args.push("--limit-rate".to_string());
+        args.push(rate.clone());
+    }
+    if opts.write_info_json {
+        args.extend(["--write-info-json", "--no-write-playlist-metafiles"].map(String::from));
+    }
+    if opts.write_subs {
+        // Uploaded subtitles only, unless a transcript wants captions:
+        // auto-generated ones are speech recognition, not lyrics, and live
+        // chat replays aren't text at all.
+        let langs = match &opts.audio_language {
+            Some(lang) => format!("{}.*", lang),
+            // Auto captions come translated into every language; `-orig`
+            // is the one in the video's own.
+            None if opts.write_auto_subs => ".*-orig".to_string(),
+            None => "all,-live_chat".to_string(),
+        };
+        args.push("--write-subs".to_string());
+        if opts.write_auto_subs {
+            args.push("--write-auto-subs".to_string());
+        }
+        args.extend(["--sub-format", "vtt", "--sub-langs"].map(String::from));
+        args.push(langs);
+    }
+    if opts.embed_info_json {
+        args.push("--embed-info-json".to_string());
+    }
+    args.push(if opts.mtime { "--mtime" } else { "--no-mtime" }.to_string());
+    if let Some(date) = &opts.date_after {
+        args.push("--dateafter".to_string());
+        args.push(date.clone());
+    }
+    if opts.live_from_start {
+        args.push("--live-from-start".to_string());
+    }
+    if opts.wait_for_video {
+        // Seconds between checks for whether the stream has started.
+        args.extend(["--wait-for-video", "30"].map(String::from));
+    }
+    args.push(
+        if opts.abort_on_missing_fragments {
+            "--abort-on-unavailable-fragments"
+        } else {
+            "--skip-unavailable-fragments"
+        }
+        .to_string(),
+    );
+    if let Some(seconds) = opts.sleep_requests {
+        args.push("--sleep-requests".to_string());
+        args.push(seconds.to_string());
+    }
+    if let Some(seconds) = opts.sleep_interval {
+        args.push("--sleep-interval".to_string());
+        args.push(seconds.to_string());
+    }
+    if let Some(path) = &opts.ffmpeg_location {
+        args.push("--ffmpeg-location".to_string());
+        args.push(path.display().to_string());
+    }
+    if let Some(path) = &opts.cache_dir {
+        args.push("--cache-dir".to_string());
+        args.push(path.display().to_string());
+    }
+    if let Some(path) = &opts.download_archive {
+        args.push("--download-archive".to_string());
+        args.push(path.display().to_string());
+    }
+    if let Some(path) = &opts.config_location {
+        args.push("--config-location".to_string());
+        args.push(path.display().to_string());
     }
+    if let Some(seconds) = opts.max_duration {
+        args.push("--match-filter".to_string());
+        // `<?` lets through videos whose duration isn't known, such as
+        // some live streams and other sites' uploads.
+        args.push(format!("duration <? {}", seconds));
+    }
+    if let Some(n) = opts.max_downloads {
+        args.push("--max-downloads".to_string());
+        args.push(n.to_string());
+    }
+    if let Some(n) = opts.playlist_start.filter(|_| is_playlist_url(url)) {
+        args.push("--playlist-start".to_string());
+        args.push(n.to_string());
+    }
+    if let Some(n) = opts.latest_items.filter(|_| is_playlist_url(url)) {
+        args.push("--playlist-items".to_string());
+        args.push(latest_items_selector(url, n));
+    }
+    if let Some(username) = &opts.username {
+        args.push("--username".to_string());
+        args.push(username.clone());
+    }
+    if let Some(password) = &opts.password {
+        args.push("--password".to_string());
+        args.push(password.clone());
+    }
+    if let Some(cookies) = &opts.cookies {
+        args.push("--cookies".to_string());
+        args.push(cookies.display().to_string());
+    }
+    if opts.split_chapters {
+        // Without chapters there is nothing to split and the video is
+        // kept whole; with them the tracks are written next to the whole
+        // file, which yt-dlp keeps.
+        args.push("--split-chapters".to_string());
+        args.push("--output".to_string());
+        args.push(format!("chapter:{}", chapter_template(output_template)));
+    }
+    args.push("--output".to_string());
+    args.push(output_template.to_string());
+    args.push(url.to_string());
+    args
 }
 
-fn main() -> io::Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+/// File name for each track of a video split by chapters.
+const CHAPTER_FILE_NAME: &str = "%(title)s - %(section_number)02d - %(section_title)s.%(ext)s";
 
-    let mut app = App::new();
-    let mut spinner_frame = 0u32;
+/// The `chapter:` output template: the split tracks go in the folder the
+/// whole video would have, so library and date folders still apply.
+fn chapter_template(output_template: &str) -> String {
+    match output_template.rsplit_once('/') {
+        Some((dir, _)) => format!("{}/{}", dir, CHAPTER_FILE_NAME),
+        None => CHAPTER_FILE_NAME.to_string(),
+    }
+}
 
-    loop {
-        terminal.draw(|f| ui(f, &mut app, spinner_frame))?;
+/// Fills `{date}` and `{url}` in the configured comment template.
+fn expand_tag_comment(template: &str, url: &str) -> String {
+    template
+        .replace("{date}", &Local::now().format("%Y-%m-%d").to_string())
+        .replace("{url}", url)
+}
 
-        if app.state == AppState::Downloading {
-            spinner_frame = spinner_frame.wrapping_add(1);
+/// How many times one item is retried after a 429 before it counts as
+/// failed.
+const RATE_LIMIT_RETRIES: usize = 3;
 
-            if event::poll(std::time::Duration::from_millis(50))? {
-                if let Event::Key(key) = event::read()? {
-                    if key.code == KeyCode::Esc {
-                        break;
-                    }
+/// Somewhere between 30 and 60 seconds. The jitter keeps retries from
+/// landing in lockstep with whatever else is hitting YouTube.
+fn rate_limit_backoff() -> Duration {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    Duration::from_secs(30 + u64::from(nanos % 31))
+}
+
+/// One set of arguments per item of a playlist of `count` entries, each
+/// `args` with `--playlist-items <i>` before the URL. With `latest`, only
+/// the newest that many items, from whichever end of the listing they're at.
+fn per_item_runs(
+    args: &[String],
+    url: &str,
+    count: usize,
+    latest: Option<usize>,
+) -> Vec<Vec<String>> {
+    let items = match latest {
+        Some(n) if lists_newest_first(url) => 1..=n.min(count),
+        Some(n) => count.saturating_sub(n) + 1..=count,
+        None => 1..=count,
+    };
+    // The item's own selection replaces the one for the whole playlist.
+    let mut base = args.to_vec();
+    if let Some(at) = base.iter().position(|a| a == "--playlist-items") {
+        base.drain(at..at + 2);
+    }
+    items
+        .map(|i| {
+            let mut item_args = base.clone();
+            let at = item_args.len() - 1;
+            item_args.splice(at..at, ["--playlist-items".to_string(), i.to_string()]);
+            item_args
+        })
+        .collect()
+}
+
+/// yt-dlp exits with 101 when it stops because `--max-downloads` was
+/// reached, which is the limit working, not a failure.
+const MAX_DOWNLOADS_REACHED: i32 = 101;
+
+fn exited_ok(status: ExitStatus) -> bool {
+    status.success() || status.code() == Some(MAX_DOWNLOADS_REACHED)
+}
+
+/// "Audio: opus, 160 kbps", or the range over several tracks.
+fn resolved_audio_summary(tracks: &[AudioInfo]) -> Option<String> {
+    let low = tracks.iter().map(|t| t.kbps).min()?;
+    let high = tracks.iter().map(|t| t.kbps).max()?;
+    let mut codecs: Vec<&str> = tracks.iter().map(|t| t.codec.as_str()).collect();
+    codecs.sort();
+    codecs.dedup();
+    Some(if low == high {
+        format!("Audio: {}, {} kbps", codecs.join("/"), low)
+    } else {
+        format!("Audio: {}, {}–{} kbps", codecs.join("/"), low, high)
+    })
+}
+
+/// Keeps the output of a run where yt-dlp crashed, for a bug report, in
+/// `<data dir>/ytd/yt-dlp-crash.log`. Each crash replaces the last.
+fn save_crash_log(output: &str) -> io::Result<PathBuf> {
+    let path = dirs::data_dir()
+        .ok_or_else(|| io::Error::other("no data directory"))?
+        .join("ytd")
+        .join("yt-dlp-crash.log");
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, output)?;
+    Ok(path)
+}
+
+/// The yt-dlp command line as shown to the user, with the `--password` value
+/// blanked out.
+fn display_command(args: &[String]) -> String {
+    let mut shown = vec!["yt-dlp".to_string()];
+    let mut hide_next = false;
+    for arg in args {
+        if hide_next {
+            shown.push("********".to_string());
+            hide_next = false;
+        } else {
+            hide_next = arg == "--password";
+            shown.push(arg.clone());
+        }
+    }
+    shown.join(" ")
+}
+
+/// Accepts YYYYMMDD or YYYY-MM-DD and returns the date in yt-dlp's YYYYMMDD
+/// form.
+fn parse_date_input(s: &str) -> Option<String> {
+    let s = s.trim();
+    NaiveDate::parse_from_str(s, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+        .ok()
+        .map(|d| d.format("%Y%m%d").to_string())
+}
+
+/// The next time the clock shows `at`: later today, or else tomorrow. A
+/// time skipped by a DST change has no occurrence that day.
+fn next_occurrence(now: DateTime<Local>, at: NaiveTime) -> Option<DateTime<Local>> {
+    let today = now
+        .date_naive()
+        .and_time(at)
+        .and_local_timezone(Local)
+        .earliest();
+    match today {
+        Some(t) if t > now => Some(t),
+        _ => (now.date_naive() + chrono::Days::new(1))
+            .and_time(at)
+            .and_local_timezone(Local)
+            .earliest(),
+    }
+}
+
+/// Seconds from `HH:MM:SS`, `MM:SS` or a plain number of seconds. `None`
+/// for zero or anything else.
+fn parse_duration(text: &str) -> Option<u64> {
+    let parts: Vec<&str> = text.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let mut seconds = 0u64;
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let value: u64 = part.parse().ok()?;
+        // Only the first part may run past 59.
+        if i > 0 && value >= 60 {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(value)?;
+    }
+    (seconds > 0).then_some(seconds)
+}
+
+/// `1:02:03` or `2:03`, the way video lengths are usually written.
+fn format_duration(seconds: u64) -> String {
+    let (h, m, s) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+/// `3h 05m 10s`, `5m 10s` or `10s`.
+fn format_countdown(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    let (h, m, s) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+/// `MM:SS`, with the minutes going past 59 rather than adding hours.
+fn format_clock(seconds: u64) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Checks a user-typed file name for anything that would fail, or misbehave,
+/// on common filesystems.
+fn validate_file_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Name can't be empty".to_string());
+    }
+    if name == "." || name == ".." {
+        return Err("Not a valid name".to_string());
+    }
+    if let Some(c) = name.chars().find(|&c| is_reserved_char(c)) {
+        return Err(format!("Name can't contain '{}'", c.escape_default()));
+    }
+    Ok(())
+}
+
+/// `734 KiB`, `45.2 MiB` or `5.10 GiB`.
+fn format_bytes(bytes: u64) -> String {
+    const KIB: f64 = 1024.0;
+    let b = bytes as f64;
+    if b < KIB * KIB {
+        format!("{:.0} KiB", b / KIB)
+    } else if b < KIB * KIB * KIB {
+        format!("{:.1} MiB", b / (KIB * KIB))
+    } else {
+        format!("{:.2} GiB", b / (KIB * KIB * KIB))
+    }
+}
+
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The worker thread's handles onto the shared download state.
+struct Worker {
+    sink: OutputSink,
+    child: Arc<Mutex<Option<Child>>>,
+    cancel: Arc<AtomicBool>,
+    skip: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    current_item: Arc<AtomicUsize>,
+    total_items: Arc<AtomicUsize>,
+    /// Per-item runs each download one video, so yt-dlp's own
+    /// `--max-downloads` never triggers; the worker counts instead.
+    max_downloads: Option<usize>,
+    /// The first item to download, when resuming. Per-item runs override
+    /// `--playlist-start`, so the worker skips the earlier ones itself.
+    playlist_start: usize,
+    /// Only the newest this many items. Per-item runs override
+    /// `--playlist-items` too, so the worker picks them itself.
+    latest_items: Option<usize>,
+    /// Bytes per second below which an item counts as slow, when adaptive
+    /// quality is on.
+    slow_speed: Option<f64>,
+    /// `-f` for the remaining items once the user agrees to lower quality.
+    low_quality_format: &'static str,
+    quality_prompt: Arc<Mutex<QualityPrompt>>,
+    /// Set while waiting out a 429, for the countdown.
+    rate_limited_until: Arc<Mutex<Option<Instant>>>,
+    /// Where to look up lyrics, and the ffmpeg that embeds them, when
+    /// fetching lyrics is on.
+    #[cfg(feature = "lyrics")]
+    lyrics: Option<(lyrics::Provider, PathBuf)>,
+    #[cfg(feature = "lyrics")]
+    lyrics_result: Arc<Mutex<Option<(usize, usize)>>>,
+    /// The ffmpeg that trims silence from the tracks, when that's on.
+    trim_silence: Option<PathBuf>,
+    /// The custom cover image, and the ffmpeg that embeds it, when one was
+    /// picked.
+    cover: Option<(PathBuf, PathBuf)>,
+    cover_result: Arc<Mutex<Option<(usize, usize)>>>,
+    /// The ffmpeg that makes preview copies and their bitrate, when those
+    /// are on.
+    preview: Option<(PathBuf, String)>,
+    preview_result: Arc<Mutex<Option<(usize, usize)>>>,
+}
+
+impl Worker {
+    /// The audio files the run created that are still there.
+    fn created_tracks(&self) -> Vec<PathBuf> {
+        let mut tracks: Vec<PathBuf> = self
+            .sink
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| is_audio_file(p) && p.is_file())
+            .cloned()
+            .collect();
+        tracks.sort();
+        tracks.dedup();
+        tracks
+    }
+
+    /// Trims silence from every track the run created. A track ffmpeg
+    /// can't re-encode is left as it was, with a line in the log.
+    fn trim_silence_from_tracks(&self, ffmpeg: &Path) {
+        let tracks = self.created_tracks();
+        if tracks.is_empty() {
+            return;
+        }
+        self.sink.push_line(&format!(
+            "{} {} track{}",
+            TRIMMING_SILENCE,
+            tracks.len(),
+            if tracks.len() == 1 { "" } else { "s" }
+        ));
+        for track in &tracks {
+            if self.cancel.load(Ordering::SeqCst) {
+                return;
+            }
+            if let Err(e) = trim_silence(ffmpeg, track) {
+                self.sink.push_line(&format!(
+                    "WARNING: Couldn't trim silence from {}: {}",
+                    track.display(),
+                    e
+                ));
+            }
+        }
+    }
+
+    /// Embeds `image` as the cover of every track the run created.
+    fn embed_custom_cover(&self, image: &Path, ffmpeg: &Path) {
+        let tracks = self.created_tracks();
+        if tracks.is_empty() {
+            return;
+        }
+        self.sink.push_line(&format!(
+            "Embedding the cover art in {} track{}",
+            tracks.len(),
+            if tracks.len() == 1 { "" } else { "s" }
+        ));
+        let mut failed = 0;
+        for track in &tracks {
+            if self.cancel.load(Ordering::SeqCst) {
+                return;
+            }
+            if embed_cover(ffmpeg, track, image).is_err() {
+                failed += 1;
+            }
+        }
+        *self.cover_result.lock().unwrap() = Some((tracks.len() - failed, failed));
+    }
+
+    /// Makes a preview copy of every audio or video file the run created,
+    /// adding the previews to `created`.
+    fn make_previews(&self, ffmpeg: &Path, bitrate: &str) {
+        let mut sources: Vec<PathBuf> = self
+            .sink
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| (is_audio_file(p) || is_video_file(p)) && !is_preview(p) && p.is_file())
+            .cloned()
+            .collect();
+        sources.sort();
+        sources.dedup();
+        let mut made = 0;
+        for (i, src) in sources.iter().enumerate() {
+            if self.cancel.load(Ordering::SeqCst) {
+                return;
+            }
+            self.sink.push_line(&format!(
+                "{} {} ({} of {})",
+                MAKING_PREVIEW,
+                src.display(),
+                i + 1,
+                sources.len()
+            ));
+            if let Ok(preview) = make_preview(ffmpeg, src, bitrate) {
+                self.sink.created.lock().unwrap().push(preview);
+                made += 1;
+            }
+        }
+        if !sources.is_empty() {
+            *self.preview_result.lock().unwrap() = Some((made, sources.len() - made));
+        }
+    }
+
+    /// Looks up lyrics for the tracks the run created and embeds the ones
+    /// found. Tracks without artist and title tags aren't looked up.
+    #[cfg(feature = "lyrics")]
+    fn embed_lyrics(&self, provider: &lyrics::Provider, ffmpeg: &Path) {
+        let tracks = self.created_tracks();
+        if tracks.is_empty() {
+            return;
+        }
+        self.sink.push_line(&format!(
+            "Looking up lyrics for {} track{}",
+            tracks.len(),
+            if tracks.len() == 1 { "" } else { "s" }
+        ));
+        let mut embedded = 0;
+        for track in &tracks {
+            if self.cancel.load(Ordering::SeqCst) {
+                return;
+            }
+            let Ok(tags) = read_tags(ffmpeg, track) else {
+                continue;
+            };
+            let (Some(artist), Some(title)) = (tags.get("artist"), tags.get("title")) else {
+                continue;
+            };
+            if let Some(text) = provider.fetch_lyrics(artist, title) {
+                if lyrics::embed_lyrics(ffmpeg, track, &text).is_ok() {
+                    embedded += 1;
                 }
             }
+        }
+        *self.lyrics_result.lock().unwrap() = Some((embedded, tracks.len()));
+    }
 
-            if app.check_download() {
-                // Download finished
+    /// Whether the item that just finished was slow throughout: enough
+    /// speed samples, with the median under the threshold.
+    fn item_was_slow(&self) -> bool {
+        let Some(threshold) = self.slow_speed else {
+            return false;
+        };
+        let mut speeds = std::mem::take(&mut *self.sink.speeds.lock().unwrap());
+        if speeds.len() < 3 {
+            return false;
+        }
+        speeds.sort_by(f64::total_cmp);
+        speeds[speeds.len() / 2] < threshold
+    }
+
+    /// Asks the UI whether to switch to lower quality and waits for the
+    /// answer.
+    fn ask_lower_quality(&self) -> bool {
+        *self.quality_prompt.lock().unwrap() = QualityPrompt::Asking;
+        loop {
+            if self.cancel.load(Ordering::SeqCst) {
+                return false;
             }
-            continue;
+            match *self.quality_prompt.lock().unwrap() {
+                QualityPrompt::Accepted => return true,
+                QualityPrompt::Declined => return false,
+                _ => {}
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Runs one item, holding off while paused. A child that exits while
+    /// paused was killed to pause it (see `set_child_paused`), so the item is
+    /// run again once resumed.
+    fn run_item(&self, args: &[String]) -> io::Result<ExitStatus> {
+        loop {
+            while self.paused.load(Ordering::SeqCst) && !self.cancel.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(100));
+            }
+            if self.cancel.load(Ordering::SeqCst) {
+                return Ok(ExitStatus::default());
+            }
+            let status = run_yt_dlp(args, &self.sink, &self.child)?;
+            if !self.paused.load(Ordering::SeqCst) || self.cancel.load(Ordering::SeqCst) {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Runs one item, and when YouTube answers 429 waits half a minute or so
+    /// and tries again. Retrying straight away only prolongs the block.
+    fn run_item_backing_off(&self, args: &[String]) -> io::Result<ExitStatus> {
+        let mut retries = 0;
+        loop {
+            let seen = self.sink.output.lock().unwrap().total();
+            let status = self.run_item(args)?;
+            if let Some(how) = abnormal_exit(status) {
+                // Killed by ytd itself to skip or cancel.
+                if !self.cancel.load(Ordering::SeqCst) && !self.skip.load(Ordering::SeqCst) {
+                    self.sink.push_line(&format!("{} ({})", CRASH_MARKER, how));
+                }
+            }
+            if exited_ok(status)
+                || retries == RATE_LIMIT_RETRIES
+                || self.cancel.load(Ordering::SeqCst)
+                || self.skip.load(Ordering::SeqCst)
+            {
+                return Ok(status);
+            }
+            let output: Vec<String> = self
+                .sink
+                .output
+                .lock()
+                .unwrap()
+                .since(seen)
+                .map(String::from)
+                .collect();
+            if classify_error(&output.join("\n")) != Some(FailureKind::RateLimited) {
+                return Ok(status);
+            }
+            retries += 1;
+            let wait = rate_limit_backoff();
+            self.sink.push_line(&format!(
+                "Rate limited (HTTP 429), retrying in {}s",
+                wait.as_secs()
+            ));
+            let until = Instant::now() + wait;
+            *self.rate_limited_until.lock().unwrap() = Some(until);
+            while Instant::now() < until
+                && !self.cancel.load(Ordering::SeqCst)
+                && !self.skip.load(Ordering::SeqCst)
+            {
+                thread::sleep(Duration::from_millis(100));
+            }
+            *self.rate_limited_until.lock().unwrap() = None;
+        }
+    }
+
+    /// Downloads one URL. Playlists run as one child per item so a single hung
+    /// video can be killed without taking the rest of the playlist with it.
+    /// Returns whether everything that wasn't skipped succeeded, or an error
+    /// if yt-dlp couldn't be started at all.
+    fn download_url(
+        &self,
+        url: &str,
+        args: Vec<String>,
+        known_count: Option<usize>,
+    ) -> io::Result<bool> {
+        self.current_item.store(0, Ordering::SeqCst);
+        self.total_items.store(0, Ordering::SeqCst);
+
+        let count = known_count.or_else(|| {
+            is_playlist_url(url)
+                .then(|| count_playlist_items(url))
+                .flatten()
+        });
+        let runs: Vec<Vec<String>> = match count {
+            Some(n) if n > 1 => {
+                let runs = per_item_runs(&args, url, n, self.latest_items);
+                self.total_items.store(runs.len(), Ordering::SeqCst);
+                runs
+            }
+            _ => vec![args],
+        };
+
+        let mut runs = runs;
+        let mut success = true;
+        let mut downloaded = 0;
+        // Asked at most once per URL; a "no" sticks.
+        let mut quality_asked = false;
+        for i in 0..runs.len() {
+            if self.cancel.load(Ordering::SeqCst) {
+                return Ok(false);
+            }
+            if runs.len() > 1 && i + 1 < self.playlist_start {
+                continue;
+            }
+            if self.max_downloads.is_some_and(|max| downloaded >= max) {
+                self.sink
+                    .push_line(&format!("Reached the limit of {} downloads", downloaded));
+                break;
+            }
+            self.current_item.store(i + 1, Ordering::SeqCst);
+            self.sink.speeds.lock().unwrap().clear();
+
+            let started_before = self.sink.files_started.load(Ordering::SeqCst);
+            let status = self.run_item_backing_off(&runs[i])?;
+            // An item already in the archive exits fine without writing
+            // anything, and doesn't use up the limit.
+            let wrote_files = self.sink.files_started.load(Ordering::SeqCst) > started_before;
+            if self.skip.swap(false, Ordering::SeqCst) {
+                self.sink
+                    .push_line(&format!("Skipped item {} of {}", i + 1, runs.len()));
+            } else if status.code() == Some(MAX_DOWNLOADS_REACHED) {
+                break;
+            } else if exited_ok(status) {
+                if wrote_files {
+                    downloaded += 1;
+                }
+            } else {
+                success = false;
+            }
+
+            // Between items is the natural point to change quality.
+            if !quality_asked && i + 1 < runs.len() && self.item_was_slow() {
+                quality_asked = true;
+                if self.ask_lower_quality() {
+                    self.sink
+                        .push_line("Switching the remaining items to lower quality");
+                    for later in &mut runs[i + 1..] {
+                        if let Some(at) = later.iter().position(|a| a == "-f") {
+                            later[at + 1] = self.low_quality_format.to_string();
+                        }
+                    }
+                }
+            }
+        }
+        Ok(success && !self.cancel.load(Ordering::SeqCst))
+    }
+}
+
+struct App {
+    config: Config,
+    music_dir: PathBuf,
+    history: History,
+    state: AppState,
+    playlist_name: String,
+    url: String,
+    error_message: String,
+    files_downloaded: Vec<String>,
+    download_output: Arc<Mutex<OutputLog>>,
+    download_output_final: String,
+    download_done: Arc<AtomicBool>,
+    download_success: Arc<AtomicBool>,
+    options: DownloadOptions,
+    low_bandwidth: bool,
+    current_child: Arc<Mutex<Option<Child>>>,
+    cancel_requested: Arc<AtomicBool>,
+    /// Set by the SIGTERM handler; the loops shut down when they see it.
+    terminate_requested: Arc<AtomicBool>,
+    skip_requested: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    current_item: Arc<AtomicUsize>,
+    total_items: Arc<AtomicUsize>,
+    files_started: Arc<AtomicUsize>,
+    skipped: Arc<AtomicUsize>,
+    /// Files this run wrote, from yt-dlp's output; what undo deletes.
+    created: Arc<Mutex<Vec<PathBuf>>>,
+    /// Videos this run downloaded; what undo takes out of the archive.
+    video_ids: Arc<Mutex<Vec<String>>>,
+    fragments_skipped: Arc<AtomicUsize>,
+    too_long: Arc<AtomicUsize>,
+    /// Shift+D on the Done screen is waiting for y/n.
+    confirm_undo: bool,
+    progress: Arc<Mutex<Progress>>,
+    compact: bool,
+    /// Show the raw yt-dlp output instead of the progress view.
+    show_full_log: bool,
+    /// How many lines above the bottom the full log is scrolled.
+    log_scroll: usize,
+    /// The newest error line the full log has already followed.
+    seen_error: Option<usize>,
+    /// The error line the full log is held on while output keeps coming,
+    /// until the user scrolls.
+    log_pin: Option<usize>,
+    /// The full-screen log pager, open over the download screen.
+    pager: Option<Pager>,
+    /// The URLs from the URL field, downloaded one after another.
+    url_queue: Vec<String>,
+    /// 1-based position in `url_queue` of the URL being downloaded.
+    queue_position: Arc<AtomicUsize>,
+    queue_selected: usize,
+    /// Outcome of each queued URL, in order, filled in by the worker.
+    url_results: Arc<Mutex<Vec<UrlResult>>>,
+    /// Entry count of the last playlist URL that was inspected, keyed by URL
+    /// so going back and forth on the confirm screen doesn't recount.
+    item_count: PerUrl<ItemCount>,
+    /// Only fetch uploads since this URL was last downloaded.
+    only_new: bool,
+    /// Pick up a cancelled playlist where it stopped, from the history's
+    /// resume point.
+    resume: bool,
+    /// A manually entered `--dateafter`, which wins over the history.
+    date_after_override: Option<String>,
+    field_input: String,
+    input_error: Option<String>,
+    settings_selected: usize,
+    /// Where Esc on the settings screen goes back to.
+    settings_return: AppState,
+    /// Why the last settings change couldn't be saved, if it couldn't.
+    settings_error: Option<String>,
+    rename_selected: usize,
+    setup_selected: usize,
+    /// The selectable list on screen, if any. Set while drawing.
+    list_area: Option<ListArea>,
+    /// Whether yt-dlp and ffmpeg were found, checked on the wizard's last
+    /// page.
+    setup_tools: Option<(bool, bool)>,
+    /// Shown on the first screen, e.g. that ffmpeg is missing.
+    startup_warning: Option<String>,
+    /// Gathered when the diagnostics screen opens, plus how the last copy
+    /// or cache clear went.
+    diagnostics: Option<(Diagnostics, Option<Result<String, String>>)>,
+    /// Updating the managed yt-dlp.
+    yt_dlp_update: BackgroundJob,
+    /// Selection and scroll position of the Done screen's file list.
+    done_list_state: ListState,
+    /// Outcome of the last play or undo on the Done screen, `Err` shown in
+    /// red.
+    done_status: Option<Result<String, String>>,
+    /// The new name being typed for the selected file, without its extension.
+    rename_input: Option<String>,
+    /// Typed on the confirm screen when login is enabled. Kept in memory for
+    /// this session only.
+    password: Option<String>,
+    /// This run's staging directory when staged downloads are on.
+    staging_dir: Option<PathBuf>,
+    /// The URL field takes a search query instead of URLs.
+    search_mode: bool,
+    search: Arc<Mutex<Lookup<SearchResult>>>,
+    search_selected: usize,
+    /// Held from the start of a download until it finishes, or until ytd
+    /// exits.
+    lock: Option<LockGuard>,
+    formats: Arc<Mutex<Lookup<FormatOption>>>,
+    format_selected: usize,
+    /// Label of the format picked from the list, shown instead of the raw
+    /// selector.
+    format_label: Option<String>,
+    /// The format picker was opened because the chosen format wasn't
+    /// available; picking one retries the download straight away.
+    retry_with_format: bool,
+    /// The picker was opened by Enter on the confirm screen, so picking
+    /// goes on to the download.
+    pick_then_download: bool,
+    /// When a confirmed download is to start, for off-peak hours.
+    scheduled_for: Option<DateTime<Local>>,
+    /// Set in `--watch` mode, for as long as it lasts.
+    watch: Option<Watch>,
+    /// The video was blocked and this run fell back to audio only.
+    audio_fallback_used: bool,
+    last_run: Option<LastRun>,
+    /// What went wrong with the last download, if it was recognised.
+    failure: Option<FailureKind>,
+    /// Files in the playlist that look like the queued video, keyed by URL
+    /// like `item_count`.
+    duplicates: PerUrl<Lookup<String>>,
+    /// Where the global archive says the queued video already went.
+    archived_in: Option<(String, String)>,
+    /// Whether the queued video is a live stream; `None` while yt-dlp is
+    /// still being asked.
+    live_status: PerUrl<Option<LiveStatus>>,
+    /// Estimated download size, keyed by URL and format together so a new
+    /// format asks again. `None` while yt-dlp is still being asked.
+    size_estimate: PerUrl<Option<SizeEstimate>>,
+    /// Structured progress for the headless `--json` mode.
+    events: Option<Sender<DownloadEvent>>,
+    /// Running from `--url` without the UI, so nobody is there to answer a
+    /// prompt.
+    headless: bool,
+    /// Embedded as the cover of every track after the download, replacing
+    /// the thumbnail.
+    cover_image: Option<PathBuf>,
+    /// How many files got the custom cover, and how many failed.
+    cover_result: Arc<Mutex<Option<(usize, usize)>>>,
+    /// How many previews were made, and how many failed.
+    preview_result: Arc<Mutex<Option<(usize, usize)>>>,
+    /// How many files `organize_by_tags` moved and the folders, relative to
+    /// the playlist folder, that tracks went into; or why it couldn't.
+    organize_result: Option<Result<(usize, Vec<String>), String>>,
+    /// Staged files left in the staging folder because a file of the same
+    /// name was already in the playlist folder.
+    staging_kept: Vec<PathBuf>,
+    /// What max quality audio resolved to, per downloaded track.
+    resolved_audio: Vec<AudioInfo>,
+    /// How many tracks got lyrics embedded, out of how many were looked up.
+    #[cfg(feature = "lyrics")]
+    lyrics_result: Arc<Mutex<Option<(usize, usize)>>>,
+    quality_prompt: Arc<Mutex<QualityPrompt>>,
+    /// When the worker will retry after a 429.
+    rate_limited_until: Arc<Mutex<Option<Instant>>>,
+    /// How the post-download hook went, once one has been started.
+    hook_status: Arc<Mutex<Option<Line<'static>>>>,
+    hook_running: Arc<AtomicBool>,
+}
+
+impl App {
+    fn new(config: Config, music_dir: PathBuf, history: History) -> Self {
+        let options = DownloadOptions {
+            format: config
+                .format
+                .clone()
+                .unwrap_or_else(|| DownloadOptions::default().format),
+            // The config file skips the check the input field does.
+            audio_language: config
+                .audio_language
+                .clone()
+                .filter(|lang| is_valid_language(lang)),
+            genre: config.genre.clone().filter(|g| validate_genre(g).is_ok()),
+            max_duration: config.max_duration.as_deref().and_then(parse_duration),
+            ..DownloadOptions::default()
+        };
+        let output = OutputLog::new(config.max_output_lines);
+        Self {
+            config,
+            music_dir,
+            history,
+            state: AppState::InputPlaylistName,
+            playlist_name: String::new(),
+            url: String::new(),
+            error_message: String::new(),
+            files_downloaded: Vec::new(),
+            download_output: Arc::new(Mutex::new(output)),
+            download_output_final: String::new(),
+            download_done: Arc::new(AtomicBool::new(false)),
+            download_success: Arc::new(AtomicBool::new(false)),
+            options,
+            low_bandwidth: false,
+            current_child: Arc::new(Mutex::new(None)),
+            cancel_requested: Arc::new(AtomicBool::new(false)),
+            terminate_requested: Arc::new(AtomicBool::new(false)),
+            skip_requested: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            current_item: Arc::new(AtomicUsize::new(0)),
+            total_items: Arc::new(AtomicUsize::new(0)),
+            files_started: Arc::new(AtomicUsize::new(0)),
+            skipped: Arc::new(AtomicUsize::new(0)),
+            created: Arc::new(Mutex::new(Vec::new())),
+            video_ids: Arc::new(Mutex::new(Vec::new())),
+            fragments_skipped: Arc::new(AtomicUsize::new(0)),
+            too_long: Arc::new(AtomicUsize::new(0)),
+            confirm_undo: false,
+            progress: Arc::new(Mutex::new(Progress::default())),
+            compact: false,
+            show_full_log: false,
+            log_scroll: 0,
+            seen_error: None,
+            log_pin: None,
+            pager: None,
+            url_queue: Vec::new(),
+            queue_position: Arc::new(AtomicUsize::new(0)),
+            url_results: Arc::new(Mutex::new(Vec::new())),
+            queue_selected: 0,
+            item_count: Arc::new(Mutex::new(None)),
+            only_new: false,
+            resume: false,
+            date_after_override: None,
+            field_input: String::new(),
+            input_error: None,
+            settings_selected: 0,
+            settings_return: AppState::InputPlaylistName,
+            settings_error: None,
+            rename_selected: 0,
+            setup_selected: 0,
+            list_area: None,
+            setup_tools: None,
+            startup_warning: None,
+            diagnostics: None,
+            yt_dlp_update: Arc::new(Mutex::new(None)),
+            done_list_state: ListState::default(),
+            done_status: None,
+            rename_input: None,
+            password: None,
+            staging_dir: None,
+            search_mode: false,
+            search: Arc::new(Mutex::new(Lookup::Ready(Vec::new()))),
+            search_selected: 0,
+            lock: None,
+            formats: Arc::new(Mutex::new(Lookup::Ready(Vec::new()))),
+            format_selected: 0,
+            format_label: None,
+            retry_with_format: false,
+            pick_then_download: false,
+            scheduled_for: None,
+            watch: None,
+            audio_fallback_used: false,
+            last_run: None,
+            failure: None,
+            duplicates: Arc::new(Mutex::new(None)),
+            archived_in: None,
+            live_status: Arc::new(Mutex::new(None)),
+            size_estimate: Arc::new(Mutex::new(None)),
+            events: None,
+            headless: false,
+            cover_image: None,
+            cover_result: Arc::new(Mutex::new(None)),
+            preview_result: Arc::new(Mutex::new(None)),
+            resolved_audio: Vec::new(),
+            organize_result: None,
+            staging_kept: Vec::new(),
+            #[cfg(feature = "lyrics")]
+            lyrics_result: Arc::new(Mutex::new(None)),
+            quality_prompt: Arc::new(Mutex::new(QualityPrompt::Idle)),
+            rate_limited_until: Arc::new(Mutex::new(None)),
+            hook_status: Arc::new(Mutex::new(None)),
+            hook_running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// The queued URL, when only one was entered.
+    fn single_url(&self) -> Option<&str> {
+        match self.url_queue.as_slice() {
+            [url] => Some(url.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The `--dateafter` a download of `url` will use: a manual date if one
+    /// was entered, otherwise the last successful run of that URL when "only
+    /// new" is on. `None` on a first run means a full download.
+    fn date_after_for(&self, url: &str) -> Option<String> {
+        if let Some(date) = &self.date_after_override {
+            return Some(date.clone());
+        }
+        if !self.only_new {
+            return None;
+        }
+        self.history
+            .last_run(url)
+            .map(|t| t.format("%Y%m%d").to_string())
+    }
+
+    fn begin_edit(&mut self, field: Field) {
+        self.field_input = match field {
+            Field::DateAfter => self.date_after_override.clone().unwrap_or_default(),
+            Field::AudioLanguage => self.options.audio_language.clone().unwrap_or_default(),
+            Field::Username => self.config.username.clone().unwrap_or_default(),
+            Field::Password => self.password.clone().unwrap_or_default(),
+            Field::MaxDownloads => self
+                .options
+                .max_downloads
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            Field::LatestItems => self
+                .options
+                .latest_items
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            Field::StartAt => self
+                .scheduled_for
+                .map(|t| t.format("%H:%M").to_string())
+                .unwrap_or_default(),
+            Field::CookiesFile => self
+                .config
+                .cookies_file
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            Field::CoverImage => self
+                .cover_image
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            Field::Genre => self.options.genre.clone().unwrap_or_default(),
+            Field::MaxDuration => self
+                .options
+                .max_duration
+                .map(format_duration)
+                .unwrap_or_default(),
+        };
+        self.input_error = None;
+        self.state = AppState::EditField(field);
+    }
+
+    fn open_settings(&mut self) {
+        self.settings_return = self.state;
+        self.settings_error = None;
+        self.state = AppState::Settings;
+    }
+
+    fn toggle_selected_setting(&mut self) {
+        SETTINGS[self.settings_selected].toggle(&mut self.config);
+        self.settings_error = self.config.save().err();
+    }
+
+    fn begin_rename(&mut self) {
+        let Some(name) = self.files_downloaded.get(self.rename_selected) else {
+            return;
+        };
+        let stem = Path::new(name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.rename_input = Some(stem);
+        self.input_error = None;
+    }
+
+    /// Renames the selected file in place, keeping its folder and extension.
+    /// Refuses to overwrite another file.
+    fn commit_rename(&mut self) {
+        let Some(new_stem) = self.rename_input.clone() else {
+            return;
+        };
+        let new_stem = new_stem.trim();
+        if let Err(e) = validate_file_name(new_stem) {
+            self.input_error = Some(e);
+            return;
+        }
+
+        let old_name = self.files_downloaded[self.rename_selected].clone();
+        let old_path = Path::new(&old_name);
+        let file_name = match old_path.extension() {
+            Some(ext) => format!("{}.{}", new_stem, ext.to_string_lossy()),
+            None => new_stem.to_string(),
+        };
+        let new_name = old_path
+            .with_file_name(file_name)
+            .to_string_lossy()
+            .into_owned();
+        if new_name != old_name {
+            let dir = self.playlist_dir();
+            let target = dir.join(&new_name);
+            if target.exists() {
+                self.input_error = Some(format!("{} already exists", new_name));
+                return;
+            }
+            let source = dir.join(&old_name);
+            if let Err(e) = std::fs::rename(&source, &target) {
+                self.input_error = Some(format!("Rename failed: {}", e));
+                return;
+            }
+            // Undo should still find it under its new name.
+            for path in self.created.lock().unwrap().iter_mut() {
+                if *path == source {
+                    *path = target.clone();
+                }
+            }
+            self.files_downloaded[self.rename_selected] = new_name;
+        }
+        self.rename_input = None;
+        self.input_error = None;
+    }
+
+    /// Validates and stores the edited value. An empty input clears it.
+    fn commit_edit(&mut self, field: Field) {
+        let input = self.field_input.trim();
+        match field {
+            Field::DateAfter => {
+                if input.is_empty() {
+                    self.date_after_override = None;
+                } else if let Some(date) = parse_date_input(input) {
+                    self.date_after_override = Some(date);
+                } else {
+                    self.input_error = Some("Not a valid date".to_string());
+                    return;
+                }
+            }
+            Field::AudioLanguage => {
+                if input.is_empty() {
+                    self.options.audio_language = None;
+                } else if is_valid_language(input) {
+                    self.options.audio_language = Some(input.to_string());
+                } else {
+                    self.input_error = Some("Not a valid language code".to_string());
+                    return;
+                }
+            }
+            Field::MaxDownloads => {
+                if input.is_empty() {
+                    self.options.max_downloads = None;
+                } else if let Some(n) = input.parse().ok().filter(|&n: &usize| n > 0) {
+                    self.options.max_downloads = Some(n);
+                } else {
+                    self.input_error = Some("Enter a whole number above zero".to_string());
+                    return;
+                }
+            }
+            Field::LatestItems => {
+                if input.is_empty() {
+                    self.options.latest_items = None;
+                } else if let Some(n) = input.parse().ok().filter(|&n: &usize| n > 0) {
+                    self.options.latest_items = Some(n);
+                } else {
+                    self.input_error = Some("Enter a whole number above zero".to_string());
+                    return;
+                }
+            }
+            Field::StartAt => {
+                if input.is_empty() {
+                    self.scheduled_for = None;
+                } else if let Some(at) = NaiveTime::parse_from_str(input, "%H:%M")
+                    .ok()
+                    .and_then(|t| next_occurrence(Local::now(), t))
+                {
+                    self.scheduled_for = Some(at);
+                } else {
+                    self.input_error = Some("Enter a time like 02:00 or 23:30".to_string());
+                    return;
+                }
+            }
+            Field::CoverImage => {
+                if input.is_empty() {
+                    self.cover_image = None;
+                } else {
+                    let path = PathBuf::from(input);
+                    if let Err(e) = validate_cover_image(&path) {
+                        self.input_error = Some(e);
+                        return;
+                    }
+                    self.cover_image = Some(path);
+                }
+            }
+            Field::CookiesFile => {
+                let path = (!input.is_empty()).then(|| PathBuf::from(input));
+                if let Some(p) = path.as_ref().filter(|p| !p.is_file()) {
+                    self.input_error = Some(format!("{} does not exist", p.display()));
+                    return;
+                }
+                self.config.cookies_file = path;
+                if let Err(e) = self.config.save() {
+                    self.input_error = Some(e);
+                    return;
+                }
+            }
+            Field::Genre => {
+                if input.is_empty() {
+                    self.options.genre = None;
+                } else if let Err(e) = validate_genre(input) {
+                    self.input_error = Some(e);
+                    return;
+                } else {
+                    self.options.genre = Some(input.to_string());
+                }
+            }
+            Field::MaxDuration => {
+                if input.is_empty() {
+                    self.options.max_duration = None;
+                } else if let Some(seconds) = parse_duration(input) {
+                    self.options.max_duration = Some(seconds);
+                } else {
+                    self.input_error =
+                        Some("Enter a length like 10:00, 1:30:00 or 600".to_string());
+                    return;
+                }
+            }
+            Field::Username => {
+                self.config.username = (!input.is_empty()).then(|| input.to_string());
+                if let Err(e) = self.config.save() {
+                    self.input_error = Some(e);
+                    return;
+                }
+            }
+            // Not trimmed: spaces can be part of a password.
+            Field::Password => {
+                self.password = (!self.field_input.is_empty()).then(|| self.field_input.clone());
+            }
+        }
+        self.input_error = None;
+        self.state = AppState::Confirm;
+    }
+
+    /// Turns a mouse event into the key it stands for: the wheel scrolls
+    /// like Up/Down. A left click selects the list item under it and needs
+    /// no key.
+    fn mouse_key(&mut self, mouse: MouseEvent) -> Option<KeyEvent> {
+        match mouse.kind {
+            MouseEventKind::ScrollUp => Some(KeyEvent::from(KeyCode::Up)),
+            MouseEventKind::ScrollDown => Some(KeyEvent::from(KeyCode::Down)),
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.click_list(mouse.column, mouse.row);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn click_list(&mut self, column: u16, row: u16) {
+        let Some(i) = self.list_area.and_then(|l| l.item_at(column, row)) else {
+            return;
+        };
+        match self.state {
+            AppState::SearchResults if i < self.search.lock().unwrap().len() => {
+                self.search_selected = i
+            }
+            AppState::Formats if i < self.formats.lock().unwrap().len() => self.format_selected = i,
+            AppState::Queue if i < self.url_queue.len() => self.queue_selected = i,
+            AppState::Settings if i < SETTINGS.len() => self.settings_selected = i,
+            AppState::Setup(SetupStep::Format) if i < SETUP_FORMATS.len() => {
+                self.setup_selected = i
+            }
+            AppState::Rename if self.rename_input.is_none() && i < self.files_downloaded.len() => {
+                self.rename_selected = i
+            }
+            AppState::Done if i < self.files_downloaded.len() => {
+                self.done_list_state.select(Some(i))
+            }
+            _ => {}
+        }
+    }
+
+    /// Skips the playlist prompt for the configured default playlist.
+    fn use_default_playlist(&mut self) {
+        let Some(name) = self.config.default_playlist.as_deref().map(str::trim) else {
+            return;
+        };
+        if !name.is_empty() {
+            self.playlist_name = name.to_string();
+            self.state = AppState::InputUrl;
+        }
+    }
+
+    /// `url` without tracking parameters, as queued and recorded in history.
+    fn clean_url(&self, url: &str) -> String {
+        normalize_url(url, self.config.keep_url_timestamp)
+    }
+
+    /// Enter on the confirm screen: download now, or wait for the start time
+    /// if one is set.
+    fn confirm_download(&mut self) {
+        // Unless a format was already picked by hand.
+        if self.config.always_ask_format
+            && self.format_label.is_none()
+            && self.single_url().is_some()
+        {
+            self.pick_then_download = true;
+            self.open_format_picker();
+            return;
+        }
+        if self.scheduled_for.is_some() {
+            self.state = AppState::Scheduled;
+        } else {
+            self.state = AppState::Downloading;
+            self.start_download();
+        }
+    }
+
+    /// Starts the scheduled download once its time has come.
+    fn check_schedule(&mut self) {
+        if self.scheduled_for.is_some_and(|at| Local::now() >= at) {
+            self.scheduled_for = None;
+            self.state = AppState::Downloading;
+            self.start_download();
+        }
+    }
+
+    /// Starts `--watch` on the queued URL, with the first check right away.
+    fn begin_watch(&mut self) {
+        self.watch = Some(Watch {
+            next_check: Instant::now(),
+            results: Vec::new(),
+        });
+        self.state = AppState::Watching;
+    }
+
+    fn check_watch(&mut self) {
+        if self
+            .watch
+            .as_ref()
+            .is_some_and(|w| Instant::now() >= w.next_check)
+        {
+            self.state = AppState::Downloading;
+            self.start_download();
+        }
+    }
+
+    /// Notes how a check went and waits for the next one. A failed check,
+    /// e.g. with the network down, is logged like any other.
+    fn finish_watch_check(&mut self) {
+        let new_files = self
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| self.is_output_file(p) && p.is_file())
+            .count();
+        let time = Local::now().format("%H:%M");
+        let line = if self.state == AppState::Done {
+            Line::from(match new_files {
+                0 => format!("{} No new items", time),
+                1 => format!("{} 1 new file", time),
+                n => format!("{} {} new files", time, n),
+            })
+        } else {
+            Line::styled(
+                format!("{} Check failed: {}", time, self.error_message),
+                Style::default().fg(Color::Red),
+            )
+        };
+        let interval = Duration::from_secs(self.config.watch_interval_minutes.max(1) * 60);
+        self.reset_download_state();
+        if let Some(watch) = self.watch.as_mut() {
+            watch.results.push(line);
+            let excess = watch.results.len().saturating_sub(WATCH_LOG_LINES);
+            watch.results.drain(..excess);
+            watch.next_check = Instant::now() + interval;
+        }
+        self.state = AppState::Watching;
+    }
+
+    /// Seconds until the next watch check.
+    fn watch_countdown(&self) -> u64 {
+        self.watch.as_ref().map_or(0, |w| {
+            w.next_check
+                .saturating_duration_since(Instant::now())
+                .as_secs()
+        })
+    }
+
+    /// Starts the first-run wizard, with the music folder prefilled.
+    fn begin_setup(&mut self) {
+        self.field_input = self.music_dir.display().to_string();
+        self.input_error = None;
+        self.state = AppState::Setup(SetupStep::MusicDir);
+    }
+
+    fn advance_setup(&mut self, step: SetupStep) {
+        match step {
+            SetupStep::MusicDir => {
+                let dir = self.field_input.trim();
+                if !dir.is_empty() && !Path::new(dir).is_absolute() {
+                    self.input_error = Some("Use a full path, e.g. /home/me/Music".to_string());
+                    return;
+                }
+                self.config.music_dir = (!dir.is_empty()).then(|| PathBuf::from(dir));
+                self.state = AppState::Setup(SetupStep::Format);
+            }
+            SetupStep::Format => {
+                let (_, format, free) = SETUP_FORMATS[self.setup_selected];
+                self.config.format = format.map(String::from);
+                self.config.prefer_free_formats = free;
+                self.setup_tools = Some((
+                    command_available(yt_dlp(), "--version"),
+                    command_available(self.config.ffmpeg_program(), "-version"),
+                ));
+                self.state = AppState::Setup(SetupStep::Tools);
+            }
+            SetupStep::Tools => self.finish_setup(),
+        }
+    }
+
+    /// Esc in the wizard: drop the answers given so far and save the
+    /// defaults, which still counts as set up.
+    fn skip_setup(&mut self) {
+        let defaults = Config::default();
+        self.config.music_dir = defaults.music_dir;
+        self.config.format = defaults.format;
+        self.config.prefer_free_formats = defaults.prefer_free_formats;
+        self.finish_setup();
+    }
+
+    /// Writes the config, so later launches skip the wizard, and applies it.
+    fn finish_setup(&mut self) {
+        if let Err(e) = self.config.save() {
+            self.input_error = Some(e);
+            return;
+        }
+        if let Ok(dir) = self.config.music_dir() {
+            self.music_dir = dir;
+        }
+        if let Some(format) = &self.config.format {
+            self.options.format = format.clone();
+        }
+        self.input_error = None;
+        self.state = AppState::InputPlaylistName;
+    }
+
+    /// For `--clip`: queues the clipboard's URL into the last used playlist
+    /// and opens the confirm screen. Without a usable URL the normal first
+    /// screen stays, with a note saying why.
+    fn start_from_clipboard(&mut self) {
+        let Some(url) = clipboard::read_clipboard().filter(|t| is_url(t)) else {
+            self.input_error = Some("No URL on the clipboard".to_string());
+            return;
+        };
+        self.playlist_name = self
+            .history
+            .last_playlist()
+            .unwrap_or("Downloads")
+            .to_string();
+        self.url = url.clone();
+        self.url_queue = vec![self.clean_url(&url)];
+        self.state = AppState::Confirm;
+        self.request_item_count();
+        self.request_duplicate_check();
+        self.request_live_check();
+    }
+
+    /// Counts the playlist's entries in the background, unless this URL was
+    /// already counted.
+    fn request_item_count(&mut self) {
+        let Some(url) = self.single_url().map(String::from) else {
+            return;
+        };
+        if !is_playlist_url(&url) || self.item_count() != ItemCount::Unknown {
+            return;
+        }
+        *self.item_count.lock().unwrap() = Some((url.clone(), ItemCount::Counting));
+
+        let count_ref = self.item_count.clone();
+        thread::spawn(move || {
+            let count = count_playlist_items(&url).map_or(ItemCount::Unknown, ItemCount::Known);
+            let mut slot = count_ref.lock().unwrap();
+            // The URL may have been edited while we were counting.
+            if slot.as_ref().is_some_and(|(u, _)| *u == url) {
+                *slot = Some((url, count));
+            }
+        });
+    }
+
+    /// Runs the search typed into the URL field in the background.
+    fn start_search(&mut self) {
+        let query = self.url.trim().to_string();
+        let n = self.config.search_results.max(1);
+        *self.search.lock().unwrap() = Lookup::Pending;
+        self.search_selected = 0;
+        self.state = AppState::SearchResults;
+
+        let search_ref = self.search.clone();
+        thread::spawn(move || {
+            let state = match search_youtube(&query, n) {
+                Ok(results) => Lookup::Ready(results),
+                Err(e) => Lookup::Failed(e),
+            };
+            *search_ref.lock().unwrap() = state;
+        });
+    }
+
+    fn is_searching(&self) -> bool {
+        matches!(*self.search.lock().unwrap(), Lookup::Pending)
+    }
+
+    /// Queues the highlighted search result and moves on to the confirm
+    /// screen.
+    fn choose_search_result(&mut self) {
+        let url = match &*self.search.lock().unwrap() {
+            Lookup::Ready(results) => results.get(self.search_selected).map(|r| r.url()),
+            _ => None,
+        };
+        if let Some(url) = url {
+            self.url_queue = vec![url];
+            self.state = AppState::Confirm;
+            self.request_duplicate_check();
+            self.request_live_check();
+        }
+    }
+
+    /// Moves the selected queue entry up (`-1`) or down (`1`) one place,
+    /// keeping it selected.
+    fn move_queue_item(&mut self, by: isize) {
+        let from = self.queue_selected;
+        let Some(to) = from
+            .checked_add_signed(by)
+            .filter(|&to| to < self.url_queue.len())
+        else {
+            return;
+        };
+        self.url_queue.swap(from, to);
+        self.queue_selected = to;
+        self.sync_url_field();
+    }
+
+    fn remove_queue_item(&mut self) {
+        if self.queue_selected < self.url_queue.len() {
+            self.url_queue.remove(self.queue_selected);
+            self.queue_selected = self
+                .queue_selected
+                .min(self.url_queue.len().saturating_sub(1));
+            self.sync_url_field();
+        }
+    }
+
+    /// Keeps the URL field matching the edited queue, for when the user goes
+    /// back to it.
+    fn sync_url_field(&mut self) {
+        self.url = self.url_queue.join(" ");
+    }
+
+    /// Fetches the formats of the single queued video in the background.
+    fn open_format_picker(&mut self) {
+        let Some(url) = self.single_url().map(String::from) else {
+            return;
+        };
+        *self.formats.lock().unwrap() = Lookup::Pending;
+        self.format_selected = 0;
+        self.state = AppState::Formats;
+
+        let formats_ref = self.formats.clone();
+        thread::spawn(move || {
+            let state = match list_formats(&url) {
+                Ok(formats) => Lookup::Ready(formats),
+                Err(e) => Lookup::Failed(e),
+            };
+            *formats_ref.lock().unwrap() = state;
+        });
+    }
+
+    fn choose_format(&mut self) {
+        let chosen = match &*self.formats.lock().unwrap() {
+            Lookup::Ready(formats) => formats.get(self.format_selected).cloned(),
+            _ => None,
+        };
+        if let Some(format) = chosen {
+            // A video format is kept as video, not extracted back to audio;
+            // one without sound gets the best audio merged in.
+            self.options.video = format.height.is_some();
+            self.options.format = if format.video_only {
+                format!("{}+bestaudio", format.id)
+            } else {
+                format.id.clone()
+            };
+            self.format_label = Some(format.label());
+            if std::mem::take(&mut self.retry_with_format) {
+                self.state = AppState::Downloading;
+                self.start_download();
+            } else if std::mem::take(&mut self.pick_then_download) {
+                self.confirm_download();
+            } else {
+                self.state = AppState::Confirm;
+            }
+        }
+    }
+
+    /// Picks up after a failure that has a way around it, instead of
+    /// stopping at the error.
+    fn recover_from_failure(&mut self) {
+        // A watch goes on to its next check instead.
+        if self.single_url().is_none() || self.watch.is_some() {
+            return;
+        }
+        match self.failure {
+            // List the formats the video does have.
+            Some(FailureKind::FormatUnavailable) if self.config.ask_format_on_failure => {
+                self.failure = None;
+                self.reset_download_state();
+                self.retry_with_format = true;
+                self.open_format_picker();
+            }
+            // Tried once: if audio-only is blocked as well, that's the error.
+            Some(FailureKind::Restricted)
+                if self.config.audio_fallback && self.options.format != AUDIO_FALLBACK_FORMAT =>
+            {
+                self.failure = None;
+                self.reset_download_state();
+                self.options.format = AUDIO_FALLBACK_FORMAT.to_string();
+                self.options.video = false;
+                self.format_label = None;
+                self.audio_fallback_used = true;
+                self.state = AppState::Downloading;
+                self.start_download();
+            }
+            _ => {}
+        }
+    }
+
+    /// Resolves the queued video's title in the background and looks for
+    /// similarly named files, when the duplicate check is on.
+    fn request_duplicate_check(&mut self) {
+        let Some(url) = self.single_url().map(String::from) else {
+            return;
+        };
+        self.archived_in = self
+            .check_global_archive(&url)
+            .map(|found| (url.clone(), found));
+        if !self.config.duplicate_check || is_playlist_url(&url) {
+            return;
+        }
+        if self.duplicates().is_some() {
+            return;
+        }
+        *self.duplicates.lock().unwrap() = Some((url.clone(), Lookup::Pending));
+
+        let dir = self.playlist_dir();
+        let duplicates_ref = self.duplicates.clone();
+        thread::spawn(move || {
+            let found = match fetch_title(&url) {
+                Some(title) => Lookup::Ready(find_similar(&dir, &title)),
+                None => Lookup::Failed("Could not resolve the title".to_string()),
+            };
+            let mut slot = duplicates_ref.lock().unwrap();
+            if slot.as_ref().is_some_and(|(u, _)| *u == url) {
+                *slot = Some((url, found));
+            }
+        });
+    }
+
+    /// `<music_dir>/.ytd-global-archive.txt`, shared by every playlist.
+    fn global_archive(&self) -> PathBuf {
+        self.music_dir.join(GLOBAL_ARCHIVE)
+    }
+
+    /// "Already downloaded in playlist X" when global dedup is on and the
+    /// archive lists the video. The archive only has IDs, so the playlist
+    /// comes from the history.
+    fn check_global_archive(&self, url: &str) -> Option<String> {
+        let id = video_id(url).filter(|_| self.config.global_dedup)?;
+        if !archive_contains(&self.global_archive(), &id) {
+            return None;
+        }
+        let playlist = self
+            .history
+            .entries
+            .iter()
+            .rev()
+            .find(|e| e.success && video_id(&e.url).as_deref() == Some(id.as_str()))
+            .map(|e| e.playlist.clone());
+        Some(match playlist {
+            Some(playlist) => format!(
+                "Already downloaded in playlist {}; it will be skipped",
+                playlist
+            ),
+            None => "Already downloaded into another playlist; it will be skipped".to_string(),
+        })
+    }
+
+    /// The duplicate check result for the queued URL, if one was started.
+    fn duplicates(&self) -> Option<Lookup<String>> {
+        match &*self.duplicates.lock().unwrap() {
+            Some((url, found)) if Some(url.as_str()) == self.single_url() => Some(found.clone()),
+            _ => None,
+        }
+    }
+
+    /// Asks yt-dlp in the background whether the queued video is live or
+    /// upcoming, so the confirm screen can offer the live options.
+    fn request_live_check(&mut self) {
+        let Some(url) = self.single_url().map(String::from) else {
+            return;
+        };
+        if is_playlist_url(&url) || self.live_status_entry().is_some() {
+            return;
+        }
+        *self.live_status.lock().unwrap() = Some((url.clone(), None));
+
+        let live_ref = self.live_status.clone();
+        thread::spawn(move || {
+            let status = fetch_live_status(&url).unwrap_or(LiveStatus::NotLive);
+            let mut slot = live_ref.lock().unwrap();
+            if slot.as_ref().is_some_and(|(u, _)| *u == url) {
+                *slot = Some((url, Some(status)));
+            }
+        });
+    }
+
+    fn size_estimate_key(&self) -> Option<String> {
+        self.single_url()
+            .map(|url| format!("{} {}", url, format_selector(&self.options)))
+    }
+
+    /// Estimates the download size in the background, once per URL and
+    /// format. Called every tick of the confirm screen.
+    fn request_size_estimate(&mut self) {
+        let Some(key) = self.size_estimate_key() else {
+            return;
+        };
+        if self.size_estimate_entry().is_some() {
+            return;
+        }
+        *self.size_estimate.lock().unwrap() = Some((key.clone(), None));
+
+        let url = self.single_url().unwrap_or_default().to_string();
+        let format = format_selector(&self.options);
+        let estimate_ref = self.size_estimate.clone();
+        thread::spawn(move || {
+            let estimate = fetch_size_estimate(&url, &format);
+            let mut slot = estimate_ref.lock().unwrap();
+            if slot.as_ref().is_some_and(|(k, _)| *k == key) {
+                *slot = Some((key, Some(estimate)));
+            }
+        });
+    }
+
+    fn size_estimate_entry(&self) -> Option<Option<SizeEstimate>> {
+        let key = self.size_estimate_key()?;
+        match &*self.size_estimate.lock().unwrap() {
+            Some((k, estimate)) if *k == key => Some(*estimate),
+            _ => None,
+        }
+    }
+
+    fn live_status_entry(&self) -> Option<Option<LiveStatus>> {
+        match &*self.live_status.lock().unwrap() {
+            Some((url, status)) if Some(url.as_str()) == self.single_url() => Some(*status),
+            _ => None,
+        }
+    }
+
+    fn is_checking_live(&self) -> bool {
+        self.live_status_entry() == Some(None)
+    }
+
+    fn live_status(&self) -> LiveStatus {
+        self.live_status_entry()
+            .flatten()
+            .unwrap_or(LiveStatus::NotLive)
+    }
+
+    /// Ends a live recording early. yt-dlp keeps what it has recorded and
+    /// finishes as if the stream had ended.
+    fn stop_recording(&self) {
+        if let Some(child) = self.current_child.lock().unwrap().as_mut() {
+            if let Err(e) = interrupt_child(child) {
+                let mut out = self.download_output.lock().unwrap();
+                out.push(format!("Could not stop the recording: {}", e));
+            }
+        }
+    }
+
+    fn has_duplicates(&self) -> bool {
+        matches!(self.duplicates(), Some(Lookup::Ready(similar)) if !similar.is_empty())
+    }
+
+    fn item_count(&self) -> ItemCount {
+        match &*self.item_count.lock().unwrap() {
+            Some((url, count)) if Some(url.as_str()) == self.single_url() => *count,
+            _ => ItemCount::Unknown,
+        }
+    }
+
+    fn is_large_playlist(&self) -> bool {
+        matches!(self.item_count(), ItemCount::Known(n) if n > self.config.large_playlist_threshold)
+    }
+
+    /// Worst-acceptable audio, a capped rate and no post-processing, for
+    /// tethered or metered connections.
+    fn apply_low_bandwidth_preset(&mut self) {
+        self.options.format = "ba[ext=m4a]/worstaudio".to_string();
+        self.options.video = false;
+        self.format_label = None;
+        self.options.limit_rate = Some("300K".to_string());
+        self.options.embed_thumbnail = false;
+        self.options.add_metadata = false;
+        self.low_bandwidth = true;
+    }
+
+    /// The Confirm screen's a/v switch: the configured audio format, or the
+    /// best video with its audio.
+    fn set_video(&mut self, video: bool) {
+        if video == self.options.video {
+            return;
+        }
+        if self.low_bandwidth {
+            self.clear_low_bandwidth_preset();
+        }
+        self.options.video = video;
+        self.options.format = if video {
+            VIDEO_FORMAT.to_string()
+        } else {
+            self.config
+                .format
+                .clone()
+                .unwrap_or_else(|| DownloadOptions::default().format)
+        };
+        self.format_label = None;
+    }
+
+    /// What counts as a downloaded file: audio, or video in video mode.
+    fn is_output_file(&self, path: &Path) -> bool {
+        match &self.options.remux_video {
+            _ if !self.options.video => is_audio_file(path),
+            Some(container) => path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(container)),
+            None => is_video_file(path),
+        }
+    }
+
+    /// Bytes per second below which the worker offers lower quality. Off
+    /// headless, where the offer would wait for an answer forever.
+    fn slow_speed(&self) -> Option<f64> {
+        (self.config.adaptive_quality && !self.headless)
+            .then_some(self.config.slow_speed_kib as f64 * 1024.0)
+    }
+
+    /// What lower quality means for this download.
+    fn low_quality_format(&self) -> &'static str {
+        if self.options.video {
+            LOW_QUALITY_VIDEO_FORMAT
+        } else {
+            LOW_QUALITY_FORMAT
+        }
+    }
+
+    fn clear_low_bandwidth_preset(&mut self) {
+        let defaults = DownloadOptions::default();
+        self.options.format = self.config.format.clone().unwrap_or(defaults.format);
+        self.format_label = None;
+        self.options.limit_rate = defaults.limit_rate;
+        self.options.embed_thumbnail = defaults.embed_thumbnail;
+        self.options.add_metadata = defaults.add_metadata;
+        self.low_bandwidth = false;
+    }
+
+    /// The username and password to pass on, if login is turned on.
+    fn login_credentials(&self) -> (Option<String>, Option<String>) {
+        if self.config.login {
+            (self.config.username.clone(), self.password.clone())
+        } else {
+            (None, None)
+        }
+    }
+
+    /// The `--output` template, relative to the download directory.
+    fn output_template(&self) -> &str {
+        if self.config.library_mode {
+            &self.config.library_template
+        } else if self.config.date_folders {
+            &self.config.date_template
+        } else {
+            "%(title)s.%(ext)s"
+        }
+    }
+
+    fn playlist_dir(&self) -> PathBuf {
+        self.music_dir.join(&self.playlist_name)
+    }
+
+    /// `<playlist>/yt-dlp.conf`, if the playlist has its own yt-dlp options.
+    fn playlist_config(&self) -> Option<PathBuf> {
+        Some(self.playlist_dir().join("yt-dlp.conf")).filter(|p| p.is_file())
+    }
+
+    /// Where yt-dlp writes this run's files: the staging directory if there
+    /// is one, otherwise straight into the playlist folder.
+    fn download_dir(&self) -> PathBuf {
+        self.staging_dir
+            .clone()
+            .unwrap_or_else(|| self.playlist_dir())
+    }
+
+    /// `self.options` with the settings from the config applied, i.e. what
+    /// `start_download` runs with and the confirm screen previews.
+    fn run_options(&self) -> DownloadOptions {
+        let mut options = self.options.clone();
+        options.write_info_json = self.config.chapters_sidecar || self.config.nfo_sidecar;
+        options.write_subs = self.config.lyrics_sidecar || self.config.transcript_sidecar;
+        options.write_auto_subs = self.config.transcript_sidecar;
+        options.embed_info_json = self.config.embed_info_json;
+        options.album = self
+            .config
+            .album_from_playlist
+            .then(|| self.playlist_name.clone());
+        options.keep_source_album = self.config.keep_source_album;
+        options.mtime = self.config.mtime;
+        options.cookies = self.config.cookies_file.clone();
+        options.prefer_free_formats = self.config.prefer_free_formats;
+        options.config_location = self.playlist_config();
+        options.ffmpeg_location = self.config.ffmpeg_location.clone();
+        options.cache_dir = self.config.cache_dir.clone();
+        options.download_archive = if self.config.global_dedup {
+            Some(self.global_archive())
+        } else {
+            self.watch
+                .as_ref()
+                .map(|_| self.playlist_dir().join(WATCH_ARCHIVE))
+        };
+        options.trim_silence = self.config.trim_silence;
+        options.split_chapters = self.config.split_chapters;
+        options.remux_video = self
+            .config
+            .remux_video
+            .then(|| self.config.video_container.ext().to_string());
+        options.sleep_requests = self.config.sleep_requests;
+        options.sleep_interval = self.config.sleep_interval;
+        options.playlist_start = self.resume_point().filter(|_| self.resume);
+        options.abort_on_missing_fragments = self.config.abort_on_missing_fragments;
+        // Only meaningful for the stream they were chosen for.
+        options.live_from_start &= self.live_status() == LiveStatus::Live;
+        options.wait_for_video &= self.live_status() == LiveStatus::Upcoming;
+        // The default selector only accepts m4a, which would leave nothing
+        // free to prefer, and caps the bitrate at the best m4a's. A format
+        // picked by hand is kept as it is.
+        if self.config.max_quality_audio
+            && !options.video
+            && options.format == DownloadOptions::default().format
+        {
+            options.format = MAX_QUALITY_FORMAT.to_string();
+        } else if self.config.prefer_free_formats
+            && options.format == DownloadOptions::default().format
+        {
+            options.format = "ba".to_string();
+        }
+        options.max_quality_audio = options.format == MAX_QUALITY_FORMAT;
+        // The custom cover is embedded afterwards; the thumbnail would only
+        // be overwritten.
+        if self.cover_image.is_some() {
+            options.embed_thumbnail = false;
+        }
+        if thumbnail_unsupported_ext(&options.format).is_some() {
+            options.embed_thumbnail = false;
+        }
+        let (username, password) = self.login_credentials();
+        options.username = username;
+        options.password = password;
+        options
+    }
+
+    /// Where this run downloads to before moving in, with staged downloads
+    /// on. Keyed by playlist so a cancelled staged run can resume its
+    /// partials.
+    fn run_staging_dir(&self) -> Option<PathBuf> {
+        self.config.staged_downloads.then(|| {
+            std::env::temp_dir()
+                .join("ytd-staging")
+                .join(&self.playlist_name)
+        })
+    }
+
+    /// The yt-dlp arguments for downloading `url` with `options` into
+    /// `music_dir`.
+    fn yt_dlp_args(&self, options: &DownloadOptions, music_dir: &Path, url: &str) -> Vec<String> {
+        let output_template = long_path_template(format!(
+            "{}/{}",
+            music_dir.display(),
+            self.output_template()
+        ));
+        let opts = DownloadOptions {
+            date_after: self.date_after_for(url),
+            comment: self
+                .config
+                .tag_comment
+                .as_deref()
+                .map(|t| expand_tag_comment(t, url)),
+            ..options.clone()
+        };
+        build_yt_dlp_args(&opts, &output_template, url)
+    }
+
+    /// Starts the worker thread, or switches to the error screen if another
+    /// instance is already downloading into this playlist.
+    fn start_download(&mut self) {
+        self.last_run = Some(LastRun {
+            playlist_name: self.playlist_name.clone(),
+            url_queue: self.url_queue.clone(),
+            options: self.options.clone(),
+            format_label: self.format_label.clone(),
+        });
+        let _ = std::fs::create_dir_all(self.playlist_dir());
+        match acquire_lock(&self.playlist_dir()) {
+            Ok(lock) => self.lock = Some(lock),
+            Err(e) => {
+                self.error_message = e;
+                self.state = AppState::Error;
+                return;
+            }
+        }
+
+        self.staging_dir = self.run_staging_dir();
+        let music_dir = self.download_dir();
+
+        let _ = std::fs::create_dir_all(&music_dir);
+
+        let options = self.run_options();
+        if let Some(ext) = thumbnail_unsupported_ext(&options.format)
+            .filter(|_| self.options.embed_thumbnail && self.cover_image.is_none())
+        {
+            self.download_output.lock().unwrap().push(format!(
+                "[ytd] Not embedding the thumbnail: {} files can't hold one",
+                ext
+            ));
+        }
+        self.options = options;
+        let jobs: Vec<(String, Vec<String>)> = self
+            .url_queue
+            .iter()
+            .map(|url| {
+                (
+                    url.clone(),
+                    self.yt_dlp_args(&self.options, &music_dir, url),
+                )
+            })
+            .collect();
+        let known_count = match self.item_count() {
+            ItemCount::Known(n) => Some(n),
+            _ => None,
+        };
+        let continue_on_error = self.config.continue_on_error;
+
+        let worker = Worker {
+            sink: OutputSink {
+                output: self.download_output.clone(),
+                files_started: self.files_started.clone(),
+                skipped: self.skipped.clone(),
+                created: self.created.clone(),
+                video_ids: self.video_ids.clone(),
+                fragments_skipped: self.fragments_skipped.clone(),
+                too_long: self.too_long.clone(),
+                progress: self.progress.clone(),
+                redact: self.options.password.clone(),
+                events: self.events.clone(),
+                speeds: Arc::new(Mutex::new(Vec::new())),
+                started: self.config.timestamp_log.then(Instant::now),
+            },
+            child: self.current_child.clone(),
+            cancel: self.cancel_requested.clone(),
+            skip: self.skip_requested.clone(),
+            paused: self.paused.clone(),
+            current_item: self.current_item.clone(),
+            total_items: self.total_items.clone(),
+            max_downloads: self.options.max_downloads,
+            playlist_start: self.options.playlist_start.unwrap_or(1),
+            latest_items: self.options.latest_items,
+            slow_speed: self.slow_speed(),
+            low_quality_format: self.low_quality_format(),
+            quality_prompt: self.quality_prompt.clone(),
+            rate_limited_until: self.rate_limited_until.clone(),
+            #[cfg(feature = "lyrics")]
+            lyrics: (self.config.fetch_lyrics && !self.options.video).then(|| {
+                (
+                    lyrics::Provider {
+                        endpoint: self.config.lyrics_endpoint.clone(),
+                        timeout: Duration::from_secs(self.config.lyrics_timeout_secs),
+                    },
+                    self.config.ffmpeg_program(),
+                )
+            }),
+            #[cfg(feature = "lyrics")]
+            lyrics_result: self.lyrics_result.clone(),
+            trim_silence: (self.options.trim_silence && !self.options.video)
+                .then(|| self.config.ffmpeg_program()),
+            cover: self
+                .cover_image
+                .clone()
+                .map(|image| (image, self.config.ffmpeg_program())),
+            cover_result: self.cover_result.clone(),
+            preview: self.config.preview_copy.then(|| {
+                (
+                    self.config.ffmpeg_program(),
+                    self.config.preview_bitrate.clone(),
+                )
+            }),
+            preview_result: self.preview_result.clone(),
+        };
+        let done_ref = self.download_done.clone();
+        let success_ref = self.download_success.clone();
+        let position_ref = self.queue_position.clone();
+        let results_ref = self.url_results.clone();
+
+        thread::spawn(move || {
+            let job_count = jobs.len();
+            let mut results = Vec::new();
+            for (i, (url, args)) in jobs.into_iter().enumerate() {
+                if worker.cancel.load(Ordering::SeqCst) {
+                    break;
+                }
+                position_ref.store(i + 1, Ordering::SeqCst);
+
+                // The confirm-screen count only applies to a lone URL.
+                let known = if job_count == 1 { known_count } else { None };
+                let created_from = worker.sink.created.lock().unwrap().len();
+                let outcome = worker.download_url(&url, args, known);
+                let created = created_from..worker.sink.created.lock().unwrap().len();
+                match outcome {
+                    Ok(ok) => {
+                        results.push((url, ok, created));
+                        if !ok && !continue_on_error {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        worker.sink.push_line(&format!("Failed to spawn: {}", e));
+                        results.push((url, false, created));
+                        break;
+                    }
+                }
+            }
+
+            let success = results.len() == job_count && results.iter().all(|(_, ok, _)| *ok);
+            if let Some(ffmpeg) = worker.trim_silence.as_ref().filter(|_| success) {
+                worker.trim_silence_from_tracks(ffmpeg);
+            }
+            if let Some((image, ffmpeg)) = worker.cover.as_ref().filter(|_| success) {
+                worker.embed_custom_cover(image, ffmpeg);
+            }
+            #[cfg(feature = "lyrics")]
+            if let Some((provider, ffmpeg)) = &worker.lyrics {
+                worker.embed_lyrics(provider, ffmpeg);
+            }
+            // Last, so the previews carry the tags and art added above.
+            if let Some((ffmpeg, bitrate)) = worker.preview.as_ref().filter(|_| success) {
+                worker.make_previews(ffmpeg, bitrate);
+            }
+            *results_ref.lock().unwrap() = results;
+            success_ref.store(success, Ordering::SeqCst);
+            done_ref.store(true, Ordering::SeqCst);
+        });
+    }
+
+    /// The files this run created that are still there. Only paths inside
+    /// the playlist folder count, whatever yt-dlp printed.
+    fn created_files(&self) -> Vec<PathBuf> {
+        let dir = self.playlist_dir();
+        let mut files: Vec<PathBuf> = self
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.starts_with(&dir) && p.is_file())
+            .cloned()
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// How many audio (or video) files this run created, leaving out
+    /// sidecars and whatever was in the folder before.
+    fn created_output_count(&self) -> usize {
+        self.created_files()
+            .iter()
+            .filter(|p| self.is_output_file(p))
+            .count()
+    }
+
+    /// Deletes what this run created (and nothing else in the folder, which
+    /// may have been there before), and drops its videos from the archive.
+    fn undo_last_download(&mut self) {
+        self.confirm_undo = false;
+        let files = self.created_files();
+        let video_ids = self.video_ids.lock().unwrap().clone();
+        match undo_download(&files, self.options.download_archive.as_deref(), &video_ids) {
+            Ok(()) => {
+                let dir = self.playlist_dir();
+                self.files_downloaded
+                    .retain(|name| !files.contains(&dir.join(name)));
+                self.done_list_state = ListState::default()
+                    .with_selected((!self.files_downloaded.is_empty()).then_some(0));
+                self.created.lock().unwrap().clear();
+                self.video_ids.lock().unwrap().clear();
+                self.done_status = Some(Ok(format!(
+                    "Deleted {} file{} created by this download",
+                    files.len(),
+                    if files.len() == 1 { "" } else { "s" }
+                )));
+            }
+            Err(e) => self.done_status = Some(Err(format!("Undo stopped: {}", e))),
+        }
+    }
+
+    /// The run finished without writing anything because every item was
+    /// already there.
+    fn nothing_new(&self) -> bool {
+        self.files_started.load(Ordering::SeqCst) == 0 && self.skipped.load(Ordering::SeqCst) > 0
+    }
+
+    /// Opens the selected file, or with `all` every downloaded file in list
+    /// order, in the media player.
+    fn play_downloaded(&mut self, all: bool) {
+        let dir = self.playlist_dir();
+        let files: Vec<PathBuf> = if all {
+            self.files_downloaded.iter().map(|f| dir.join(f)).collect()
+        } else {
+            self.done_list_state
+                .selected()
+                .and_then(|i| self.files_downloaded.get(i))
+                .map(|f| dir.join(f))
+                .into_iter()
+                .collect()
+        };
+        if files.is_empty() {
+            return;
+        }
+        self.done_status = Some(
+            player::play(
+                self.config.player_command.as_deref(),
+                &self.playlist_name,
+                &files,
+            )
+            .map(|()| format!("Playing {}", player::describe(&files))),
+        );
+    }
+
+    /// Kills the running yt-dlp child, if any. The worker thread notices the
+    /// exit and moves on (or stops, if a cancel was requested).
+    fn kill_current_child(&self) {
+        if let Some(child) = self.current_child.lock().unwrap().as_mut() {
+            let _ = child.kill();
+        }
+    }
+
+    fn toggle_pause(&self) {
+        let paused = !self.is_paused();
+        self.paused.store(paused, Ordering::SeqCst);
+        if let Some(child) = self.current_child.lock().unwrap().as_mut() {
+            if let Err(e) = set_child_paused(child, paused) {
+                self.paused.store(!paused, Ordering::SeqCst);
+                let mut out = self.download_output.lock().unwrap();
+                out.push(format!("Could not pause: {}", e));
+            }
+        }
+    }
+
+    /// The slow post-processing step yt-dlp is in, if any, judging by the
+    /// last line it printed. These run ffmpeg without progress output, so
+    /// the status line names them instead.
+    fn post_processing_phase(&self) -> Option<&'static str> {
+        let output = self.download_output.lock().unwrap();
+        let last = strip_timestamp(output.last()?);
+        if last.starts_with("[Merger]") {
+            Some("Merging video and audio...")
+        } else if last.starts_with(TRIMMING_SILENCE) {
+            Some("Trimming silence...")
+        } else if last.starts_with(MAKING_PREVIEW) {
+            Some("Making previews...")
+        } else {
+            None
+        }
+    }
+
+    fn quality_prompt(&self) -> QualityPrompt {
+        *self.quality_prompt.lock().unwrap()
+    }
+
+    fn answer_quality_prompt(&self, lower: bool) {
+        let mut prompt = self.quality_prompt.lock().unwrap();
+        if *prompt == QualityPrompt::Asking {
+            *prompt = if lower {
+                QualityPrompt::Accepted
+            } else {
+                QualityPrompt::Declined
+            };
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn download_frame(&self, spinner: u32) -> DownloadFrame {
+        DownloadFrame {
+            spinner,
+            output_lines: self.download_output.lock().unwrap().total(),
+            progress: self.progress.lock().unwrap().clone(),
+            item: (
+                self.current_item.load(Ordering::SeqCst),
+                self.total_items.load(Ordering::SeqCst),
+            ),
+            queue_position: self.queue_position.load(Ordering::SeqCst),
+            files_started: self.files_started.load(Ordering::SeqCst),
+            skipped: self.skipped.load(Ordering::SeqCst),
+            paused: self.is_paused(),
+            rate_limit_wait: self.rate_limit_wait(),
+            quality_prompt: self.quality_prompt(),
+            log_view: (self.show_full_log, self.log_scroll, self.pager.is_some()),
+        }
+    }
+
+    /// For cache-related failures: a fresh cache makes yt-dlp fetch the
+    /// player again.
+    fn clear_yt_dlp_cache(&self) -> Result<String, String> {
+        clear_cache(self.config.cache_dir.as_deref())
+            .map(|()| "Cleared yt-dlp's cache".to_string())
+            .map_err(|e| format!("Couldn't clear yt-dlp's cache: {}", e))
+    }
+
+    /// Downloads the latest yt-dlp over ytd's own copy in the background.
+    /// A system yt-dlp is left to whatever installed it.
+    fn request_yt_dlp_update(&mut self) {
+        if self.config.yt_dlp_source != YtDlpSource::Managed {
+            if let Some((_, status)) = self.diagnostics.as_mut() {
+                *status = Some(Err(
+                    "yt-dlp is the system one: update it the way it was installed, or set yt_dlp_source = \"managed\""
+                        .to_string(),
+                ));
+            }
+            return;
+        }
+        if self.is_updating_yt_dlp() {
+            return;
+        }
+        *self.yt_dlp_update.lock().unwrap() = Some(None);
+        let update_ref = self.yt_dlp_update.clone();
+        thread::spawn(move || {
+            let result = managed::managed_path()
+                .ok_or_else(|| "no data directory to keep it in".to_string())
+                .and_then(|path| {
+                    let version = managed::install_latest(&path)?;
+                    managed::use_program(path);
+                    Ok(version)
+                })
+                .map(|version| format!("Updated yt-dlp to {}", version))
+                .map_err(|e| format!("Couldn't update yt-dlp: {}", e));
+            *update_ref.lock().unwrap() = Some(Some(result));
+        });
+    }
+
+    fn is_updating_yt_dlp(&self) -> bool {
+        matches!(*self.yt_dlp_update.lock().unwrap(), Some(None))
+    }
+
+    /// Shows a finished update on the diagnostics screen, with the new
+    /// version filled in.
+    fn check_yt_dlp_update(&mut self) {
+        let mut slot = self.yt_dlp_update.lock().unwrap();
+        if !matches!(*slot, Some(Some(_))) {
+            return;
+        }
+        let result = slot.take().flatten();
+        drop(slot);
+        if self.diagnostics.is_some() {
+            self.diagnostics = Some((gather_diagnostics(&self.config, &self.music_dir), result));
+        }
+    }
+
+    /// Seconds left before retrying after a 429, while waiting one out.
+    fn rate_limit_wait(&self) -> Option<u64> {
+        self.rate_limited_until
+            .lock()
+            .unwrap()
+            .map(|until| until.saturating_duration_since(Instant::now()).as_secs() + 1)
+    }
+
+    fn skip_current_item(&self) {
+        if self.total_items.load(Ordering::SeqCst) > 1 {
+            self.skip_requested.store(true, Ordering::SeqCst);
+            self.kill_current_child();
+        }
+    }
+
+    fn cancel_download(&mut self) {
+        self.remember_resume_point();
+        self.cancel_requested.store(true, Ordering::SeqCst);
+        self.kill_current_child();
+    }
+
+    /// Notes the item a playlist was on when it got cancelled, so the next
+    /// run of it can offer to start there instead of re-checking everything.
+    fn remember_resume_point(&mut self) {
+        let item = self.current_item.load(Ordering::SeqCst);
+        if self.total_items.load(Ordering::SeqCst) <= 1 || item <= 1 {
+            return;
+        }
+        let position = self.queue_position.load(Ordering::SeqCst);
+        if let Some(url) = position
+            .checked_sub(1)
+            .and_then(|i| self.url_queue.get(i))
+            .cloned()
+        {
+            self.history.set_resume_point(&url, item);
+        }
+    }
+
+    /// Where the queued playlist's last, cancelled run stopped.
+    fn resume_point(&self) -> Option<usize> {
+        self.single_url()
+            .and_then(|url| self.history.resume_point(url))
+    }
+
+    /// Sets `terminate_requested` when the process gets SIGTERM, instead of
+    /// dying on the spot with the terminal in raw mode and yt-dlp orphaned.
+    fn watch_sigterm(&self) -> io::Result<()> {
+        signal_hook::flag::register(SIGTERM, self.terminate_requested.clone()).map(|_| ())
+    }
+
+    /// For SIGTERM: cancels like Ctrl+C, then makes sure yt-dlp has exited
+    /// and been reaped before ytd goes.
+    fn shut_down(&mut self) {
+        if self.state == AppState::Downloading {
+            self.cancel_download();
+            self.wait_for_worker();
+        }
+        // Normally the worker reaps its child; this covers one that outlived
+        // the wait.
+        if let Some(mut child) = self.current_child.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Waits up to five seconds for the worker thread to notice a cancel
+    /// and finish.
+    fn wait_for_worker(&self) {
+        for _ in 0..50 {
+            if self.download_done.load(Ordering::SeqCst) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Cancels and goes back to the URL field, URL intact, so a mistake can
+    /// be fixed without restarting the app. Partials are kept.
+    fn cancel_to_url_input(&mut self) {
+        self.watch = None;
+        self.cancel_download();
+        self.wait_for_worker();
+        self.reset_download_state();
+        self.state = AppState::InputUrl;
+    }
+
+    /// Starts the last download again exactly as it was started, whatever
+    /// was changed since.
+    fn rerun_last(&mut self) {
+        let Some(last) = self.last_run.clone() else {
+            return;
+        };
+        self.wait_for_worker();
+        self.reset_download_state();
+        self.playlist_name = last.playlist_name;
+        self.url_queue = last.url_queue;
+        self.options = last.options;
+        self.format_label = last.format_label;
+        self.failure = None;
+        self.confirm_undo = false;
+        self.done_status = None;
+        self.state = AppState::Downloading;
+        self.start_download();
+    }
+
+    /// Scrolls the full log to a new error line when it's following the
+    /// bottom, and keeps it there as more output comes in, so the error
+    /// doesn't scroll away. Scrolling by hand lets go of it.
+    fn follow_log_errors(&mut self) {
+        let output = self.download_output.lock().unwrap();
+        let latest = output.latest_error();
+        if latest != self.seen_error {
+            self.seen_error = latest;
+            if self.show_full_log && (self.log_scroll == 0 || self.log_pin.is_some()) {
+                self.log_pin = latest;
+            }
+        }
+        if let Some(pin) = self.log_pin {
+            match output.lines_after(pin) {
+                Some(after) => self.log_scroll = after,
+                None => self.log_pin = None,
+            }
+        }
+    }
+
+    /// Puts everything the worker shares with the UI back to how `App::new`
+    /// left it, so the next download doesn't start out cancelled, paused or
+    /// already done. The shared state is replaced rather than cleared: a
+    /// worker that outlived `wait_for_worker` keeps the old copies, and
+    /// whatever it still writes can't leak into the next run.
+    fn reset_download_state(&mut self) {
+        self.cancel_requested = Arc::new(AtomicBool::new(false));
+        self.skip_requested = Arc::new(AtomicBool::new(false));
+        self.paused = Arc::new(AtomicBool::new(false));
+        self.download_done = Arc::new(AtomicBool::new(false));
+        self.download_success = Arc::new(AtomicBool::new(false));
+        self.current_item = Arc::new(AtomicUsize::new(0));
+        self.total_items = Arc::new(AtomicUsize::new(0));
+        self.files_started = Arc::new(AtomicUsize::new(0));
+        self.skipped = Arc::new(AtomicUsize::new(0));
+        self.created = Arc::new(Mutex::new(Vec::new()));
+        self.video_ids = Arc::new(Mutex::new(Vec::new()));
+        self.fragments_skipped = Arc::new(AtomicUsize::new(0));
+        self.too_long = Arc::new(AtomicUsize::new(0));
+        self.queue_position = Arc::new(AtomicUsize::new(0));
+        self.current_child = Arc::new(Mutex::new(None));
+        self.progress = Arc::new(Mutex::new(Progress::default()));
+        self.quality_prompt = Arc::new(Mutex::new(QualityPrompt::Idle));
+        self.rate_limited_until = Arc::new(Mutex::new(None));
+        self.download_output = Arc::new(Mutex::new(OutputLog::new(self.config.max_output_lines)));
+        self.download_output_final.clear();
+        self.files_downloaded.clear();
+        self.audio_fallback_used = false;
+        #[cfg(feature = "lyrics")]
+        {
+            self.lyrics_result = Arc::new(Mutex::new(None));
+        }
+        self.cover_result = Arc::new(Mutex::new(None));
+        self.preview_result = Arc::new(Mutex::new(None));
+        self.resolved_audio.clear();
+        self.organize_result = None;
+        self.staging_kept.clear();
+        self.url_results = Arc::new(Mutex::new(Vec::new()));
+        self.show_full_log = false;
+        self.log_scroll = 0;
+        self.seen_error = None;
+        self.log_pin = None;
+        self.pager = None;
+        self.lock = None;
+        self.staging_dir = None;
+    }
+
+    /// Cancels, waits for the worker to let go of its files, then removes the
+    /// partial downloads. Plain cancel keeps them so yt-dlp can resume.
+    fn cancel_and_clean(&mut self) -> String {
+        self.cancel_download();
+        self.wait_for_worker();
+        let dir = self.download_dir();
+        let created = self.created.lock().unwrap().clone();
+        let removed = cleanup_partials(&dir, &created);
+        format!(
+            "Cancelled; removed {} partial file{} from {}",
+            removed,
+            if removed == 1 { "" } else { "s" },
+            dir.display()
+        )
+    }
+
+    /// Adds the finished run to the history, one entry per URL with the
+    /// audio files it created.
+    fn record_history(&mut self) {
+        let results = self.url_results.lock().unwrap().clone();
+        let created = self.created.lock().unwrap().clone();
+        for (url, ok, range) in results {
+            if ok {
+                self.history.clear_resume_point(&url);
+            }
+            let files = created
+                .get(range)
+                .unwrap_or_default()
+                .iter()
+                .filter(|p| self.is_output_file(p) && p.is_file())
+                .cloned()
+                .collect();
+            self.history.record(HistoryEntry {
+                url,
+                playlist: self.playlist_name.clone(),
+                timestamp: Local::now().timestamp(),
+                success: ok,
+                files,
+            });
+        }
+    }
+
+    /// Writes this run's files to `<playlist>/<playlist>.m3u8` from the
+    /// Done screen, with characters a file name can't hold replaced.
+    fn export_session(&mut self) {
+        let count = self.url_results.lock().unwrap().len();
+        let entries = &self.history.entries[self.history.entries.len().saturating_sub(count)..];
+        let out = self
+            .playlist_dir()
+            .join(format!("{}.m3u8", safe_file_name(&self.playlist_name)));
+        self.done_status = Some(
+            export_playlist(entries, ExportFormat::M3u, &out)
+                .map(|summary| describe_export(&summary, &out))
+                .map_err(|e| format!("Could not write {}: {}", out.display(), e)),
+        );
+    }
+
+    /// One line per queued URL saying how it went. Empty for a single URL.
+    fn queue_summary(&self) -> Vec<Line<'static>> {
+        if self.url_queue.len() < 2 {
+            return Vec::new();
+        }
+        let results = self.url_results.lock().unwrap();
+        self.url_queue
+            .iter()
+            .enumerate()
+            .map(|(i, url)| match results.get(i) {
+                Some((_, true, _)) => {
+                    Line::styled(format!("✓ {}", url), Style::default().fg(Color::Green))
+                }
+                Some((_, false, _)) => {
+                    Line::styled(format!("✗ {}", url), Style::default().fg(Color::Red))
+                }
+                None => Line::styled(
+                    format!("- {} (not started)", url),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            })
+            .collect()
+    }
+
+    /// Turns the `.info.json` files this run wrote into the enabled
+    /// sidecars, then removes them; they were only requested for this. Ones
+    /// already there, from earlier runs or the user's yt-dlp.conf, stay.
+    fn process_info_json(&self, write_sidecars: bool) {
+        if !self.options.write_info_json {
+            return;
+        }
+        let dir = self.download_dir();
+        let info_jsons: Vec<PathBuf> = self
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.starts_with(&dir) && p.is_file())
+            .filter(|p| p.to_string_lossy().ends_with(".info.json"))
+            .cloned()
+            .collect();
+        for info_json in info_jsons {
+            let mut written = Vec::new();
+            if write_sidecars
+                && self.config.chapters_sidecar
+                && sidecars::write_chapters_sidecar(&info_json).unwrap_or(false)
+            {
+                written.push(sidecars::sidecar_path(&info_json, ".chapters.txt"));
+            }
+            let nfo = sidecars::sidecar_path(&info_json, ".nfo");
+            if write_sidecars
+                && self.config.nfo_sidecar
+                && sidecars::write_nfo(&info_json, &nfo).is_ok()
+            {
+                written.push(nfo);
+            }
+            self.created.lock().unwrap().extend(written);
+            let _ = std::fs::remove_file(&info_json);
+        }
+    }
+
+    /// The subtitle files this run wrote that are still there.
+    fn created_subtitles(&self) -> Vec<PathBuf> {
+        let mut subtitles: Vec<PathBuf> = self
+            .created
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "vtt") && p.is_file())
+            .cloned()
+            .collect();
+        subtitles.sort();
+        subtitles.dedup();
+        subtitles
+    }
+
+    /// Turns fetched subtitles into `.lrc` lyrics and transcripts, then
+    /// removes them; the subtitles were only wanted for that. With several
+    /// languages the first one wins.
+    fn process_subtitles(&self, write_sidecars: bool) {
+        if !self.options.write_subs {
+            return;
+        }
+        for subs in self.created_subtitles() {
+            let lrc = sidecars::lrc_path(&subs);
+            // Auto captions are speech recognition, not lyrics, and yt-dlp
+            // doesn't say which subtitles were auto-generated, so no .lrc is
+            // made once they're fetched too.
+            if write_sidecars
+                && self.config.lyrics_sidecar
+                && !self.options.write_auto_subs
+                && !lrc.exists()
+                && sidecars::write_lrc(&subs, &lrc).unwrap_or(false)
+            {
+                self.created.lock().unwrap().push(lrc);
+            }
+            let transcript = sidecars::transcript_path(&subs);
+            if write_sidecars
+                && self.config.transcript_sidecar
+                && !transcript.exists()
+                && sidecars::vtt_to_transcript(&subs, &transcript).unwrap_or(false)
+            {
+                self.created.lock().unwrap().push(transcript);
+            }
+            let _ = std::fs::remove_file(&subs);
+        }
+    }
+
+    /// Moves the playlist's tracks into folders by their tags, keeping
+    /// `created` pointing at where this run's files ended up.
+    fn organize_playlist(&mut self) {
+        let ffmpeg = self.config.ffmpeg_program();
+        let dir = self.playlist_dir();
+        let moved = organize_by_tags(
+            &dir,
+            &self.created_files(),
+            &self.config.tag_folders_template,
+            |path| read_tags(&ffmpeg, path),
+        );
+        self.organize_result = Some(match moved {
+            Ok(moves) => {
+                for path in self.created.lock().unwrap().iter_mut() {
+                    if let Some((_, to)) = moves.iter().find(|(from, _)| from == path) {
+                        *path = to.clone();
+                    }
+                }
+                let mut folders: Vec<String> = moves
+                    .iter()
+                    .filter_map(|(_, to)| to.parent()?.strip_prefix(&dir).ok())
+                    .map(|folder| folder.display().to_string())
+                    .collect();
+                folders.sort();
+                folders.dedup();
+                Ok((moves.len(), folders))
+            }
+            Err(e) => Err(format!("Couldn't sort files by their tags: {}", e)),
+        });
+    }
+
+    /// Runs the configured post hook in the background. Its outcome only
+    /// shows up on the Done screen; a failing hook doesn't fail the download.
+    fn run_post_hook(&self, hook: &str) {
+        let mut command = if cfg!(windows) {
+            let mut c = Command::new("cmd");
+            c.arg("/C");
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.arg("-c");
+            c
+        };
+        command
+            .arg(hook)
+            .env("YTD_PLAYLIST", &self.playlist_name)
+            .env("YTD_DIR", self.playlist_dir())
+            .env("YTD_FILE_COUNT", self.created_output_count().to_string())
+            .env("YTD_URL", self.url_queue.join(" "))
+            .stdin(std::process::Stdio::null());
+
+        *self.hook_status.lock().unwrap() = Some(Line::from("Post hook: running..."));
+        self.hook_running.store(true, Ordering::SeqCst);
+        let status_ref = self.hook_status.clone();
+        let running_ref = self.hook_running.clone();
+        thread::spawn(move || {
+            let line = match command.output() {
+                Ok(out) if out.status.success() => {
+                    Line::styled("Post hook: finished", Style::default().fg(Color::Green))
+                }
+                Ok(out) => {
+                    let stderr = String::from_utf8_lossy(&out.stderr);
+                    let last = stderr.lines().rfind(|l| !l.trim().is_empty()).unwrap_or("");
+                    Line::styled(
+                        format!("Post hook failed ({}): {}", out.status, last),
+                        Style::default().fg(Color::Red),
+                    )
+                }
+                Err(e) => Line::styled(
+                    format!("Post hook could not start: {}", e),
+                    Style::default().fg(Color::Red),
+                ),
+            };
+            *status_ref.lock().unwrap() = Some(line);
+            running_ref.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Blocks until the post hook is done, for the headless modes, which
+    /// would otherwise exit under it. Returns its status line.
+    fn wait_for_post_hook(&self) -> Option<String> {
+        while self.hook_running.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        self.hook_status
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|line| line.to_string())
+    }
+
+    fn check_download(&mut self) -> bool {
+        if self.download_done.load(Ordering::SeqCst) {
+            self.lock = None;
+            self.download_output_final = self.download_output.lock().unwrap().text();
+
+            let failed = self
+                .url_results
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, ok, _)| !ok)
+                .count();
+            self.resume = false;
+
+            let success = self.download_success.load(Ordering::SeqCst);
+            self.process_info_json(success);
+            self.process_subtitles(success);
+
+            if let Some(staging) = &self.staging_dir {
+                if success {
+                    let moved = move_staged_files(staging, &self.playlist_dir());
+                    let kept = moved.as_deref().unwrap_or_default();
+                    // A kept file's path stays in staging, so it isn't taken
+                    // for the file of the same name that was already there.
+                    for path in self.created.lock().unwrap().iter_mut() {
+                        if let Ok(relative) = path.strip_prefix(staging) {
+                            if !kept.contains(path) {
+                                *path = self.playlist_dir().join(relative);
+                            }
+                        }
+                    }
+                    if let Ok(kept) = &moved {
+                        self.staging_kept = kept.clone();
+                    }
+                    if let Err(e) = moved {
+                        self.error_message = format!(
+                            "Downloaded, but moving files from {} failed: {}",
+                            staging.display(),
+                            e
+                        );
+                        self.record_history();
+                        self.state = AppState::Error;
+                        return true;
+                    }
+                } else {
+                    let _ = std::fs::remove_dir_all(staging);
+                }
+            }
+            if success && self.config.organize_by_tags && !self.options.video {
+                self.organize_playlist();
+            }
+            self.record_history();
+
+            if success {
+                let music_dir = self.playlist_dir();
+
+                // Paths relative to the playlist folder; library mode and
+                // date folders nest them in subfolders.
+                self.files_downloaded = walk_files(&music_dir)
+                    .iter()
+                    .filter(|p| self.is_output_file(p))
+                    .filter_map(|p| p.strip_prefix(&music_dir).ok())
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect();
+                self.files_downloaded.sort();
+                if self.options.max_quality_audio {
+                    self.resolved_audio = self
+                        .created_files()
+                        .iter()
+                        .filter(|p| self.is_output_file(p))
+                        .filter_map(|p| audio_info(&self.config.ffmpeg_program(), p))
+                        .collect();
+                }
+                // Video downloads don't list audio files, so the worker's
+                // previews are added here to show up next to their video.
+                for preview in self.created_files().iter().filter(|p| is_preview(p)) {
+                    if let Ok(name) = preview.strip_prefix(&music_dir) {
+                        let name = name.to_string_lossy().into_owned();
+                        if !self.files_downloaded.contains(&name) {
+                            self.files_downloaded.push(name);
+                        }
+                    }
+                }
+                self.files_downloaded.sort();
+
+                if let Some(hook) = self.config.post_hook.clone() {
+                    self.run_post_hook(&hook);
+                }
+                self.done_list_state = ListState::default()
+                    .with_selected((!self.files_downloaded.is_empty()).then_some(0));
+                self.state = AppState::Done;
+            } else {
+                self.failure = classify_error(&self.download_output_final);
+                self.error_message = match self.failure {
+                    Some(FailureKind::YtDlpCrashed) => {
+                        match save_crash_log(&self.download_output_final) {
+                            Ok(path) => format!(
+                                "{} The full output is saved in {}.",
+                                FailureKind::YtDlpCrashed.hint(),
+                                path.display()
+                            ),
+                            Err(_) => FailureKind::YtDlpCrashed.hint().to_string(),
+                        }
+                    }
+                    Some(kind) => kind.hint().to_string(),
+                    None if self.url_queue.len() > 1 => format!(
+                        "{} of {} URLs failed. Check your connection and URLs.",
+                        failed,
+                        self.url_queue.len()
+                    ),
+                    None => "Download failed. Check your connection and URL.".to_string(),
+                };
+                self.state = AppState::Error;
+            }
+            return true;
+        }
+        false
+    }
+}
+
+/// A short, stable test video (the one yt-dlp's own test suite uses).
+const SELF_TEST_URL: &str = "https://www.youtube.com/watch?v=BaW_jenozKc";
+
+/// End-to-end check of the yt-dlp integration: downloads the test video into
+/// a scratch directory with the default options and checks a file came out.
+fn run_self_test() -> bool {
+    let dir = std::env::temp_dir().join(format!("ytd-self-test-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        println!("FAIL: could not create {}: {}", dir.display(), e);
+        return false;
+    }
+    println!("Downloading {} to {}", SELF_TEST_URL, dir.display());
+
+    let args = build_yt_dlp_args(
+        &DownloadOptions::default(),
+        &format!("{}/%(title)s.%(ext)s", dir.display()),
+        SELF_TEST_URL,
+    );
+    let result = Command::new(yt_dlp()).args(&args).output();
+
+    let ok = match result {
+        Err(e) => {
+            println!("FAIL: could not run yt-dlp: {}", e);
+            false
+        }
+        Ok(output) => {
+            let produced = std::fs::read_dir(&dir)
+                .map(|d| d.filter_map(|e| e.ok()).any(|e| is_audio_file(&e.path())))
+                .unwrap_or(false);
+            if output.status.success() && produced {
+                println!("OK: yt-dlp downloaded and converted the test video");
+                true
+            } else {
+                let log = String::from_utf8_lossy(&output.stderr);
+                println!("FAIL: yt-dlp exited with {}", output.status);
+                println!("{}", last_n_lines(&log, 10));
+                false
+            }
+        }
+    };
+
+    let _ = std::fs::remove_dir_all(&dir);
+    ok
+}
+
+/// Writes one JSON event as a line and flushes, so a reader on the other
+/// end of a pipe sees it straight away.
+fn emit_json(event: serde_json::Value) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", event);
+    let _ = stdout.flush();
+}
+
+/// `--url --json`: the same run as `run_headless`, reported as
+/// newline-delimited JSON events instead of yt-dlp's text.
+fn run_headless_json(app: &mut App) -> bool {
+    let (tx, rx) = mpsc::channel();
+    app.events = Some(tx);
+    app.state = AppState::Downloading;
+    app.start_download();
+
+    loop {
+        if app.terminate_requested.swap(false, Ordering::SeqCst) {
+            app.shut_down();
+        }
+        let finished = app.state != AppState::Downloading || app.check_download();
+        for event in rx.try_iter() {
+            emit_json(match event {
+                DownloadEvent::Progress(p) => serde_json::json!({
+                    "event": "progress",
+                    "percent": p.percent,
+                    "speed": p.speed,
+                    "eta": p.eta,
+                    "elapsed": p.elapsed,
+                    "downloaded": p.downloaded,
+                }),
+                DownloadEvent::File(path) => serde_json::json!({
+                    "event": "file",
+                    "path": path,
+                }),
+            });
+        }
+        if finished {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let success = app.state == AppState::Done;
+    let hook = app.wait_for_post_hook();
+    let mut done = serde_json::json!({
+        "event": "done",
+        "success": success,
+        "files": app.created_output_count(),
+        "new_files": app.files_started.load(Ordering::SeqCst),
+        "skipped": app.skipped.load(Ordering::SeqCst),
+        "too_long": app.too_long.load(Ordering::SeqCst),
+    });
+    if !success {
+        done["error"] = app.error_message.clone().into();
+    }
+    if let Some(hook) = hook {
+        done["hook"] = hook.into();
+    }
+    emit_json(done);
+    success
+}
+
+/// "Exported 12 entries to ..." plus how many files had gone missing.
+fn describe_export(summary: &ExportSummary, out: &Path) -> String {
+    let mut message = format!(
+        "Exported {} entr{} to {}",
+        summary.written,
+        if summary.written == 1 { "y" } else { "ies" },
+        out.display()
+    );
+    if !summary.missing.is_empty() {
+        message.push_str(&format!(
+            " ({} missing file{} left out)",
+            summary.missing.len(),
+            if summary.missing.len() == 1 { "" } else { "s" }
+        ));
+    }
+    message
+}
+
+/// `--url X --stdout`: streams the media itself to stdout, for
+/// `ytd --url X --stdout | mpv -`. Nothing is saved and nothing is
+/// post-processed; yt-dlp sends its progress to stderr on its own.
+fn run_to_stdout(config: &Config, url: &str) -> bool {
+    if io::stdout().is_terminal() {
+        eprintln!("ytd: --stdout writes raw media; pipe it into a player or a file");
+        return false;
+    }
+    let format = config
+        .format
+        .clone()
+        .unwrap_or_else(|| DownloadOptions::default().format);
+    let mut command = Command::new(yt_dlp());
+    command.args(["-f", &format, "--output", "-"]);
+    if let Some(cookies) = &config.cookies_file {
+        command.arg("--cookies").arg(cookies);
+    }
+    if let Some(path) = &config.ffmpeg_location {
+        command.arg("--ffmpeg-location").arg(path);
+    }
+    command
+        .arg(normalize_url(url, config.keep_url_timestamp))
+        .stdin(std::process::Stdio::null());
+    match command.status() {
+        Ok(status) => status.success(),
+        Err(e) => {
+            eprintln!("ytd: could not start yt-dlp: {}", e);
+            false
+        }
+    }
+}
+
+/// The `--url` mode: runs the queued download with the configured options
+/// and prints yt-dlp's output line by line instead of drawing the TUI.
+fn run_headless(app: &mut App) -> bool {
+    app.state = AppState::Downloading;
+    app.start_download();
+    if app.state == AppState::Error {
+        eprintln!("{}", app.error_message);
+        return false;
+    }
+
+    let mut printed = 0;
+    loop {
+        if app.terminate_requested.swap(false, Ordering::SeqCst) {
+            app.shut_down();
+        }
+        let done = app.check_download();
+        {
+            let output = app.download_output.lock().unwrap();
+            for line in output.since(printed) {
+                println!("{}", line);
+            }
+            printed = output.total();
+        }
+        if done {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    if let Some(hook) = app.wait_for_post_hook() {
+        eprintln!("{}", hook);
+    }
+
+    match app.state {
+        AppState::Done if app.nothing_new() => {
+            println!(
+                "All items already downloaded ({} skipped) in {}",
+                app.skipped.load(Ordering::SeqCst),
+                app.playlist_dir().display()
+            );
+            true
+        }
+        AppState::Done => {
+            println!(
+                "Done: {} file(s) in {}",
+                app.files_downloaded.len(),
+                app.playlist_dir().display()
+            );
+            true
+        }
+        _ => {
+            eprintln!("{}", app.error_message);
+            false
+        }
+    }
+}
+
+/// Switches to ytd's own yt-dlp, downloading it first if it isn't there.
+/// Falls back to the one on PATH if that fails.
+fn use_managed_yt_dlp() {
+    let Some(path) = managed::managed_path() else {
+        eprintln!("ytd: no data directory for a managed yt-dlp, using the one on PATH");
+        return;
+    };
+    if !path.is_file() {
+        eprintln!("ytd: downloading yt-dlp into {}...", path.display());
+        if let Err(e) = managed::install_latest(&path) {
+            eprintln!(
+                "ytd: could not download yt-dlp ({}), using the one on PATH",
+                e
+            );
+            return;
+        }
+    }
+    managed::use_program(path);
+}
+
+fn main() -> io::Result<()> {
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if cli.self_test {
+        std::process::exit(if run_self_test() { 0 } else { 1 });
+    }
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(out) = &cli.export {
+        let history = History::load();
+        match export_playlist(&history.entries, ExportFormat::for_path(out), out) {
+            Ok(summary) => {
+                for file in &summary.missing {
+                    eprintln!("ytd: skipped missing file {}", file.display());
+                }
+                println!("{}", describe_export(&summary, out));
+            }
+            Err(e) => {
+                eprintln!("ytd: could not write {}: {}", out.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if config.yt_dlp_source == YtDlpSource::Managed {
+        use_managed_yt_dlp();
+    }
+
+    if cli.stdout {
+        let ok = run_to_stdout(&config, &cli.urls[0]);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // Resolved before entering the TUI so a missing home directory fails
+    // loudly instead of downloading relative to the working directory.
+    let music_dir = if cli.here {
+        std::env::current_dir()
+            .map_err(|e| format!("Could not determine the current directory: {}", e))
+    } else {
+        config.music_dir()
+    };
+    let music_dir = match music_dir {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if !cli.urls.is_empty() && !cli.watch {
+        if let Some(warning) = ffmpeg_warning(&config) {
+            eprintln!("ytd: {}", warning);
+        }
+        let default_playlist = config.default_playlist.clone();
+        let mut app = App::new(config, music_dir, History::load());
+        app.headless = true;
+        app.watch_sigterm()?;
+        app.playlist_name = cli
+            .playlist
+            .or(default_playlist)
+            .unwrap_or_else(|| "Downloads".to_string());
+        app.url_queue = cli.urls.iter().map(|url| app.clean_url(url)).collect();
+        let ok = if cli.json {
+            run_headless_json(&mut app)
+        } else {
+            run_headless(&mut app)
+        };
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // Raw mode and the alternate screen make a mess of a pipe or a file.
+    if !io::stdout().is_terminal() {
+        eprintln!(
+            "ytd: stdout is not a terminal, so the interactive UI can't start.\n\
+             For non-interactive downloads use: ytd --url <URL> [--playlist <name>]"
+        );
+        std::process::exit(2);
+    }
+
+    // Before raw mode, so failing here leaves the terminal as it was.
+    let mut app = App::new(config, music_dir, History::load());
+    app.watch_sigterm()?;
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    app.compact = cli.compact;
+    if cli.watch {
+        let default_playlist = app.config.default_playlist.clone();
+        app.playlist_name = cli
+            .playlist
+            .or(default_playlist)
+            .unwrap_or_else(|| "Downloads".to_string());
+        app.url_queue = vec![app.clean_url(&cli.urls[0])];
+        app.begin_watch();
+    } else if cli.clip {
+        app.start_from_clipboard();
+    } else if Config::is_first_run() {
+        // The wizard checks for ffmpeg itself.
+        app.begin_setup();
+    } else {
+        app.use_default_playlist();
+        app.startup_warning = ffmpeg_warning(&app.config);
+    }
+    let mut spinner_frame = 0u32;
+    let mut spun_at = Instant::now();
+    // What the download screen was last drawn from, and whether something
+    // it doesn't cover (a key, a resize) happened since.
+    let mut drawn: Option<DownloadFrame> = None;
+    let mut redraw = true;
+    let mut exit_message = None;
+    let mut mouse_captured = false;
+
+    loop {
+        if app.terminate_requested.load(Ordering::SeqCst) {
+            app.shut_down();
+            break;
+        }
+
+        // Follows the setting, which can change on the settings screen.
+        if app.config.mouse != mouse_captured {
+            if app.config.mouse {
+                execute!(terminal.backend_mut(), EnableMouseCapture)?;
+            } else {
+                execute!(terminal.backend_mut(), DisableMouseCapture)?;
+            }
+            mouse_captured = app.config.mouse;
+        }
+
+        if app.state == AppState::Downloading {
+            app.follow_log_errors();
+        }
+        let frame = (app.state == AppState::Downloading).then(|| app.download_frame(spinner_frame));
+        if redraw || frame.is_none() || frame != drawn {
+            app.list_area = None;
+            terminal.draw(|f| draw(f, &mut app, spinner_frame))?;
+        }
+        drawn = frame;
+        redraw = false;
+
+        if app.state == AppState::Downloading {
+            if !app.is_paused() && spun_at.elapsed() >= SPINNER_INTERVAL {
+                spinner_frame = spinner_frame.wrapping_add(1);
+                spun_at = Instant::now();
+            }
+
+            if event::poll(Duration::from_millis(50))? {
+                redraw = true;
+                let key = match event::read()? {
+                    Event::Key(key) => Some(key),
+                    Event::Mouse(mouse) => app.mouse_key(mouse),
+                    _ => None,
+                };
+                if let Some(key) = key {
+                    if let Some(pager) = app.pager.as_mut() {
+                        let output = app.download_output.lock().unwrap().text();
+                        if !pager.handle_key(key, &output) {
+                            app.pager = None;
+                        }
+                        if app.check_download() {
+                            app.recover_from_failure();
+                        }
+                        continue;
+                    }
+                    // Few terminals report Shift+Esc, so 'x' does the same.
+                    if (key.code == KeyCode::Esc && key.modifiers.contains(KeyModifiers::SHIFT))
+                        || key.code == KeyCode::Char('x')
+                    {
+                        exit_message = Some(app.cancel_and_clean());
+                        break;
+                    }
+                    if key.code == KeyCode::Char('q')
+                        || (key.code == KeyCode::Char('c')
+                            && key.modifiers.contains(KeyModifiers::CONTROL))
+                    {
+                        app.cancel_download();
+                        break;
+                    }
+                    match key.code {
+                        KeyCode::Esc => app.cancel_to_url_input(),
+                        KeyCode::Char('k') => app.skip_current_item(),
+                        KeyCode::Char('p') => app.toggle_pause(),
+                        KeyCode::Char('y') => app.answer_quality_prompt(true),
+                        KeyCode::Char('n') => app.answer_quality_prompt(false),
+                        KeyCode::Char('l') => {
+                            app.show_full_log = !app.show_full_log;
+                            app.log_scroll = 0;
+                            app.log_pin = None;
+                        }
+                        KeyCode::Char('v') => app.pager = Some(Pager::new()),
+                        KeyCode::Char('s') if app.live_status() != LiveStatus::NotLive => {
+                            app.stop_recording()
+                        }
+                        KeyCode::Up if app.show_full_log => {
+                            app.log_pin = None;
+                            app.log_scroll += 1
+                        }
+                        KeyCode::Down if app.show_full_log => {
+                            app.log_pin = None;
+                            app.log_scroll = app.log_scroll.saturating_sub(1)
+                        }
+                        KeyCode::PageUp if app.show_full_log => {
+                            app.log_pin = None;
+                            app.log_scroll += 10
+                        }
+                        KeyCode::PageDown if app.show_full_log => {
+                            app.log_pin = None;
+                            app.log_scroll = app.log_scroll.saturating_sub(10)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            if app.check_download() {
+                app.recover_from_failure();
+            }
+            continue;
+        }
+
+        if app.state == AppState::Confirm {
+            app.request_size_estimate();
+        }
+        if app.state == AppState::Diagnostics {
+            app.check_yt_dlp_update();
+        }
+        if app.state == AppState::Scheduled {
+            app.check_schedule();
+            if app.state == AppState::Downloading {
+                continue;
+            }
+        }
+        if app.watch.is_some() && matches!(app.state, AppState::Done | AppState::Error) {
+            app.finish_watch_check();
+        }
+        if app.state == AppState::Watching {
+            app.check_watch();
+            if app.state == AppState::Downloading {
+                continue;
+            }
+        }
+
+        // Keep redrawing while the playlist count or search is still
+        // coming in, and every second of a countdown.
+        let waiting = match app.state {
+            AppState::Confirm => {
+                app.item_count() == ItemCount::Counting
+                    || matches!(app.duplicates(), Some(Lookup::Pending))
+                    || app.is_checking_live()
+                    || app.size_estimate_entry() == Some(None)
+            }
+            AppState::SearchResults => app.is_searching(),
+            AppState::Formats => matches!(*app.formats.lock().unwrap(), Lookup::Pending),
+            AppState::Done => app.hook_running.load(Ordering::SeqCst),
+            AppState::Diagnostics => app.is_updating_yt_dlp(),
+            AppState::Scheduled | AppState::Watching => true,
+            _ => false,
+        };
+        // Idle screens still wake up now and then to notice a SIGTERM.
+        let timeout = if waiting { 50 } else { 250 };
+        if !event::poll(Duration::from_millis(timeout))? {
+            continue;
+        }
+
+        let key = match event::read()? {
+            Event::Key(key) => Some(key),
+            Event::Mouse(mouse) => app.mouse_key(mouse),
+            _ => None,
+        };
+        if let Some(key) = key {
+            if key.kind == KeyEventKind::Press {
+                match app.state {
+                    AppState::Setup(step) => match key.code {
+                        KeyCode::Enter => app.advance_setup(step),
+                        KeyCode::Esc => app.skip_setup(),
+                        KeyCode::Up if step == SetupStep::Format => {
+                            app.setup_selected = app.setup_selected.saturating_sub(1)
+                        }
+                        KeyCode::Down
+                            if step == SetupStep::Format
+                                && app.setup_selected + 1 < SETUP_FORMATS.len() =>
+                        {
+                            app.setup_selected += 1
+                        }
+                        KeyCode::Char(c) if step == SetupStep::MusicDir => {
+                            app.field_input.push(c);
+                            app.input_error = None;
+                        }
+                        KeyCode::Backspace if step == SetupStep::MusicDir => {
+                            app.field_input.pop();
+                            app.input_error = None;
+                        }
+                        _ => {}
+                    },
+                    AppState::InputPlaylistName => {
+                        app.input_error = None;
+                        if key.code == KeyCode::Enter && discard_rest_of_paste()? {
+                            // Keep the first line of the paste to edit.
+                        } else if matches!(key.code, KeyCode::Enter | KeyCode::Tab) {
+                            // The URL typed before coming back here is kept.
+                            app.playlist_name = app.playlist_name.trim().to_string();
+                            if app.playlist_name.is_empty() {
+                                app.input_error = Some("Playlist name can't be empty".to_string());
+                            } else {
+                                app.state = AppState::InputUrl;
+                            }
+                        } else if key.code == KeyCode::Char('d')
+                            && key.modifiers.contains(KeyModifiers::CONTROL)
+                        {
+                            app.diagnostics =
+                                Some((gather_diagnostics(&app.config, &app.music_dir), None));
+                            app.state = AppState::Diagnostics;
+                        } else if let KeyCode::Char(c) = key.code {
+                            let max = app.config.max_name_length;
+                            if app.playlist_name.chars().count() < max {
+                                app.playlist_name.push(c);
+                            } else {
+                                app.input_error =
+                                    Some(format!("Name too long (at most {} characters)", max));
+                            }
+                        } else if key.code == KeyCode::Backspace {
+                            app.playlist_name.pop();
+                        } else if key.code == KeyCode::Esc {
+                            break;
+                        }
+                    }
+                    AppState::InputUrl => {
+                        app.input_error = None;
+                        if key.code == KeyCode::Tab {
+                            app.search_mode = !app.search_mode;
+                        } else if key.code == KeyCode::BackTab
+                            || (key.code == KeyCode::Up && app.url.is_empty())
+                        {
+                            app.state = AppState::InputPlaylistName;
+                        } else if key.code == KeyCode::Enter && discard_rest_of_paste()? {
+                            // Keep the first line of the paste to edit.
+                        } else if key.code == KeyCode::Enter && app.search_mode {
+                            if app.url.trim().is_empty() {
+                                app.input_error = Some("Type something to search for".to_string());
+                            } else {
+                                app.start_search();
+                            }
+                        } else if key.code == KeyCode::Enter {
+                            app.url_queue = app
+                                .url
+                                .split_whitespace()
+                                .map(|url| app.clean_url(url))
+                                .collect();
+                            if app.url_queue.is_empty() {
+                                app.input_error = Some("Enter at least one URL".to_string());
+                            } else {
+                                app.state = AppState::Confirm;
+                                app.request_item_count();
+                                app.request_duplicate_check();
+                                app.request_live_check();
+                            }
+                        } else if let KeyCode::Char(c) = key.code {
+                            app.url.push(c);
+                        } else if key.code == KeyCode::Backspace {
+                            app.url.pop();
+                        } else if key.code == KeyCode::Esc {
+                            break;
+                        }
+                    }
+                    AppState::SearchResults => match key.code {
+                        KeyCode::Up => app.search_selected = app.search_selected.saturating_sub(1),
+                        KeyCode::Down
+                            if app.search_selected + 1 < app.search.lock().unwrap().len() =>
+                        {
+                            app.search_selected += 1
+                        }
+                        KeyCode::Enter => app.choose_search_result(),
+                        KeyCode::Esc => app.state = AppState::InputUrl,
+                        _ => {}
+                    },
+                    AppState::Formats => match key.code {
+                        KeyCode::Up => app.format_selected = app.format_selected.saturating_sub(1),
+                        KeyCode::Down
+                            if app.format_selected + 1 < app.formats.lock().unwrap().len() =>
+                        {
+                            app.format_selected += 1
+                        }
+                        KeyCode::Enter => app.choose_format(),
+                        KeyCode::Esc => {
+                            app.retry_with_format = false;
+                            app.pick_then_download = false;
+                            app.state = AppState::Confirm;
+                        }
+                        _ => {}
+                    },
+                    AppState::Queue => match key.code {
+                        KeyCode::Char('K') => app.move_queue_item(-1),
+                        KeyCode::Char('J') => app.move_queue_item(1),
+                        KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.move_queue_item(-1)
+                        }
+                        KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.move_queue_item(1)
+                        }
+                        KeyCode::Up => app.queue_selected = app.queue_selected.saturating_sub(1),
+                        KeyCode::Down if app.queue_selected + 1 < app.url_queue.len() => {
+                            app.queue_selected += 1
+                        }
+                        KeyCode::Char('d') => app.remove_queue_item(),
+                        KeyCode::Enter if !app.url_queue.is_empty() => {
+                            app.state = AppState::Downloading;
+                            app.start_download();
+                        }
+                        KeyCode::Esc if app.url_queue.is_empty() => app.state = AppState::InputUrl,
+                        KeyCode::Esc => {
+                            // Down to one URL, the confirm screen's
+                            // single-URL checks apply again.
+                            app.state = AppState::Confirm;
+                            app.request_item_count();
+                            app.request_duplicate_check();
+                            app.request_live_check();
+                        }
+                        _ => {}
+                    },
+                    AppState::Confirm => match key.code {
+                        KeyCode::Enter if app.item_count() != ItemCount::Counting => {
+                            app.confirm_download()
+                        }
+                        KeyCode::Char('q') if app.url_queue.len() > 1 => {
+                            app.queue_selected = 0;
+                            app.state = AppState::Queue;
+                        }
+                        KeyCode::Char('b') => {
+                            if app.low_bandwidth {
+                                app.clear_low_bandwidth_preset();
+                            } else {
+                                app.apply_low_bandwidth_preset();
+                            }
+                        }
+                        KeyCode::Char('n') => app.only_new = !app.only_new,
+                        KeyCode::Char('a') => app.set_video(false),
+                        KeyCode::Char('v') => app.set_video(true),
+                        KeyCode::Char('e') if app.resume_point().is_some() => {
+                            app.resume = !app.resume
+                        }
+                        KeyCode::Char('r') if app.live_status() == LiveStatus::Live => {
+                            app.options.live_from_start = !app.options.live_from_start
+                        }
+                        KeyCode::Char('w') if app.live_status() == LiveStatus::Upcoming => {
+                            app.options.wait_for_video = !app.options.wait_for_video
+                        }
+                        KeyCode::Char('d') => app.begin_edit(Field::DateAfter),
+                        KeyCode::Char('l') => app.begin_edit(Field::AudioLanguage),
+                        KeyCode::Char('m') => app.begin_edit(Field::MaxDownloads),
+                        KeyCode::Char('i') => app.begin_edit(Field::LatestItems),
+                        KeyCode::Char('t') => app.begin_edit(Field::StartAt),
+                        KeyCode::Char('c') => app.begin_edit(Field::CoverImage),
+                        KeyCode::Char('k') => app.begin_edit(Field::CookiesFile),
+                        KeyCode::Char('g') => app.begin_edit(Field::Genre),
+                        KeyCode::Char('x') => app.begin_edit(Field::MaxDuration),
+                        KeyCode::Char('s') => app.open_settings(),
+                        KeyCode::Char('f') => app.open_format_picker(),
+                        KeyCode::Char('u') if app.config.login => app.begin_edit(Field::Username),
+                        KeyCode::Char('p') if app.config.login => app.begin_edit(Field::Password),
+                        KeyCode::Esc => app.state = AppState::InputUrl,
+                        _ => {}
+                    },
+                    AppState::Settings => match key.code {
+                        KeyCode::Up => {
+                            app.settings_selected = app.settings_selected.saturating_sub(1)
+                        }
+                        KeyCode::Down if app.settings_selected + 1 < SETTINGS.len() => {
+                            app.settings_selected += 1
+                        }
+                        KeyCode::Enter | KeyCode::Char(' ') => app.toggle_selected_setting(),
+                        KeyCode::Esc => app.state = app.settings_return,
+                        _ => {}
+                    },
+                    AppState::EditField(field) => match key.code {
+                        KeyCode::Enter => app.commit_edit(field),
+                        KeyCode::Char(c) => {
+                            app.field_input.push(c);
+                            app.input_error = None;
+                        }
+                        KeyCode::Backspace => {
+                            app.field_input.pop();
+                            app.input_error = None;
+                        }
+                        KeyCode::Esc => app.state = AppState::Confirm,
+                        _ => {}
+                    },
+                    AppState::Downloading => {
+                        if app.check_download() {
+                            // Download finished, state updated in check_download
+                        }
+                        if key.code == KeyCode::Esc {
+                            app.cancel_to_url_input();
+                        }
+                    }
+                    AppState::Done => match key.code {
+                        KeyCode::Char('y') if app.confirm_undo => app.undo_last_download(),
+                        _ if app.confirm_undo => app.confirm_undo = false,
+                        KeyCode::Char('D') if !app.created_files().is_empty() => {
+                            app.confirm_undo = true
+                        }
+                        KeyCode::Enter => break,
+                        KeyCode::Char('R') => app.rerun_last(),
+                        KeyCode::Up => app.done_list_state.select_previous(),
+                        KeyCode::Down
+                            if app
+                                .done_list_state
+                                .selected()
+                                .is_some_and(|i| i + 1 < app.files_downloaded.len()) =>
+                        {
+                            app.done_list_state.select_next()
+                        }
+                        KeyCode::PageUp => {
+                            let at = app.done_list_state.selected().unwrap_or(0);
+                            app.done_list_state.select(Some(at.saturating_sub(10)));
+                        }
+                        KeyCode::PageDown => {
+                            let last = app.files_downloaded.len().saturating_sub(1);
+                            let at = app.done_list_state.selected().unwrap_or(0);
+                            app.done_list_state.select(Some((at + 10).min(last)));
+                        }
+                        KeyCode::Char('p') => app.play_downloaded(false),
+                        KeyCode::Char('P') => app.play_downloaded(true),
+                        KeyCode::Char('e') => app.export_session(),
+                        KeyCode::Char('r') if !app.files_downloaded.is_empty() => {
+                            app.rename_selected = app.done_list_state.selected().unwrap_or(0);
+                            app.rename_input = None;
+                            app.state = AppState::Rename;
+                        }
+                        _ => {}
+                    },
+                    AppState::Rename => {
+                        if let Some(input) = app.rename_input.as_mut() {
+                            match key.code {
+                                KeyCode::Enter => app.commit_rename(),
+                                KeyCode::Char(c) => {
+                                    input.push(c);
+                                    app.input_error = None;
+                                }
+                                KeyCode::Backspace => {
+                                    input.pop();
+                                    app.input_error = None;
+                                }
+                                KeyCode::Esc => {
+                                    app.rename_input = None;
+                                    app.input_error = None;
+                                }
+                                _ => {}
+                            }
+                        } else {
+                            match key.code {
+                                KeyCode::Up => {
+                                    app.rename_selected = app.rename_selected.saturating_sub(1)
+                                }
+                                KeyCode::Down
+                                    if app.rename_selected + 1 < app.files_downloaded.len() =>
+                                {
+                                    app.rename_selected += 1;
+                                }
+                                KeyCode::Enter => app.begin_rename(),
+                                KeyCode::Esc => app.state = AppState::Done,
+                                _ => {}
+                            }
+                        }
+                    }
+                    AppState::Diagnostics => match key.code {
+                        KeyCode::Char('c') => {
+                            if let Some((diagnostics, status)) = app.diagnostics.as_mut() {
+                                *status = Some(
+                                    if clipboard::write_clipboard(&diagnostics.to_text()) {
+                                        Ok("Copied to the clipboard".to_string())
+                                    } else {
+                                        Err("Couldn't copy: no clipboard tool found (wl-copy, xclip or xsel)".to_string())
+                                    },
+                                );
+                            }
+                        }
+                        KeyCode::Char('u') => app.request_yt_dlp_update(),
+                        KeyCode::Char('x') => {
+                            let cleared = app.clear_yt_dlp_cache();
+                            if let Some((_, status)) = app.diagnostics.as_mut() {
+                                *status = Some(cleared);
+                            }
+                        }
+                        KeyCode::Esc => {
+                            app.diagnostics = None;
+                            app.state = AppState::InputPlaylistName;
+                        }
+                        _ => {}
+                    },
+                    AppState::Scheduled => match key.code {
+                        KeyCode::Esc => {
+                            app.scheduled_for = None;
+                            app.state = AppState::Confirm;
+                        }
+                        KeyCode::Char('q') => break,
+                        _ => {}
+                    },
+                    AppState::Watching => match key.code {
+                        KeyCode::Esc => {
+                            app.watch = None;
+                            app.state = AppState::InputUrl;
+                        }
+                        KeyCode::Char('q') => break,
+                        _ => {}
+                    },
+                    AppState::Error => match key.code {
+                        KeyCode::Enter => break,
+                        KeyCode::Char('C') if app.failure == Some(FailureKind::StaleCache) => {
+                            app.error_message = match app.clear_yt_dlp_cache() {
+                                Ok(message) => format!("{}. R to try the download again.", message),
+                                Err(e) => e,
+                            };
+                        }
+                        KeyCode::Char('D') if app.failure == Some(FailureKind::YtDlpCrashed) => {
+                            app.diagnostics =
+                                Some((gather_diagnostics(&app.config, &app.music_dir), None));
+                            app.state = AppState::Diagnostics;
+                        }
+                        KeyCode::Char('R') => app.rerun_last(),
+                        _ => {}
+                    },
+                }
+            }
+        }
+    }
+
+    if mouse_captured {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    if let Some(message) = exit_message {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+/// Without bracketed paste, a newline inside pasted text arrives as Enter
+/// with the rest of the paste queued right behind it, where typing never
+/// is. Throws that rest away and returns whether there was any, so the
+/// Enter can be ignored instead of submitting half a paste.
+fn discard_rest_of_paste() -> io::Result<bool> {
+    let mut discarded = false;
+    while event::poll(Duration::ZERO)? {
+        event::read()?;
+        discarded = true;
+    }
+    Ok(discarded)
+}
+
+/// Returns the last `n` lines of `text`, joined with `\n`. A trailing newline
+/// does not count as an extra empty line.
+fn last_n_lines(text: &str, n: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.len().saturating_sub(n);
+    lines[start..].join("\n")
+}
+
+/// `text` as lines, with yt-dlp's errors in red and warnings in yellow and
+/// everything else in `color`.
+fn log_lines(text: &str, color: Color) -> Vec<Line<'_>> {
+    text.lines()
+        .map(|line| {
+            let color = match Severity::of(line) {
+                Some(Severity::Error) => Color::Red,
+                Some(Severity::Warning) => Color::Yellow,
+                None => color,
+            };
+            Line::styled(line, Style::default().fg(color))
+        })
+        .collect()
+}
+
+fn progress_bar(percent: f64, width: usize) -> String {
+    let filled = ((percent.clamp(0.0, 100.0) / 100.0) * width as f64).round() as usize;
+    format!("[{}{}]", "=".repeat(filled), " ".repeat(width - filled))
+}
+
+/// The title bar text. While downloading it carries the overall progress,
+/// "ytd — Item 3/12 — 45% — My Playlist", cutting the playlist name short
+/// to fit in `width` columns.
+fn title_text(app: &App, width: usize) -> String {
+    if app.state != AppState::Downloading {
+        return "YouTube Downloader TUI".to_string();
+    }
+    let total = app.total_items.load(Ordering::SeqCst);
+    let current = app.current_item.load(Ordering::SeqCst);
+    let percent = app.progress.lock().unwrap().percent;
+    let mut title = String::from("ytd");
+    if total > 1 && current > 0 {
+        title.push_str(&format!(" — Item {}/{}", current, total));
+    }
+    if let Some(percent) = percent {
+        let overall = if total > 1 && current > 0 {
+            ((current - 1) as f64 + percent.clamp(0.0, 100.0) / 100.0) / total as f64 * 100.0
+        } else {
+            percent
+        };
+        title.push_str(&format!(" — {:.0}%", overall));
+    }
+    let room = width.saturating_sub(title.chars().count() + " — ".chars().count());
+    if room > 0 && !app.playlist_name.is_empty() {
+        let name = &app.playlist_name;
+        title.push_str(" — ");
+        if name.chars().count() <= room {
+            title.push_str(name);
+        } else if room > 1 {
+            title.extend(name.chars().take(room - 1));
+            title.push('…');
+        }
+    }
+    title
+}
+
+/// The wizard's tool check as one line, for the compact layout.
+fn setup_tools_summary(tools: Option<(bool, bool)>) -> String {
+    let (yt_dlp, ffmpeg) = tools.unwrap_or_default();
+    let mark = |found| if found { "found" } else { "missing" };
+    format!("yt-dlp {}, ffmpeg {}", mark(yt_dlp), mark(ffmpeg))
+}
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How long each spinner frame shows for.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Everything the download screens are drawn from. Compared between loop
+/// ticks so an unchanged screen isn't redrawn, which on a slow SSH link is
+/// most of them.
+#[derive(PartialEq)]
+struct DownloadFrame {
+    spinner: u32,
+    output_lines: usize,
+    progress: Progress,
+    item: (usize, usize),
+    queue_position: usize,
+    files_started: usize,
+    skipped: usize,
+    paused: bool,
+    rate_limit_wait: Option<u64>,
+    quality_prompt: QualityPrompt,
+    log_view: (bool, usize, bool),
+}
+
+/// Below this many rows the boxed layout can't fit its boxes, so the
+/// compact one is used whatever `--compact` says.
+const FULL_UI_MIN_HEIGHT: u16 = 8;
+
+/// Picks the view for the current state and terminal size.
+fn draw(f: &mut Frame, app: &mut App, spinner_frame: u32) {
+    if app.state == AppState::Downloading && app.pager.is_some() {
+        ui_pager(f, app)
+    } else if app.compact || f.area().height < FULL_UI_MIN_HEIGHT {
+        ui_compact(f, app, spinner_frame)
+    } else {
+        ui(f, app, spinner_frame)
+    }
+}
+
+/// The title, input, main and hint rows of the boxed layout. Short
+/// terminals drop the title and squeeze the hint so the main area keeps
+/// some room; everything beyond the fixed rows goes to the main area.
+fn ui_rows(area: Rect) -> std::rc::Rc<[Rect]> {
+    let (title, hint) = if area.height < 16 { (0, 2) } else { (3, 3) };
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(title),
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(hint),
+        ])
+        .split(area)
+}
+
+/// The `--compact` layout: one status line plus, if there's room, a hint
+/// line. Works down to a single row.
+fn ui_compact(f: &mut Frame, app: &App, spinner_frame: u32) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(f.area());
+
+    let (status, hint) = match app.state {
+        AppState::Setup(SetupStep::MusicDir) => (
+            format!("Music folder: {}", app.field_input),
+            "Enter to continue, Esc to skip setup",
+        ),
+        AppState::Setup(SetupStep::Format) => (
+            format!("Format: {}", SETUP_FORMATS[app.setup_selected].0),
+            "Up/Down, Enter to continue, Esc to skip setup",
+        ),
+        AppState::Setup(SetupStep::Tools) => {
+            (setup_tools_summary(app.setup_tools), "Enter to finish")
+        }
+        AppState::InputPlaylistName => (
+            format!("Playlist: {}", app.playlist_name),
+            "Enter/Tab to continue, Ctrl+D diagnostics, Esc to quit",
+        ),
+        AppState::InputUrl => (
+            format!(
+                "{}: {}",
+                if app.search_mode { "Search" } else { "URL" },
+                app.url
+            ),
+            "Enter to continue, Tab search/URL, Shift+Tab back, Esc to quit",
+        ),
+        AppState::SearchResults => (
+            match &*app.search.lock().unwrap() {
+                Lookup::Pending => format!("Searching for {}...", app.url.trim()),
+                Lookup::Ready(results) => match results.get(app.search_selected) {
+                    Some(r) => {
+                        format!("{}/{}: {}", app.search_selected + 1, results.len(), r.title)
+                    }
+                    None => "No results".to_string(),
+                },
+                Lookup::Failed(e) => format!("Search failed: {}", e),
+            },
+            "Up/Down, Enter to pick, Esc to go back",
+        ),
+        AppState::Queue => (
+            match app.url_queue.get(app.queue_selected) {
+                Some(url) => format!(
+                    "Queue {}/{}: {}",
+                    app.queue_selected + 1,
+                    app.url_queue.len(),
+                    url
+                ),
+                None => "Queue is empty".to_string(),
+            },
+            "J/K move, d remove, Enter to download, Esc back",
+        ),
+        AppState::Formats => (
+            match &*app.formats.lock().unwrap() {
+                Lookup::Pending => "Fetching formats...".to_string(),
+                Lookup::Ready(formats) => match formats.get(app.format_selected) {
+                    Some(f) => format!(
+                        "{}/{}: {}",
+                        app.format_selected + 1,
+                        formats.len(),
+                        f.label()
+                    ),
+                    None => "No formats".to_string(),
+                },
+                Lookup::Failed(e) => format!("Could not list formats: {}", e),
+            },
+            "Up/Down, Enter to pick, Esc to go back",
+        ),
+        AppState::Confirm => (
+            format!(
+                "Download {}{} to {}?",
+                app.url_queue.join(" "),
+                if app.options.video { " as video" } else { "" },
+                app.playlist_name
+            ),
+            "Enter to download, Esc to go back",
+        ),
+        AppState::Scheduled => (
+            match app.scheduled_for {
+                Some(at) => format!(
+                    "Starting {} at {} (in {})",
+                    app.playlist_name,
+                    at.format("%H:%M"),
+                    format_countdown((at - Local::now()).num_seconds())
+                ),
+                None => "Starting...".to_string(),
+            },
+            "Esc to cancel the schedule, q to quit",
+        ),
+        AppState::Watching => (
+            format!(
+                "Watching {}: next check in {}",
+                app.playlist_name,
+                format_clock(app.watch_countdown())
+            ),
+            "Esc to stop watching, q to quit",
+        ),
+        AppState::Diagnostics => (
+            match &app.diagnostics {
+                Some((d, _)) => format!("ytd {}, yt-dlp {}", d.ytd, d.yt_dlp),
+                None => String::new(),
+            },
+            "c to copy, u to update yt-dlp, x to clear yt-dlp's cache, Esc to go back",
+        ),
+        AppState::EditField(field) => (
+            format!(
+                "{}: {}",
+                field.label(),
+                if field.is_masked() {
+                    "*".repeat(app.field_input.chars().count())
+                } else {
+                    app.field_input.clone()
+                }
+            ),
+            "Enter to save, Esc to cancel",
+        ),
+        AppState::Settings => {
+            let setting = SETTINGS[app.settings_selected];
+            let mark = if setting.get(&app.config) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            (
+                format!("{} {}", mark, setting.label()),
+                "Up/Down, Space to toggle, Esc to go back",
+            )
+        }
+        AppState::Downloading => {
+            let progress = app.progress.lock().unwrap().clone();
+            let spinner = SPINNER[(spinner_frame as usize) % SPINNER.len()];
+            let status = match (app.post_processing_phase(), progress.percent) {
+                (Some(phase), _) => format!("{} {}", spinner, phase),
+                (None, Some(p)) => {
+                    let mut status = format!("{} {:.0}%", progress_bar(p, 10), p);
+                    if let Some(speed) = progress.speed {
+                        status.push_str(&format!(" {}", speed));
+                    }
+                    if let Some(eta) = progress.eta {
+                        status.push_str(&format!(" ETA {}", eta));
+                    }
+                    status
+                }
+                (None, None) => match &progress.elapsed {
+                    Some(elapsed) => format!("● Recorded {}", elapsed),
+                    None => format!("{} Downloading...", spinner),
+                },
+            };
+            if app.quality_prompt() == QualityPrompt::Asking {
+                (
+                    "Connection is slow — switch to lower quality?".to_string(),
+                    "y lower quality, n keep going",
+                )
+            } else if app.is_paused() {
+                (format!("Paused: {}", status), "p resume, Esc cancel")
+            } else if let Some(wait) = app.rate_limit_wait() {
+                (
+                    format!("Rate limited, waiting {}s...", wait),
+                    "Esc cancel, q quit",
+                )
+            } else {
+                (
+                    status,
+                    if app.live_status() == LiveStatus::NotLive {
+                        "p pause, v log, Esc cancel, q quit, x cancel and clean"
+                    } else {
+                        "s stop recording, v log, Esc cancel, q quit, x cancel and clean"
+                    },
+                )
+            }
+        }
+        AppState::Done => (
+            if app.nothing_new() {
+                format!(
+                    "All items already downloaded ({} skipped)",
+                    app.skipped.load(Ordering::SeqCst)
+                )
+            } else {
+                format!(
+                    "Done: {} file(s) in {}",
+                    app.files_downloaded.len(),
+                    app.playlist_dir().display()
+                )
+            },
+            "p play, P play all, R run again, Enter to exit",
+        ),
+        AppState::Rename => (
+            format!(
+                "Rename: {}",
+                app.files_downloaded
+                    .get(app.rename_selected)
+                    .map(String::as_str)
+                    .unwrap_or("")
+            ),
+            "Esc to go back",
+        ),
+        AppState::Error => (
+            format!("Failed: {}", app.error_message),
+            if app.failure == Some(FailureKind::StaleCache) {
+                "C clear cache, R run again, Enter to exit"
+            } else if app.failure == Some(FailureKind::YtDlpCrashed) {
+                "D diagnostics, R run again, Enter to exit"
+            } else if app.last_run.is_some() {
+                "R to run again, Enter to exit"
+            } else {
+                "Enter to exit"
+            },
+        ),
+    };
+
+    f.render_widget(Paragraph::new(status), rows[0]);
+    let hint = match (&app.input_error, app.state) {
+        (Some(err), AppState::InputPlaylistName | AppState::InputUrl) => {
+            Paragraph::new(err.as_str()).style(Style::default().fg(Color::Red))
+        }
+        _ => Paragraph::new(hint).style(Style::default().fg(Color::DarkGray)),
+    };
+    f.render_widget(hint, rows[1]);
+}
+
+/// `height` lines of `text`, ending `scroll` lines above the bottom. The
+/// scroll is clamped so the window never runs past the top.
+fn log_window(text: &str, height: usize, scroll: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let end = lines
+        .len()
+        .saturating_sub(scroll)
+        .max(height.min(lines.len()));
+    let start = end.saturating_sub(height);
+    lines[start..end].join("\n")
+}
+
+/// The full-screen log pager. Search matches are highlighted.
+fn ui_pager(f: &mut Frame, app: &mut App) {
+    let output = app.download_output.lock().unwrap().text();
+    let lines: Vec<&str> = output.lines().collect();
+    let Some(pager) = app.pager.as_mut() else {
+        return;
+    };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.area());
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .title("Log (download continues in the background)");
+    pager.height = block.inner(rows[0]).height as usize;
+
+    let top = pager.top(lines.len());
+    let query = pager.query.as_deref().unwrap_or("");
+    let highlight = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let text: Vec<Line> = lines
+        .iter()
+        .skip(top)
+        .take(pager.height)
+        .map(|line| {
+            let mut spans = Vec::new();
+            let mut at = 0;
+            for (start, end) in pager::match_ranges(line, query) {
+                spans.push(Span::raw(line[at..start].to_string()));
+                spans.push(Span::styled(line[start..end].to_string(), highlight));
+                at = end;
+            }
+            spans.push(Span::raw(line[at..].to_string()));
+            Line::from(spans)
+        })
+        .collect();
+    f.render_widget(
+        Paragraph::new(text)
+            .style(Style::default().fg(Color::White))
+            .block(block),
+        rows[0],
+    );
+
+    let footer = if let Some(typing) = &pager.typing {
+        Paragraph::new(format!("/{}", typing)).style(Style::default().fg(Color::White))
+    } else if let Some(message) = &pager.message {
+        Paragraph::new(message.as_str()).style(Style::default().fg(Color::Red))
+    } else {
+        Paragraph::new(format!(
+            "Lines {}-{} of {} · j/k, Ctrl+D/U, gg/G, / search, n/N, q to go back",
+            (top + 1).min(lines.len()),
+            (top + pager.height).min(lines.len()),
+            lines.len()
+        ))
+        .style(Style::default().fg(Color::DarkGray))
+    };
+    f.render_widget(footer, rows[1]);
+}
+
+/// Draws a background lookup: a spinner while it runs, the error if it
+/// failed, otherwise the selectable list, whose area is returned.
+fn render_lookup<T>(
+    f: &mut Frame,
+    area: Rect,
+    title: &str,
+    lookup: &Lookup<T>,
+    selected: usize,
+    spinner_frame: u32,
+    label: impl Fn(&T) -> String,
+) -> Option<ListArea> {
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .title(title.to_string());
+    match lookup {
+        Lookup::Pending => {
+            let spinner = SPINNER[(spinner_frame as usize) % SPINNER.len()];
+            let pending = Paragraph::new(format!("{} Asking yt-dlp...", spinner))
+                .style(Style::default().fg(Color::Yellow))
+                .block(block)
+                .alignment(Alignment::Center);
+            f.render_widget(pending, area);
+            None
+        }
+        Lookup::Failed(e) => {
+            let failed = Paragraph::new(format!("Failed: {}", e))
+                .style(Style::default().fg(Color::Red))
+                .block(block)
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true });
+            f.render_widget(failed, area);
+            None
+        }
+        Lookup::Ready(items) if items.is_empty() => {
+            let empty = Paragraph::new("Nothing found. Esc to go back.")
+                .style(Style::default().fg(Color::DarkGray))
+                .block(block)
+                .alignment(Alignment::Center);
+            f.render_widget(empty, area);
+            None
+        }
+        Lookup::Ready(items) => {
+            let items: Vec<ListItem> = items.iter().map(|i| ListItem::new(label(i))).collect();
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+            let mut state = ListState::default().with_selected(Some(selected));
+            f.render_stateful_widget(list, area, &mut state);
+            Some(ListArea {
+                area,
+                offset: state.offset(),
+            })
+        }
+    }
+}
+
+fn ui(f: &mut Frame, app: &mut App, spinner_frame: u32) {
+    let chunks = ui_rows(f.area());
+
+    let title = Paragraph::new(title_text(app, chunks[0].width.saturating_sub(2) as usize))
+        .style(Style::default().fg(Color::Cyan))
+        .block(Block::bordered().border_type(BorderType::Rounded))
+        .alignment(Alignment::Center);
+    f.render_widget(title, chunks[0]);
+
+    match app.state {
+        AppState::InputPlaylistName => {
+            let name_input = Paragraph::new(app.playlist_name.as_str())
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Playlist Name"),
+                )
+                .style(Style::default().fg(Color::White));
+            f.render_widget(name_input, chunks[1]);
+
+            let mut hint = vec![match &app.input_error {
+                Some(err) => Line::styled(err.as_str(), Style::default().fg(Color::Red)),
+                None => Line::styled(
+                    "Enter playlist name, then press Enter or Tab (Ctrl+D for diagnostics)",
+                    Style::default().fg(Color::DarkGray),
+                ),
+            }];
+            if let Some(warning) = &app.startup_warning {
+                hint.push(Line::styled(
+                    warning.as_str(),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            f.render_widget(
+                Paragraph::new(hint)
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: true }),
+                chunks[2],
+            );
+
+            // What was typed on the URL screen, kept for when Tab goes back.
+            let url = Paragraph::new(app.url.as_str())
+                .block(Block::bordered().border_type(BorderType::Rounded).title(
+                    match (app.url.is_empty(), app.search_mode) {
+                        (true, _) => "",
+                        (false, true) => "Search YouTube",
+                        (false, false) => "YouTube URL",
+                    },
+                ))
+                .style(Style::default().fg(Color::DarkGray));
+            f.render_widget(url, chunks[3]);
+        }
+        AppState::InputUrl => {
+            let name_display = Paragraph::new(app.playlist_name.clone())
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Playlist Name"),
+                )
+                .style(Style::default().fg(Color::Green));
+            f.render_widget(name_display, chunks[1]);
+
+            let url_input = Paragraph::new(app.url.as_str())
+                .block(Block::bordered().border_type(BorderType::Rounded).title(
+                    if app.search_mode {
+                        "Search YouTube"
+                    } else {
+                        "YouTube URL"
+                    },
+                ))
+                .style(Style::default().fg(Color::White));
+            f.render_widget(url_input, chunks[2]);
+
+            let hint = Paragraph::new(match &app.input_error {
+                Some(err) => err.as_str(),
+                None if app.search_mode => {
+                    "Type a search query, then press Enter (Tab to enter URLs instead, Shift+Tab to change playlist)"
+                }
+                None => {
+                    "Enter YouTube URL (or several, separated by spaces), then press Enter (Tab to search, Shift+Tab to change playlist)"
+                }
+            })
+            .style(Style::default().fg(if app.input_error.is_some() {
+                Color::Red
+            } else {
+                Color::DarkGray
+            }))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+            f.render_widget(hint, chunks[3]);
+        }
+        AppState::SearchResults => {
+            let header = Paragraph::new(format!("Results for \"{}\"", app.url.trim()))
+                .style(Style::default().fg(Color::Cyan))
+                .block(Block::bordered().border_type(BorderType::Rounded))
+                .alignment(Alignment::Center);
+            f.render_widget(header, chunks[1]);
+
+            app.list_area = render_lookup(
+                f,
+                chunks[2],
+                "Search Results",
+                &app.search.lock().unwrap(),
+                app.search_selected,
+                spinner_frame,
+                |r| r.title.clone(),
+            );
+
+            let hint = Paragraph::new("Up/Down to select, Enter to download, Esc to go back")
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Center);
+            f.render_widget(hint, chunks[3]);
+        }
+        AppState::Queue => {
+            let header = Paragraph::new(format!(
+                "Download queue for {} ({} URLs)",
+                app.playlist_name,
+                app.url_queue.len()
+            ))
+            .style(Style::default().fg(Color::Cyan))
+            .block(Block::bordered().border_type(BorderType::Rounded))
+            .alignment(Alignment::Center);
+            f.render_widget(header, chunks[1]);
+
+            let items: Vec<ListItem> = app
+                .url_queue
+                .iter()
+                .enumerate()
+                .map(|(i, url)| ListItem::new(format!("{}. {}", i + 1, url)))
+                .collect();
+            let list = List::new(items)
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Queue"),
+                )
+                .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+            let mut state = ListState::default().with_selected(Some(app.queue_selected));
+            f.render_stateful_widget(list, chunks[2], &mut state);
+            app.list_area = Some(ListArea {
+                area: chunks[2],
+                offset: state.offset(),
+            });
+
+            let hint = Paragraph::new(if app.url_queue.is_empty() {
+                "The queue is empty. Esc to enter URLs again"
+            } else {
+                "Up/Down select, J/K or Shift+Up/Down move, d remove, Enter to download, Esc to go back"
+            })
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+            f.render_widget(hint, chunks[3]);
+        }
+        AppState::Formats => {
+            let header = Paragraph::new(if app.retry_with_format {
+                format!(
+                    "That format isn't available for {} — pick another to retry",
+                    app.single_url().unwrap_or_default()
+                )
+            } else {
+                format!("Formats for {}", app.single_url().unwrap_or_default())
+            })
+            .style(Style::default().fg(Color::Cyan))
+            .block(Block::bordered().border_type(BorderType::Rounded))
+            .alignment(Alignment::Center);
+            f.render_widget(header, chunks[1]);
+
+            app.list_area = render_lookup(
+                f,
+                chunks[2],
+                "Formats",
+                &app.formats.lock().unwrap(),
+                app.format_selected,
+                spinner_frame,
+                FormatOption::label,
+            );
+
+            let hint =
+                Paragraph::new("Up/Down to select, Enter to use this format, Esc to go back")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center);
+            f.render_widget(hint, chunks[3]);
+        }
+        AppState::Confirm => {
+            let name_display = Paragraph::new(app.playlist_name.clone())
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Playlist Name"),
+                )
+                .style(Style::default().fg(Color::Green));
+            f.render_widget(name_display, chunks[1]);
+
+            let mut summary = match app.single_url() {
+                Some(url) => vec![format!("URL: {}", url)],
+                None => {
+                    let mut lines = vec![format!("Queue: {} URLs", app.url_queue.len())];
+                    lines.extend(app.url_queue.iter().map(|u| format!("  {}", u)));
+                    lines
+                }
+            };
+            summary.extend([
+                format!(
+                    "Format: {}",
+                    app.format_label.as_deref().unwrap_or(&app.options.format)
+                ),
+                format!(
+                    "Rate limit: {}",
+                    app.options.limit_rate.as_deref().unwrap_or("none")
+                ),
+                format!(
+                    "Thumbnail: {}  Metadata: {}",
+                    match thumbnail_unsupported_ext(&app.options.format) {
+                        _ if !app.options.embed_thumbnail => "no".to_string(),
+                        Some(ext) => format!("no, {} files can't hold one", ext),
+                        None => "yes".to_string(),
+                    },
+                    if app.options.add_metadata {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                ),
+            ]);
+            if app.config.chapters_sidecar {
+                summary.push("Chapters: written to <title>.chapters.txt".to_string());
+            }
+            if app.config.split_chapters {
+                summary.push("Chapters: split into numbered tracks".to_string());
+            }
+            if app.config.max_quality_audio
+                && !app.options.video
+                && app.options.format == DownloadOptions::default().format
+            {
+                summary.push("Max quality audio: highest bitrate, in any format".to_string());
+            }
+            if app.options.video && app.config.remux_video {
+                summary.push(format!(
+                    "Container: {} (remuxed, not re-encoded)",
+                    app.config.video_container.ext()
+                ));
+            }
+            if app.config.staged_downloads {
+                summary
+                    .push("Staged: files move into the playlist only if all succeed".to_string());
+            }
+            if let Some(template) = &app.config.tag_comment {
+                if app.options.add_metadata {
+                    summary.push(format!("Comment tag: {}", template));
+                }
+            }
+            if let Some(genre) = &app.options.genre {
+                if app.options.add_metadata {
+                    summary.push(format!("Genre tag: {}", genre));
+                }
+            }
+            if let Some(cookies) = &app.config.cookies_file {
+                summary.push(format!("Cookies: {}", cookies.display()));
+            }
+            if let Some(image) = &app.cover_image {
+                summary.push(format!(
+                    "Cover art: {} (replaces thumbnail)",
+                    image.display()
+                ));
+            }
+            if let Some(n) = app.options.max_downloads {
+                summary.push(format!("Stopping after {} downloads", n));
+            }
+            if let Some(seconds) = app.options.max_duration {
+                summary.push(format!(
+                    "Skipping videos longer than {}",
+                    format_duration(seconds)
+                ));
+            }
+            if let Some(n) = app.options.latest_items {
+                if app.url_queue.iter().any(|url| is_playlist_url(url)) {
+                    summary.push(format!("Latest {} items", n));
+                }
+            }
+            if let Some(at) = app.scheduled_for {
+                summary.push(format!(
+                    "Starts at {} (in {})",
+                    at.format("%H:%M %a"),
+                    format_countdown((at - Local::now()).num_seconds())
+                ));
+            }
+            if let Some(lang) = &app.options.audio_language {
+                summary.push(format!(
+                    "Audio language: {} (default track if unavailable)",
+                    lang
+                ));
+            }
+            if app.low_bandwidth {
+                summary.push(String::new());
+                summary.push("[LOW BANDWIDTH]".to_string());
+            }
+            match app.single_url() {
+                Some(url) => {
+                    if let Some(date) = app.date_after_for(url) {
+                        let shown = NaiveDate::parse_from_str(&date, "%Y%m%d")
+                            .map(|d| d.format("%Y-%m-%d").to_string())
+                            .unwrap_or(date);
+                        summary.push(format!("Downloading videos since {}", shown));
+                    } else if app.only_new {
+                        summary.push("First run for this URL: downloading everything".to_string());
+                    }
+                    if let Some(item) = app.history.resume_point(url) {
+                        summary.push(if app.resume {
+                            format!("Resuming from item {} (e to start over)", item)
+                        } else {
+                            format!("Resume from item {}? e to resume", item)
+                        });
+                    }
+                }
+                None if app.only_new => {
+                    summary.push("Only videos since each URL's last run".to_string())
+                }
+                None => {}
+            }
+            if let Some(path) = app.playlist_config() {
+                summary.push(format!("Using this playlist's {}", path.display()));
+            }
+            match app.live_status() {
+                LiveStatus::Live => summary.push(if app.options.live_from_start {
+                    "Live stream: recording from the start (r to record from now)".to_string()
+                } else {
+                    "Live stream: recording from now (r to record from the start)".to_string()
+                }),
+                LiveStatus::Upcoming => summary.push(if app.options.wait_for_video {
+                    "Upcoming stream: waiting for it to start (w to not wait)".to_string()
+                } else {
+                    "Upcoming stream: not started yet (w to wait for it)".to_string()
+                }),
+                LiveStatus::NotLive if app.is_checking_live() => {
+                    summary.push("Checking for a live stream...".to_string())
+                }
+                LiveStatus::NotLive => {}
+            }
+            if app.config.login {
+                summary.push(format!(
+                    "Login: {} ({})",
+                    app.config.username.as_deref().unwrap_or("no username"),
+                    if app.password.is_some() {
+                        "password set"
+                    } else {
+                        "no password"
+                    }
+                ));
+            }
+            if let Some(url) = app.single_url() {
+                let music_dir = app.run_staging_dir().unwrap_or_else(|| app.playlist_dir());
+                let args = app.yt_dlp_args(&app.run_options(), &music_dir, url);
+                summary.push(String::new());
+                summary.push(display_command(&args));
+            }
+            let mut summary: Vec<Line> = summary.into_iter().map(Line::from).collect();
+            summary.insert(
+                0,
+                Line::styled(
+                    if app.options.video {
+                        "[ VIDEO ]  a for audio only"
+                    } else {
+                        "[ AUDIO ]  v for video"
+                    },
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            );
+            if app.single_url().is_some_and(is_playlist_url) {
+                summary.push(Line::from(match app.item_count() {
+                    ItemCount::Counting => "Playlist: counting items...".to_string(),
+                    ItemCount::Known(n) => format!("Playlist: {} items", format_count(n)),
+                    ItemCount::Unknown => "Playlist: item count unknown".to_string(),
+                }));
+            }
+            if let Some((url, found)) = &app.archived_in {
+                if Some(url.as_str()) == app.single_url() {
+                    summary.push(Line::styled(found.clone(), Style::default().fg(Color::Red)));
+                }
+            }
+            let template = format!("{}/{}", app.playlist_dir().display(), app.output_template());
+            if let Some(warning) =
+                path_length_warning(&estimate_output_path(&template), cfg!(windows))
+            {
+                summary.push(Line::styled(warning, Style::default().fg(Color::Yellow)));
+            }
+            match app.duplicates() {
+                Some(Lookup::Pending) => summary.push(Line::from("Checking for similar files...")),
+                Some(Lookup::Ready(similar)) if !similar.is_empty() => {
+                    summary.push(Line::styled(
+                        "Possible duplicates already in this playlist:",
+                        Style::default().fg(Color::Red),
+                    ));
+                    summary.extend(similar.into_iter().map(|name| {
+                        Line::styled(format!("  {}", name), Style::default().fg(Color::Red))
+                    }));
+                }
+                _ => {}
+            }
+            let playlist = app.single_url().is_some_and(is_playlist_url);
+            match app.size_estimate_entry() {
+                Some(None) => summary.push(Line::from("Estimating size...")),
+                Some(Some(estimate)) if estimate.items > estimate.unknown => {
+                    let mut line = format!("Estimated size: {}", format_bytes(estimate.bytes));
+                    if playlist {
+                        line = format!(
+                            "Estimated size: ~{} for {} items (approximate",
+                            format_bytes(estimate.bytes),
+                            format_count(estimate.items)
+                        );
+                        if estimate.unknown > 0 {
+                            line.push_str(&format!(", {} unknown", estimate.unknown));
+                        }
+                        line.push(')');
+                    }
+                    summary.push(Line::from(line));
+                }
+                Some(_) => summary.push(Line::from("Estimated size: unknown")),
+                None => {}
+            }
+            if let ItemCount::Known(n) = app.item_count() {
+                if app.is_large_playlist() {
+                    summary.push(Line::styled(
+                        format!(
+                            "This will download {} items — are you sure?",
+                            format_count(n)
+                        ),
+                        Style::default().fg(Color::Red),
+                    ));
+                }
+            }
+            let confirm = Paragraph::new(Text::from(summary))
+                .style(Style::default().fg(Color::White))
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Ready to Download"),
+                )
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true });
+            f.render_widget(confirm, chunks[2]);
+
+            let hint = Paragraph::new(if app.item_count() == ItemCount::Counting {
+                "Counting playlist items..."
+            } else if app.is_large_playlist() || app.has_duplicates() {
+                "Enter to download anyway, Esc to cancel"
+            } else if app.config.login {
+                "Enter to download, a/v audio/video, b low bandwidth, n only new, d since date, l language, f format, m max downloads, i latest items, t start time, c cover, k cookies, g genre, x max length, q queue, u username, p password, s settings, Esc to go back"
+            } else {
+                "Enter to download, a/v audio/video, b low bandwidth, n only new, d since date, l language, f format, m max downloads, i latest items, t start time, c cover, k cookies, g genre, x max length, q queue, s settings, Esc to go back"
+            })
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+            f.render_widget(hint, chunks[3]);
+        }
+        AppState::Scheduled => {
+            let name_display = Paragraph::new(app.playlist_name.clone())
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Playlist Name"),
+                )
+                .style(Style::default().fg(Color::Green));
+            f.render_widget(name_display, chunks[1]);
+
+            let mut lines = vec![Line::from(format!("{} URL(s) queued", app.url_queue.len()))];
+            if let Some(at) = app.scheduled_for {
+                lines.push(Line::from(format!(
+                    "Starting at {}",
+                    at.format("%H:%M on %a %-d %b")
+                )));
+                lines.push(Line::styled(
+                    format!("in {}", format_countdown((at - Local::now()).num_seconds())),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            let countdown = Paragraph::new(lines)
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Scheduled"),
+                )
+                .alignment(Alignment::Center);
+            f.render_widget(countdown, chunks[2]);
+
+            let hint =
+                Paragraph::new("Keep ytd open until then. Esc to cancel the schedule, q to quit")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center);
+            f.render_widget(hint, chunks[3]);
+        }
+        AppState::Watching => {
+            let name_display = Paragraph::new(app.playlist_name.clone())
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Playlist Name"),
+                )
+                .style(Style::default().fg(Color::Green));
+            f.render_widget(name_display, chunks[1]);
+
+            let mut lines = vec![
+                Line::from(format!("Watching {}", app.url_queue.join(" "))),
+                Line::styled(
+                    format!("Next check in {}", format_clock(app.watch_countdown())),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Line::from(""),
+            ];
+            if let Some(watch) = &app.watch {
+                // Newest last, keeping as many as fit.
+                let room = (chunks[2].height as usize).saturating_sub(2 + lines.len());
+                let skip = watch.results.len().saturating_sub(room);
+                lines.extend(watch.results.iter().skip(skip).cloned());
+            }
+            let body = Paragraph::new(lines).block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .title("Watching"),
+            );
+            f.render_widget(body, chunks[2]);
+
+            let hint =
+                Paragraph::new("Keep ytd open to keep checking. Esc to stop watching, q to quit")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center);
+            f.render_widget(hint, chunks[3]);
+        }
+        AppState::Diagnostics => {
+            let (text, status) = match &app.diagnostics {
+                Some((d, status)) => (d.to_text(), status.clone()),
+                None => (String::new(), None),
+            };
+            let header = Paragraph::new(match status {
+                _ if app.is_updating_yt_dlp() => Line::from("Updating yt-dlp..."),
+                Some(Ok(message)) => Line::styled(message, Style::default().fg(Color::Green)),
+                Some(Err(e)) => Line::styled(e, Style::default().fg(Color::Red)),
+                None => Line::from("Include this when reporting a problem"),
+            })
+            .style(Style::default().fg(Color::Cyan))
+            .block(Block::bordered().border_type(BorderType::Rounded))
+            .alignment(Alignment::Center);
+            f.render_widget(header, chunks[1]);
+
+            let body = Paragraph::new(text)
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Diagnostics"),
+                )
+                .wrap(Wrap { trim: false });
+            f.render_widget(body, chunks[2]);
+
+            let hint = Paragraph::new(
+                "c to copy, u to update yt-dlp, x to clear yt-dlp's cache, Esc to go back",
+            )
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center);
+            f.render_widget(hint, chunks[3]);
         }
+        AppState::Setup(step) => {
+            let (number, question) = match step {
+                SetupStep::MusicDir => (1, "Where should playlists be saved?"),
+                SetupStep::Format => (2, "Which audio format do you want?"),
+                SetupStep::Tools => (3, "Checking for the tools ytd needs"),
+            };
+            let header = Paragraph::new(format!("Setup {}/3: {}", number, question))
+                .style(Style::default().fg(Color::Cyan))
+                .block(Block::bordered().border_type(BorderType::Rounded))
+                .alignment(Alignment::Center);
+            f.render_widget(header, chunks[1]);
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match app.state {
-                    AppState::InputPlaylistName => {
-                        if key.code == KeyCode::Enter {
-                            if !app.playlist_name.is_empty() {
-                                app.state = AppState::InputUrl;
-                            }
-                        } else if let KeyCode::Char(c) = key.code {
-                            app.playlist_name.push(c);
-                        } else if key.code == KeyCode::Backspace {
-                            app.playlist_name.pop();
-                        } else if key.code == KeyCode::Esc {
-                            break;
-                        }
-                    }
-                    AppState::InputUrl => {
-                        if key.code == KeyCode::Enter {
-                            if !app.url.is_empty() {
-                                app.state = AppState::Downloading;
-                                app.start_download();
-                            }
-                        } else if let KeyCode::Char(c) = key.code {
-                            app.url.push(c);
-                        } else if key.code == KeyCode::Backspace {
-                            app.url.pop();
-                        } else if key.code == KeyCode::Esc {
-                            break;
-                        }
-                    }
-                    AppState::Downloading => {
-                        if app.check_download() {
-                            // Download finished, state updated in check_download
-                        }
-                        if key.code == KeyCode::Esc {
-                            break;
-                        }
-                    }
-                    AppState::Done | AppState::Error => {
-                        if key.code == KeyCode::Enter {
-                            break;
+            match step {
+                SetupStep::MusicDir => {
+                    let input = Paragraph::new(app.field_input.as_str())
+                        .block(
+                            Block::bordered()
+                                .border_type(BorderType::Rounded)
+                                .title("Music Folder"),
+                        )
+                        .style(Style::default().fg(Color::White));
+                    f.render_widget(input, chunks[2]);
+                }
+                SetupStep::Format => {
+                    let items: Vec<ListItem> = SETUP_FORMATS
+                        .iter()
+                        .map(|(label, _, _)| ListItem::new(*label))
+                        .collect();
+                    let list = List::new(items)
+                        .block(
+                            Block::bordered()
+                                .border_type(BorderType::Rounded)
+                                .title("Format"),
+                        )
+                        .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+                    let mut state = ListState::default().with_selected(Some(app.setup_selected));
+                    f.render_stateful_widget(list, chunks[2], &mut state);
+                    app.list_area = Some(ListArea {
+                        area: chunks[2],
+                        offset: state.offset(),
+                    });
+                }
+                SetupStep::Tools => {
+                    let (yt_dlp, ffmpeg) = app.setup_tools.unwrap_or_default();
+                    let line = |found: bool, name: &str, why: &str| {
+                        if found {
+                            Line::styled(
+                                format!("✓ {} found", name),
+                                Style::default().fg(Color::Green),
+                            )
+                        } else {
+                            Line::styled(
+                                format!("✗ {} not found: {}", name, why),
+                                Style::default().fg(Color::Red),
+                            )
                         }
-                    }
+                    };
+                    let tools = Paragraph::new(vec![
+                        line(yt_dlp, "yt-dlp", "install it to download anything"),
+                        line(ffmpeg, "ffmpeg", "needed for audio conversion and metadata"),
+                    ])
+                    .block(
+                        Block::bordered()
+                            .border_type(BorderType::Rounded)
+                            .title("Tools"),
+                    )
+                    .wrap(Wrap { trim: true });
+                    f.render_widget(tools, chunks[2]);
                 }
             }
-        }
-    }
-
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    Ok(())
-}
-
-fn ui(f: &mut Frame, app: &mut App, spinner_frame: u32) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),
-            Constraint::Length(3),
-            Constraint::Min(1),
-            Constraint::Length(3),
-        ])
-        .split(f.area());
 
-    let title = Paragraph::new("YouTube Downloader TUI")
-        .style(Style::default().fg(Color::Cyan))
-        .block(Block::bordered().border_type(BorderType::Rounded))
-        .alignment(Alignment::Center);
-    f.render_widget(title, chunks[0]);
+            let hint = match &app.input_error {
+                Some(err) => Paragraph::new(err.as_str()).style(Style::default().fg(Color::Red)),
+                None => Paragraph::new(match step {
+                    SetupStep::MusicDir => "Enter to continue, Esc to skip setup",
+                    SetupStep::Format => "Up/Down to select, Enter to continue, Esc to skip setup",
+                    SetupStep::Tools => "Enter to save and start",
+                })
+                .style(Style::default().fg(Color::DarkGray)),
+            };
+            f.render_widget(hint.alignment(Alignment::Center), chunks[3]);
+        }
+        AppState::Settings => {
+            let header = Paragraph::new(match &app.settings_error {
+                Some(err) => Line::styled(err.clone(), Style::default().fg(Color::Red)),
+                None => Line::from("Settings are saved to the config file as you change them"),
+            })
+            .style(Style::default().fg(Color::Cyan))
+            .block(Block::bordered().border_type(BorderType::Rounded))
+            .alignment(Alignment::Center);
+            f.render_widget(header, chunks[1]);
 
-    match app.state {
-        AppState::InputPlaylistName => {
-            let name_input = Paragraph::new(app.playlist_name.as_str())
+            let items: Vec<ListItem> = SETTINGS
+                .iter()
+                .map(|s| {
+                    let mark = if s.get(&app.config) { "[x]" } else { "[ ]" };
+                    ListItem::new(format!("{} {}", mark, s.label()))
+                })
+                .collect();
+            let list = List::new(items)
                 .block(
                     Block::bordered()
                         .border_type(BorderType::Rounded)
-                        .title("Playlist Name"),
+                        .title("Settings"),
                 )
-                .style(Style::default().fg(Color::White));
-            f.render_widget(name_input, chunks[1]);
+                .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+            let mut state = ListState::default().with_selected(Some(app.settings_selected));
+            f.render_stateful_widget(list, chunks[2], &mut state);
+            app.list_area = Some(ListArea {
+                area: chunks[2],
+                offset: state.offset(),
+            });
 
-            let hint = Paragraph::new("Enter playlist name, then press Enter")
+            let hint = Paragraph::new("Up/Down to select, Space to toggle, Esc to go back")
                 .style(Style::default().fg(Color::DarkGray))
                 .alignment(Alignment::Center);
-            f.render_widget(hint, chunks[2]);
-
-            f.render_widget(
-                Paragraph::new("").block(Block::bordered().border_type(BorderType::Rounded)),
-                chunks[3],
-            );
+            f.render_widget(hint, chunks[3]);
         }
-        AppState::InputUrl => {
+        AppState::EditField(field) => {
             let name_display = Paragraph::new(app.playlist_name.clone())
                 .block(
                     Block::bordered()
@@ -307,19 +5529,26 @@ fn ui(f: &mut Frame, app: &mut App, spinner_frame: u32) {
                 .style(Style::default().fg(Color::Green));
             f.render_widget(name_display, chunks[1]);
 
-            let url_input = Paragraph::new(app.url.as_str())
+            let shown = if field.is_masked() {
+                "*".repeat(app.field_input.chars().count())
+            } else {
+                app.field_input.clone()
+            };
+            let input = Paragraph::new(shown)
                 .block(
                     Block::bordered()
                         .border_type(BorderType::Rounded)
-                        .title("YouTube URL"),
+                        .title(field.label()),
                 )
                 .style(Style::default().fg(Color::White));
-            f.render_widget(url_input, chunks[2]);
+            f.render_widget(input, chunks[2]);
 
-            let hint = Paragraph::new("Enter YouTube URL, then press Enter to download")
-                .style(Style::default().fg(Color::DarkGray))
-                .alignment(Alignment::Center);
-            f.render_widget(hint, chunks[3]);
+            let hint = match &app.input_error {
+                Some(err) => Paragraph::new(err.as_str()).style(Style::default().fg(Color::Red)),
+                None => Paragraph::new("Enter to save (empty clears), Esc to cancel")
+                    .style(Style::default().fg(Color::DarkGray)),
+            };
+            f.render_widget(hint.alignment(Alignment::Center), chunks[3]);
         }
         AppState::Downloading => {
             let name_display = Paragraph::new(app.playlist_name.clone())
@@ -331,75 +5560,401 @@ fn ui(f: &mut Frame, app: &mut App, spinner_frame: u32) {
                 .style(Style::default().fg(Color::Green));
             f.render_widget(name_display, chunks[1]);
 
-            let output = {
-                let out = app.download_output.lock().unwrap();
-                out.clone()
+            let (output, problem) = {
+                let log = app.download_output.lock().unwrap();
+                let problem = log
+                    .latest_problem()
+                    .map(|(severity, line)| (severity, line.to_string()));
+                (log.text(), problem)
             };
-            let output_display: String = output
-                .lines()
-                .rev()
-                .take(5)
-                .collect::<Vec<_>>()
-                .into_iter()
-                .rev()
-                .collect::<Vec<_>>()
-                .join("\n");
 
-            let spinners = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-            let spinner = spinners[(spinner_frame as usize) % spinners.len()];
+            let spinner = SPINNER[(spinner_frame as usize) % SPINNER.len()];
+            let total = app.total_items.load(Ordering::SeqCst);
+            let mut item_status = if app.url_queue.len() > 1 {
+                format!(
+                    " (URL {}/{})",
+                    app.queue_position.load(Ordering::SeqCst),
+                    app.url_queue.len()
+                )
+            } else {
+                String::new()
+            };
+            if total > 1 {
+                item_status.push_str(&format!(
+                    " (item {}/{})",
+                    app.current_item.load(Ordering::SeqCst),
+                    total
+                ));
+            }
+            // Counted as yt-dlp starts writing each file, so a video merged
+            // from separate streams counts once per stream.
+            let started = app.files_started.load(Ordering::SeqCst);
+            if started > 0 {
+                item_status.push_str(&format!(
+                    " · {} file{} started",
+                    started,
+                    if started == 1 { "" } else { "s" }
+                ));
+            }
+
+            let block =
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .title(if app.show_full_log {
+                        "Full log (Up/Down/PgUp/PgDn to scroll)"
+                    } else if app.low_bandwidth {
+                        "Progress (low bandwidth)"
+                    } else {
+                        "Progress"
+                    });
 
-            let downloading =
-                Paragraph::new(format!("{} Downloading...\n{}", spinner, output_display))
-                    .style(Style::default().fg(Color::Yellow))
-                    .block(
-                        Block::bordered()
-                            .border_type(BorderType::Rounded)
-                            .title("Progress"),
+            if app.show_full_log {
+                let height = chunks[2].height.saturating_sub(2) as usize;
+                let window = log_window(&output, height, app.log_scroll);
+                let log = Paragraph::new(log_lines(&window, Color::White)).block(block);
+                f.render_widget(log, chunks[2]);
+            } else {
+                let inner = block.inner(chunks[2]);
+                f.render_widget(block, chunks[2]);
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Length(1),
+                        Constraint::Length(1),
+                        Constraint::Length(problem.is_some() as u16),
+                        Constraint::Min(0),
+                    ])
+                    .split(inner);
+
+                let (status, color) = if app.quality_prompt() == QualityPrompt::Asking {
+                    (
+                        "Connection is slow — switch to lower quality? (y/n)".to_string(),
+                        Color::Red,
                     )
-                    .alignment(Alignment::Center);
-            f.render_widget(downloading, chunks[2]);
+                } else if app.is_paused() {
+                    (format!("Paused{} (p to resume)", item_status), Color::Cyan)
+                } else if let Some(wait) = app.rate_limit_wait() {
+                    (
+                        format!("Rate limited, waiting {}s...{}", wait, item_status),
+                        Color::Red,
+                    )
+                } else if let Some(phase) = app.post_processing_phase() {
+                    (
+                        format!("{} {}{}", spinner, phase, item_status),
+                        Color::Yellow,
+                    )
+                } else {
+                    (
+                        format!("{} Downloading...{}", spinner, item_status),
+                        Color::Yellow,
+                    )
+                };
+                f.render_widget(
+                    Paragraph::new(status)
+                        .style(Style::default().fg(color))
+                        .alignment(Alignment::Center),
+                    rows[0],
+                );
+
+                let progress = app.progress.lock().unwrap().clone();
+                if let Some(percent) = progress.percent {
+                    let mut label = format!("{:.1}%", percent);
+                    if let Some(speed) = &progress.speed {
+                        label.push_str(&format!(" at {}", speed));
+                    }
+                    if let Some(eta) = &progress.eta {
+                        label.push_str(&format!(", ETA {}", eta));
+                    }
+                    let gauge = Gauge::default()
+                        .gauge_style(Style::default().fg(Color::Yellow))
+                        .ratio((percent / 100.0).clamp(0.0, 1.0))
+                        .label(label);
+                    f.render_widget(gauge, rows[1]);
+                } else if let Some(elapsed) = &progress.elapsed {
+                    let mut label = format!("● Recorded {}", elapsed);
+                    if let Some(size) = &progress.downloaded {
+                        label.push_str(&format!(" ({})", size));
+                    }
+                    label.push_str(" · s to stop recording");
+                    f.render_widget(
+                        Paragraph::new(label)
+                            .style(Style::default().fg(Color::Red))
+                            .alignment(Alignment::Center),
+                        rows[1],
+                    );
+                }
+
+                // The latest error stays in view after the tail moves past it.
+                if let Some((severity, line)) = &problem {
+                    let color = match severity {
+                        Severity::Error => Color::Red,
+                        Severity::Warning => Color::Yellow,
+                    };
+                    f.render_widget(
+                        Paragraph::new(strip_timestamp(line).to_string())
+                            .style(Style::default().fg(color).add_modifier(Modifier::BOLD))
+                            .alignment(Alignment::Center),
+                        rows[2],
+                    );
+                }
+
+                let tail = last_n_lines(&output, rows[3].height as usize);
+                f.render_widget(
+                    Paragraph::new(log_lines(&tail, Color::DarkGray)).alignment(Alignment::Center),
+                    rows[3],
+                );
+            }
 
             f.render_widget(
-                Paragraph::new("Press Esc to cancel")
-                    .style(Style::default().fg(Color::DarkGray))
-                    .alignment(Alignment::Center),
+                Paragraph::new(if total > 1 {
+                    "Esc to cancel and edit the URL, q to quit, x to cancel and clean up, p to pause, k to skip this item, l to toggle the full log, v to page through it"
+                } else {
+                    "Esc to cancel and edit the URL, q to quit, x to cancel and clean up, p to pause, l to toggle the full log, v to page through it"
+                })
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true }),
                 chunks[3],
             );
         }
         AppState::Done => {
             let count = app.files_downloaded.len();
-            let done = Paragraph::new(format!(
-                "Download Complete! ({} file{})",
-                count,
-                if count == 1 { "" } else { "s" }
-            ))
+            let skipped = app.skipped.load(Ordering::SeqCst);
+            let done = Paragraph::new(if app.nothing_new() {
+                format!("All items already downloaded ({} skipped)", skipped)
+            } else {
+                format!(
+                    "Download Complete! ({} file{})",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                )
+            })
             .style(Style::default().fg(Color::Green))
             .block(Block::bordered().border_type(BorderType::Rounded))
             .alignment(Alignment::Center);
             f.render_widget(done, chunks[1]);
 
-            let path = format!("~/Music/{}", app.playlist_name);
-            let path_msg = Paragraph::new(format!("Saved to {}", path))
+            let mut lines = vec![Line::from(format!(
+                "Saved to {}",
+                app.playlist_dir().display()
+            ))];
+            if let Some(hook) = app.hook_status.lock().unwrap().clone() {
+                lines.push(hook);
+            }
+            if let Some((embedded, failed)) = *app.cover_result.lock().unwrap() {
+                lines.push(if failed == 0 {
+                    Line::from(format!("Cover art embedded in {} files", embedded))
+                } else {
+                    Line::styled(
+                        format!(
+                            "Cover art embedded in {} files, failed for {}",
+                            embedded, failed
+                        ),
+                        Style::default().fg(Color::Red),
+                    )
+                });
+            }
+            if !app.staging_kept.is_empty() {
+                lines.push(Line::styled(
+                    format!(
+                        "{} file{} already in the playlist folder not replaced; the new download{} left in {}",
+                        app.staging_kept.len(),
+                        if app.staging_kept.len() == 1 { "" } else { "s" },
+                        if app.staging_kept.len() == 1 { " is" } else { "s are" },
+                        app.download_dir().display()
+                    ),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            match &app.organize_result {
+                Some(Ok((moved, folders))) => {
+                    lines.push(Line::from(format!(
+                        "Sorted into {} by tags: {} files moved",
+                        app.config.tag_folders_template, moved
+                    )));
+                    for folder in folders.iter().take(ORGANIZED_FOLDERS_SHOWN) {
+                        lines.push(Line::styled(
+                            format!("{}/", folder),
+                            Style::default().fg(Color::Cyan),
+                        ));
+                    }
+                    if folders.len() > ORGANIZED_FOLDERS_SHOWN {
+                        lines.push(Line::styled(
+                            format!(
+                                "and {} more folders",
+                                folders.len() - ORGANIZED_FOLDERS_SHOWN
+                            ),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                }
+                Some(Err(e)) => {
+                    lines.push(Line::styled(e.clone(), Style::default().fg(Color::Red)))
+                }
+                None => {}
+            }
+            if let Some(line) = resolved_audio_summary(&app.resolved_audio) {
+                lines.push(Line::from(line));
+            }
+            if let Some((made, failed)) = *app.preview_result.lock().unwrap() {
+                let line = format!(
+                    "Previews ({}, .preview.opus) made for {} files",
+                    app.config.preview_bitrate, made
+                );
+                lines.push(if failed == 0 {
+                    Line::from(line)
+                } else {
+                    Line::styled(
+                        format!("{}, failed for {}", line, failed),
+                        Style::default().fg(Color::Red),
+                    )
+                });
+            }
+            #[cfg(feature = "lyrics")]
+            if let Some((embedded, looked_up)) = *app.lyrics_result.lock().unwrap() {
+                lines.push(Line::from(format!(
+                    "Lyrics found for {} of {} tracks",
+                    embedded, looked_up
+                )));
+            }
+            lines.extend(app.queue_summary());
+            let too_long = app.too_long.load(Ordering::SeqCst);
+            if too_long > 0 {
+                lines.push(Line::styled(
+                    format!(
+                        "{} video{} skipped for being longer than {}",
+                        too_long,
+                        if too_long == 1 { "" } else { "s" },
+                        format_duration(app.options.max_duration.unwrap_or_default())
+                    ),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            let fragments = app.fragments_skipped.load(Ordering::SeqCst);
+            if fragments > 0 {
+                lines.push(Line::styled(
+                    format!(
+                        "{} fragment{} could not be downloaded and were skipped; files may be incomplete",
+                        fragments,
+                        if fragments == 1 { "" } else { "s" }
+                    ),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            if app.audio_fallback_used {
+                lines.push(Line::styled(
+                    "Video blocked, downloaded audio only",
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            if app.confirm_undo {
+                let count = app.created_files().len();
+                lines.push(Line::styled(
+                    format!(
+                        "Delete the {} file{} this download created? y to delete, any other key to keep",
+                        count,
+                        if count == 1 { "" } else { "s" }
+                    ),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            match &app.done_status {
+                Some(Ok(status)) => lines.push(Line::from(status.clone())),
+                Some(Err(e)) => {
+                    lines.push(Line::styled(e.clone(), Style::default().fg(Color::Red)))
+                }
+                None => {}
+            }
+            let areas = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(lines.len() as u16), Constraint::Min(0)])
+                .split(chunks[2]);
+            let path_msg = Paragraph::new(Text::from(lines))
                 .style(Style::default().fg(Color::White))
                 .alignment(Alignment::Center);
-            f.render_widget(path_msg, chunks[2]);
+            f.render_widget(path_msg, areas[0]);
 
             if !app.files_downloaded.is_empty() {
-                let files = app.files_downloaded.join("\n");
-                let file_list = Paragraph::new(files)
-                    .style(Style::default().fg(Color::DarkGray))
+                let items: Vec<ListItem> = app
+                    .files_downloaded
+                    .iter()
+                    .map(|name| ListItem::new(name.as_str()))
+                    .collect();
+                let list = List::new(items)
                     .block(
                         Block::bordered()
                             .border_type(BorderType::Rounded)
                             .title("Downloaded"),
                     )
-                    .alignment(Alignment::Center);
-                f.render_widget(file_list, chunks[3]);
+                    .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+                f.render_stateful_widget(list, areas[1], &mut app.done_list_state);
+                app.list_area = Some(ListArea {
+                    area: areas[1],
+                    offset: app.done_list_state.offset(),
+                });
+            }
+
+            let exit_hint = Paragraph::new(if app.files_downloaded.is_empty() {
+                "R to run the same download again, Enter to exit"
             } else {
-                let exit_hint = Paragraph::new("Press Enter to exit")
-                    .style(Style::default().fg(Color::DarkGray))
+                "Up/Down/PgUp/PgDn to scroll, p to play the selected file, P to play all, e to export an .m3u8, r to rename, D to undo this download, R to run it again, Enter to exit"
+            })
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center);
+            f.render_widget(exit_hint, chunks[3]);
+        }
+        AppState::Rename => {
+            let header =
+                Paragraph::new(format!("Rename files in {}", app.playlist_dir().display()))
+                    .style(Style::default().fg(Color::Cyan))
+                    .block(Block::bordered().border_type(BorderType::Rounded))
                     .alignment(Alignment::Center);
-                f.render_widget(exit_hint, chunks[3]);
+            f.render_widget(header, chunks[1]);
+
+            let items: Vec<ListItem> = app
+                .files_downloaded
+                .iter()
+                .map(|name| ListItem::new(name.as_str()))
+                .collect();
+            let list = List::new(items)
+                .block(
+                    Block::bordered()
+                        .border_type(BorderType::Rounded)
+                        .title("Files"),
+                )
+                .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+            let mut state = ListState::default().with_selected(Some(app.rename_selected));
+            f.render_stateful_widget(list, chunks[2], &mut state);
+            app.list_area = Some(ListArea {
+                area: chunks[2],
+                offset: state.offset(),
+            });
+
+            match &app.rename_input {
+                Some(input) => {
+                    let title = match &app.input_error {
+                        Some(err) => format!("New name: {}", err),
+                        None => "New name (Enter to apply, Esc to cancel)".to_string(),
+                    };
+                    let edit = Paragraph::new(input.as_str())
+                        .block(
+                            Block::bordered()
+                                .border_type(BorderType::Rounded)
+                                .title(title),
+                        )
+                        .style(Style::default().fg(if app.input_error.is_some() {
+                            Color::Red
+                        } else {
+                            Color::White
+                        }));
+                    f.render_widget(edit, chunks[3]);
+                }
+                None => {
+                    let hint = Paragraph::new("Up/Down to select, Enter to rename, Esc when done")
+                        .style(Style::default().fg(Color::DarkGray))
+                        .alignment(Alignment::Center);
+                    f.render_widget(hint, chunks[3]);
+                }
             }
         }
         AppState::Error => {
@@ -409,16 +5964,538 @@ fn ui(f: &mut Frame, app: &mut App, spinner_frame: u32) {
                 .alignment(Alignment::Center);
             f.render_widget(error, chunks[1]);
 
-            let error_msg = Paragraph::new(app.error_message.clone())
+            let mut lines = vec![Line::from(app.error_message.clone())];
+            lines.extend(app.queue_summary());
+            let error_msg = Paragraph::new(Text::from(lines))
                 .style(Style::default().fg(Color::Red))
                 .block(Block::bordered().border_type(BorderType::Rounded))
                 .alignment(Alignment::Center);
             f.render_widget(error_msg, chunks[2]);
 
-            let exit_hint = Paragraph::new("Press Enter to exit")
-                .style(Style::default().fg(Color::DarkGray))
-                .alignment(Alignment::Center);
+            let exit_hint = Paragraph::new(if app.failure == Some(FailureKind::StaleCache) {
+                "C to clear yt-dlp's cache, R to run the same download again, Enter to exit"
+            } else if app.failure == Some(FailureKind::YtDlpCrashed) {
+                "D for diagnostics and updating yt-dlp, R to run the same download again, Enter to exit"
+            } else if app.last_run.is_some() {
+                "R to run the same download again, Enter to exit"
+            } else {
+                "Press Enter to exit"
+            })
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center);
             f.render_widget(exit_hint, chunks[3]);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_app() -> App {
+        App::new(
+            Config::default(),
+            std::env::temp_dir().join("ytd-test-music"),
+            History::default(),
+        )
+    }
+
+    #[test]
+    fn picking_a_video_format_downloads_video() {
+        let mut app = test_app();
+        let video = FormatOption {
+            id: "137".to_string(),
+            ext: "mp4".to_string(),
+            height: Some(1080),
+            bitrate: Some(3041),
+            size: None,
+            video_only: true,
+        };
+        *app.formats.lock().unwrap() = Lookup::Ready(vec![video]);
+        app.choose_format();
+        assert!(app.options.video);
+        assert_eq!(app.options.format, "137+bestaudio");
+        let args = build_yt_dlp_args(&app.options, "%(title)s.%(ext)s", "https://youtu.be/x");
+        assert!(!args.iter().any(|a| a == "--extract-audio"));
+    }
+
+    #[test]
+    fn previewed_command_has_the_config_applied() {
+        let mut app = test_app();
+        app.config.nfo_sidecar = true;
+        app.config.embed_info_json = true;
+        app.config.mtime = true;
+        let url = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+        let args = app.yt_dlp_args(&app.run_options(), &app.playlist_dir(), url);
+        for flag in ["--write-info-json", "--embed-info-json", "--mtime"] {
+            assert!(args.iter().any(|a| a == flag), "{} missing", flag);
+        }
+    }
+
+    #[test]
+    fn invalid_audio_language_in_the_config_is_ignored() {
+        let config = Config {
+            audio_language: Some("en]/b[".to_string()),
+            ..Config::default()
+        };
+        let app = App::new(config, std::env::temp_dir(), History::default());
+        assert_eq!(app.options.audio_language, None);
+        let config = Config {
+            audio_language: Some("pt-BR".to_string()),
+            ..Config::default()
+        };
+        let app = App::new(config, std::env::temp_dir(), History::default());
+        assert_eq!(app.options.audio_language.as_deref(), Some("pt-BR"));
+    }
+
+    #[test]
+    fn title_shows_playlist_progress_and_fits_the_width() {
+        let mut app = test_app();
+        assert_eq!(title_text(&app, 80), "YouTube Downloader TUI");
+
+        app.state = AppState::Downloading;
+        app.playlist_name = "My Playlist".to_string();
+        app.total_items.store(12, Ordering::SeqCst);
+        app.current_item.store(3, Ordering::SeqCst);
+        app.progress.lock().unwrap().percent = Some(50.0);
+        assert_eq!(title_text(&app, 80), "ytd — Item 3/12 — 21% — My Playlist");
+        assert_eq!(title_text(&app, 30), "ytd — Item 3/12 — 21% — My Pl…");
+        assert_eq!(title_text(&app, 20), "ytd — Item 3/12 — 21%");
+    }
+
+    #[test]
+    fn reset_download_state_forgets_a_finished_download() {
+        let mut app = test_app();
+        app.download_done.store(true, Ordering::SeqCst);
+        app.download_success.store(true, Ordering::SeqCst);
+        app.files_started.store(2, Ordering::SeqCst);
+        app.files_downloaded.push("a.m4a".to_string());
+        app.download_output
+            .lock()
+            .unwrap()
+            .push("[download] 100% of 1MiB");
+        app.progress.lock().unwrap().percent = Some(100.0);
+
+        app.reset_download_state();
+
+        assert!(!app.check_download());
+        assert!(app.state == AppState::InputPlaylistName);
+        assert!(!app.download_success.load(Ordering::SeqCst));
+        assert_eq!(app.files_started.load(Ordering::SeqCst), 0);
+        assert!(app.files_downloaded.is_empty());
+        assert_eq!(app.download_output.lock().unwrap().text(), "");
+        assert_eq!(app.progress.lock().unwrap().percent, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sigterm_stops_and_reaps_the_child() {
+        let mut app = test_app();
+        app.watch_sigterm().unwrap();
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id().to_string();
+        *app.current_child.lock().unwrap() = Some(child);
+
+        signal_hook::low_level::raise(SIGTERM).unwrap();
+        assert!(app.terminate_requested.load(Ordering::SeqCst));
+        app.shut_down();
+
+        assert!(app.current_child.lock().unwrap().is_none());
+        // A zombie would still answer signal 0; a reaped child doesn't.
+        let alive = Command::new("kill")
+            .args(["-0", &pid])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!alive.success());
+    }
+
+    #[test]
+    fn full_log_holds_on_a_new_error_until_scrolled() {
+        let mut app = test_app();
+        app.show_full_log = true;
+        let push = |app: &App, line: &str| app.download_output.lock().unwrap().push(line);
+        push(&app, "[download] Destination: a.m4a");
+        push(&app, "ERROR: [youtube] abc: Video unavailable");
+        app.follow_log_errors();
+        assert_eq!(app.log_scroll, 0);
+
+        push(&app, "[youtube] Extracting URL: def");
+        push(&app, "[download] Destination: b.m4a");
+        app.follow_log_errors();
+        assert_eq!(app.log_scroll, 2);
+
+        // Scrolling lets go, and a later error doesn't take the view back.
+        app.log_pin = None;
+        app.log_scroll = 5;
+        push(&app, "ERROR: [youtube] def: Private video");
+        app.follow_log_errors();
+        assert_eq!(app.log_scroll, 5);
+        assert_eq!(app.log_pin, None);
+    }
+
+    #[test]
+    fn reset_download_state_clears_cancel_and_pause() {
+        let mut app = test_app();
+        app.cancel_requested.store(true, Ordering::SeqCst);
+        app.skip_requested.store(true, Ordering::SeqCst);
+        app.paused.store(true, Ordering::SeqCst);
+
+        app.reset_download_state();
+
+        assert!(!app.cancel_requested.load(Ordering::SeqCst));
+        assert!(!app.skip_requested.load(Ordering::SeqCst));
+        assert!(!app.is_paused());
+    }
+
+    #[test]
+    fn split_chapters_writes_tracks_next_to_the_whole_video() {
+        let opts = DownloadOptions {
+            split_chapters: true,
+            ..DownloadOptions::default()
+        };
+        let args = build_yt_dlp_args(&opts, "/music/Mixes/%(title)s.%(ext)s", "https://x");
+        let chapter = args.iter().position(|a| a.starts_with("chapter:")).unwrap();
+        assert_eq!(
+            args[chapter],
+            "chapter:/music/Mixes/%(title)s - %(section_number)02d - %(section_title)s.%(ext)s"
+        );
+        assert!(args.contains(&"--split-chapters".to_string()));
+        assert_eq!(args[args.len() - 2], "/music/Mixes/%(title)s.%(ext)s");
+    }
+
+    #[test]
+    fn thumbnail_is_dropped_only_for_containers_that_cannot_hold_one() {
+        assert_eq!(thumbnail_unsupported_ext("ba[ext=wav]/ba"), Some("wav"));
+        assert_eq!(thumbnail_unsupported_ext("ba[ext=m4a]"), None);
+        assert_eq!(thumbnail_unsupported_ext("ba[ext=m4a]/ba[ext=wav]"), None);
+        assert_eq!(thumbnail_unsupported_ext("251"), None);
+    }
+
+    #[test]
+    fn playlist_start_only_applies_to_playlists() {
+        let opts = DownloadOptions {
+            playlist_start: Some(30),
+            ..DownloadOptions::default()
+        };
+        let playlist = "https://www.youtube.com/playlist?list=PL123";
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", playlist);
+        let at = args.iter().position(|a| a == "--playlist-start").unwrap();
+        assert_eq!(args[at + 1], "30");
+
+        let video = "https://www.youtube.com/watch?v=abc";
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", video);
+        assert!(!args.contains(&"--playlist-start".to_string()));
+    }
+
+    #[test]
+    fn latest_items_pick_the_per_item_runs() {
+        let opts = DownloadOptions {
+            latest_items: Some(2),
+            ..DownloadOptions::default()
+        };
+        let item = |run: &[String]| {
+            assert_eq!(run.iter().filter(|a| *a == "--playlist-items").count(), 1);
+            let at = run.iter().position(|a| a == "--playlist-items").unwrap();
+            run[at + 1].clone()
+        };
+
+        let playlist = "https://www.youtube.com/playlist?list=PL123";
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", playlist);
+        let runs = per_item_runs(&args, playlist, 5, opts.latest_items);
+        assert_eq!(runs.iter().map(|r| item(r)).collect::<Vec<_>>(), ["4", "5"]);
+        assert_eq!(runs[0].last().map(String::as_str), Some(playlist));
+
+        let channel = "https://www.youtube.com/@someone/videos";
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", channel);
+        let runs = per_item_runs(&args, channel, 5, opts.latest_items);
+        assert_eq!(runs.iter().map(|r| item(r)).collect::<Vec<_>>(), ["1", "2"]);
+
+        let runs = per_item_runs(&args, channel, 1, Some(3));
+        assert_eq!(runs.len(), 1);
+        assert_eq!(per_item_runs(&args, channel, 3, None).len(), 3);
+    }
+
+    #[test]
+    fn rate_limit_is_recognised_and_backed_off_from() {
+        let output =
+            "ERROR: [youtube] abc: Unable to download webpage: HTTP Error 429: Too Many Requests";
+        assert!(classify_error(output) == Some(FailureKind::RateLimited));
+        let wait = rate_limit_backoff().as_secs();
+        assert!((30..=60).contains(&wait));
+    }
+
+    #[test]
+    fn max_quality_audio_takes_any_format_and_reports_its_bitrate() {
+        let mut app = test_app();
+        app.options.format = MAX_QUALITY_FORMAT.to_string();
+        app.options.max_quality_audio = true;
+        let args = build_yt_dlp_args(&app.options, "%(title)s.%(ext)s", "https://x");
+        let quality = args.iter().position(|a| a == "--audio-quality").unwrap();
+        assert_eq!(args[quality + 1], "0");
+        assert!(app.is_output_file(Path::new("Song.opus")));
+        assert!(app.is_output_file(Path::new("Song.flac")));
+
+        let track = |codec: &str, kbps| AudioInfo {
+            codec: codec.to_string(),
+            kbps,
+        };
+        assert_eq!(resolved_audio_summary(&[]), None);
+        assert_eq!(
+            resolved_audio_summary(&[track("opus", 160)]).as_deref(),
+            Some("Audio: opus, 160 kbps")
+        );
+        assert_eq!(
+            resolved_audio_summary(&[track("opus", 160), track("aac", 129), track("opus", 140)])
+                .as_deref(),
+            Some("Audio: aac/opus, 129–160 kbps")
+        );
+    }
+
+    #[test]
+    fn path_too_long_errors_are_recognised() {
+        let linux =
+            "ERROR: unable to open for writing: [Errno 36] File name too long: 'x.m4a.part'";
+        assert!(classify_error(linux) == Some(FailureKind::PathTooLong));
+        let windows = "ERROR: unable to open for writing: [WinError 206] The filename or extension is too long";
+        assert!(classify_error(windows) == Some(FailureKind::PathTooLong));
+    }
+
+    #[test]
+    fn yt_dlp_crashes_are_told_apart_from_failed_downloads() {
+        let traceback =
+            "Traceback (most recent call last):\n  File \"yt_dlp/__main__.py\", line 17\n\
+                         AttributeError: 'NoneType' object has no attribute 'get'";
+        assert!(classify_error(traceback) == Some(FailureKind::YtDlpCrashed));
+        // A verbose traceback behind an error that has a better hint.
+        let verbose = format!("ERROR: Requested format is not available\n{}", traceback);
+        assert!(classify_error(&verbose) == Some(FailureKind::FormatUnavailable));
+
+        #[cfg(unix)]
+        {
+            let segfault = Command::new("sh")
+                .args(["-c", "kill -SEGV $$"])
+                .status()
+                .unwrap();
+            let how = abnormal_exit(segfault).unwrap();
+            assert_eq!(how, "signal 11");
+            let line = format!("{} ({})", CRASH_MARKER, how);
+            assert!(classify_error(&line) == Some(FailureKind::YtDlpCrashed));
+            let interrupted = Command::new("sh")
+                .args(["-c", "kill -INT $$"])
+                .status()
+                .unwrap();
+            assert_eq!(abnormal_exit(interrupted), None);
+        }
+        let failed = Command::new("sh").args(["-c", "exit 1"]).status().unwrap();
+        assert_eq!(abnormal_exit(failed), None);
+    }
+
+    #[test]
+    fn album_tag_overrides_or_fills_in_the_source_album() {
+        let mut opts = DownloadOptions {
+            album: Some("Road Trip: 2024".to_string()),
+            ..DownloadOptions::default()
+        };
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", "https://x");
+        assert!(args.contains(&"Road Trip\\: 2024:%(meta_album)s".to_string()));
+        let one_word = DownloadOptions {
+            album: Some("Downloads".to_string()),
+            ..DownloadOptions::default()
+        };
+        let args = build_yt_dlp_args(&one_word, "%(title)s.%(ext)s", "https://x");
+        assert!(args.contains(&"Downloads%(ytd_none|)s:%(meta_album)s".to_string()));
+
+        opts.keep_source_album = true;
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", "https://x");
+        assert!(args.contains(&"Road Trip\\: 2024:%(ytd_album)s".to_string()));
+        assert!(args.contains(&"%(album,ytd_album)s:%(meta_album)s".to_string()));
+
+        opts.add_metadata = false;
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", "https://x");
+        assert!(!args.iter().any(|a| a.contains("meta_album")));
+    }
+
+    #[test]
+    fn max_duration_is_parsed_and_filtered_on() {
+        assert_eq!(parse_duration("600"), Some(600));
+        assert_eq!(parse_duration("10:00"), Some(600));
+        assert_eq!(parse_duration("1:30:05"), Some(5405));
+        assert_eq!(parse_duration("90:00"), Some(5400));
+        for bad in ["", "0", "1:60", "1:-5", "ten", "1:2:3:4", "1.5"] {
+            assert_eq!(parse_duration(bad), None, "{}", bad);
+        }
+        assert_eq!(format_duration(5405), "1:30:05");
+
+        let opts = DownloadOptions {
+            max_duration: Some(600),
+            ..DownloadOptions::default()
+        };
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", "https://x");
+        let filter = args.iter().position(|a| a == "--match-filter").unwrap();
+        assert_eq!(args[filter + 1], "duration <? 600");
+    }
+
+    #[test]
+    fn watch_logs_a_failed_check_and_waits_for_the_next() {
+        let mut app = test_app();
+        app.url_queue = vec!["https://www.youtube.com/playlist?list=PL1".to_string()];
+        app.begin_watch();
+        assert!(app.state == AppState::Watching);
+        assert_eq!(app.watch_countdown(), 0);
+
+        app.state = AppState::Error;
+        app.error_message = "Download failed. Check your connection and URL.".to_string();
+        app.finish_watch_check();
+        assert!(app.state == AppState::Watching);
+        assert!(app.watch_countdown() > 59 * 60);
+        let results = &app.watch.as_ref().unwrap().results;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].to_string().contains("Check failed"));
+        assert_eq!(format_clock(3599), "59:59");
+    }
+
+    #[test]
+    fn video_toggle_keeps_the_video_and_its_files() {
+        let mut app = test_app();
+        app.set_video(true);
+        let args = build_yt_dlp_args(&app.options, "%(title)s.%(ext)s", "https://x");
+        assert!(args.contains(&VIDEO_FORMAT.to_string()));
+        assert!(args.contains(&"--merge-output-format".to_string()));
+        assert!(!args.contains(&"--extract-audio".to_string()));
+        assert!(app.is_output_file(Path::new("Talk.mp4")));
+        assert!(!app.is_output_file(Path::new("Talk.m4a")));
+
+        app.options.remux_video = Some("mkv".to_string());
+        let args = build_yt_dlp_args(&app.options, "%(title)s.%(ext)s", "https://x");
+        let remux = args.iter().position(|a| a == "--remux-video").unwrap();
+        assert_eq!(args[remux + 1], "mkv");
+        assert!(!args.contains(&"--merge-output-format".to_string()));
+        assert!(app.is_output_file(Path::new("Talk.mkv")));
+        assert!(!app.is_output_file(Path::new("Talk.mp4")));
+        let output = "[VideoRemuxer] Remuxing video from mp4 to webm; Destination: Talk.webm\n\
+                      ERROR: Postprocessing: Conversion failed!";
+        assert!(classify_error(output) == Some(FailureKind::RemuxFailed));
+
+        app.set_video(false);
+        assert_eq!(app.options.format, DownloadOptions::default().format);
+        let args = build_yt_dlp_args(&app.options, "%(title)s.%(ext)s", "https://x");
+        assert!(args.contains(&"--extract-audio".to_string()));
+        assert!(app.is_output_file(Path::new("Talk.m4a")));
+    }
+
+    #[test]
+    fn only_this_runs_audio_files_are_counted() {
+        let mut app = test_app();
+        app.playlist_name = format!("count-test-{}", std::process::id());
+        let dir = app.playlist_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["Old.m4a", "New.m4a", "New.info.json"] {
+            std::fs::write(dir.join(name), b"x").unwrap();
+        }
+        app.created
+            .lock()
+            .unwrap()
+            .extend([dir.join("New.m4a"), dir.join("New.info.json")]);
+        assert_eq!(app.created_output_count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lower_quality_is_offered_only_with_someone_to_answer() {
+        let mut app = test_app();
+        app.config.adaptive_quality = true;
+        assert_eq!(app.slow_speed(), Some(150.0 * 1024.0));
+        app.headless = true;
+        assert_eq!(app.slow_speed(), None);
+    }
+
+    #[test]
+    fn lower_quality_keeps_the_video_in_video_mode() {
+        let mut app = test_app();
+        assert_eq!(app.low_quality_format(), LOW_QUALITY_FORMAT);
+        app.set_video(true);
+        assert_eq!(app.low_quality_format(), LOW_QUALITY_VIDEO_FORMAT);
+    }
+
+    #[test]
+    fn genre_tag_is_escaped_and_validated() {
+        let mut opts = DownloadOptions {
+            genre: Some("Jazz".to_string()),
+            album: Some("Late Night".to_string()),
+            ..DownloadOptions::default()
+        };
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", "https://x");
+        // A bare word would otherwise be read as a field name.
+        assert!(args.contains(&"Jazz%(ytd_none|)s:%(meta_genre)s".to_string()));
+        assert!(args.contains(&"Late Night:%(meta_album)s".to_string()));
+
+        opts.genre = Some("Drum & Bass: 100%".to_string());
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", "https://x");
+        assert!(args.contains(&"Drum & Bass\\: 100%%:%(meta_genre)s".to_string()));
+
+        assert!(validate_genre("Hip-Hop/Rap").is_ok());
+        assert!(validate_genre("Jazz\nFunk").is_err());
+        assert!(validate_genre("  ").is_err());
+    }
+
+    #[test]
+    fn every_terminal_height_renders_without_panicking() {
+        let mut app = test_app();
+        app.playlist_name = "Mix".to_string();
+        app.url_queue = vec!["https://www.youtube.com/watch?v=abc".to_string()];
+        app.files_downloaded = vec!["Song.m4a".to_string()];
+        for state in [
+            AppState::InputPlaylistName,
+            AppState::Confirm,
+            AppState::Downloading,
+            AppState::Done,
+            AppState::Error,
+        ] {
+            app.state = state;
+            for height in 1..40 {
+                let backend = ratatui::backend::TestBackend::new(60, height);
+                let mut terminal = Terminal::new(backend).unwrap();
+                terminal.draw(|f| draw(f, &mut app, 0)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn one_word_comment_stays_literal() {
+        let opts = DownloadOptions {
+            comment: Some("Liked".to_string()),
+            ..DownloadOptions::default()
+        };
+        let args = build_yt_dlp_args(&opts, "%(title)s.%(ext)s", "https://x");
+        assert!(args.contains(&"Liked%(ytd_none|)s:%(meta_comment)s".to_string()));
+    }
+
+    #[test]
+    fn last_n_lines_fewer_than_n() {
+        assert_eq!(last_n_lines("a\nb", 5), "a\nb");
+    }
+
+    #[test]
+    fn last_n_lines_exactly_n() {
+        assert_eq!(last_n_lines("a\nb\nc", 3), "a\nb\nc");
+    }
+
+    #[test]
+    fn last_n_lines_more_than_n() {
+        assert_eq!(last_n_lines("a\nb\nc\nd\ne\nf", 2), "e\nf");
+    }
+
+    #[test]
+    fn last_n_lines_empty_input() {
+        assert_eq!(last_n_lines("", 5), "");
+        assert_eq!(last_n_lines("a\nb", 0), "");
+    }
+
+    #[test]
+    fn last_n_lines_trailing_newline() {
+        assert_eq!(last_n_lines("a\nb\nc\n", 2), "b\nc");
+        assert_eq!(last_n_lines("a\n", 5), "a");
+    }
+}
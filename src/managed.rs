@@ -0,0 +1,152 @@
+//! ytd's own copy of yt-dlp: the standalone release binary, kept under the
+//! data directory so an old system yt-dlp can't break downloads. Fetched
+//! with `curl`, like the clipboard goes through command-line tools.
+
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+const RELEASES: &str = "https://github.com/yt-dlp/yt-dlp/releases/latest/download";
+
+static PROGRAM: OnceLock<PathBuf> = OnceLock::new();
+
+/// The yt-dlp every command runs: ytd's own copy once `use_program` has
+/// been called, otherwise whichever is on PATH.
+pub fn yt_dlp() -> &'static Path {
+    PROGRAM
+        .get()
+        .map(PathBuf::as_path)
+        .unwrap_or(Path::new("yt-dlp"))
+}
+
+/// Runs `path` for the rest of the process. Only the first call counts.
+pub fn use_program(path: PathBuf) {
+    let _ = PROGRAM.set(path);
+}
+
+/// `<data dir>/ytd/bin/yt-dlp`, e.g. `~/.local/share/ytd/bin/yt-dlp`.
+pub fn managed_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| {
+        d.join("ytd")
+            .join("bin")
+            .join(format!("yt-dlp{}", std::env::consts::EXE_SUFFIX))
+    })
+}
+
+/// The release file for this platform. Anything without a standalone
+/// build gets the zipapp, which needs Python.
+fn release_asset() -> &'static str {
+    if cfg!(windows) {
+        "yt-dlp.exe"
+    } else if cfg!(target_os = "macos") {
+        "yt-dlp_macos"
+    } else if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        "yt-dlp_linux"
+    } else if cfg!(all(target_os = "linux", target_arch = "aarch64")) {
+        "yt-dlp_linux_aarch64"
+    } else if cfg!(all(target_os = "linux", target_arch = "arm")) {
+        "yt-dlp_linux_armv7l"
+    } else {
+        "yt-dlp"
+    }
+}
+
+/// `curl` with `args`, failing on HTTP errors. Returns its stdout.
+fn curl(args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--proto", "=https"])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Could not run curl: {}", e))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(match stderr.lines().rfind(|l| !l.trim().is_empty()) {
+            Some(line) => line.trim().to_string(),
+            None => format!("curl failed ({})", output.status),
+        })
+    }
+}
+
+/// The hash `SHA2-256SUMS` lists for `asset`, lowercased.
+fn expected_checksum(sums: &str, asset: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        // `sha256sum` marks binary mode with a `*` before the name.
+        let name = name.trim_start().trim_start_matches('*');
+        (name == asset).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// Downloads the latest release to `path`, replacing what's there only once
+/// its checksum matches. Returns the new version.
+pub fn install_latest(path: &Path) -> Result<String, String> {
+    let asset = release_asset();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    }
+    let sums = curl(&[&format!("{}/SHA2-256SUMS", RELEASES)])?;
+    let expected = expected_checksum(&String::from_utf8_lossy(&sums), asset)
+        .ok_or_else(|| format!("The release lists no checksum for {}", asset))?;
+
+    let partial = path.with_extension("part");
+    let partial_arg = partial.to_string_lossy();
+    curl(&["--output", &partial_arg, &format!("{}/{}", RELEASES, asset)])?;
+    let bytes = std::fs::read(&partial)
+        .map_err(|e| format!("Could not read {}: {}", partial.display(), e))?;
+    let actual = sha256_hex(&bytes);
+    if actual != expected {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!(
+            "Checksum mismatch for {} (expected {}, got {})",
+            asset, expected, actual
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&partial, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Could not make {} executable: {}", partial.display(), e))?;
+    }
+    std::fs::rename(&partial, path)
+        .map_err(|e| format!("Could not move it into {}: {}", path.display(), e))?;
+
+    let output = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("The downloaded yt-dlp doesn't run: {}", e))?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// SHA-256 of `data` as lowercase hex, like `SHA2-256SUMS` has it.
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_are_looked_up() {
+        let sums = "1111  yt-dlp\nABCD  yt-dlp_linux\n2222 *yt-dlp.exe\n";
+        assert_eq!(
+            expected_checksum(sums, "yt-dlp_linux").as_deref(),
+            Some("abcd")
+        );
+        assert_eq!(
+            expected_checksum(sums, "yt-dlp.exe").as_deref(),
+            Some("2222")
+        );
+        assert_eq!(expected_checksum(sums, "yt-dlp_macos"), None);
+    }
+}
@@ -0,0 +1,227 @@
+//! A less/vim-style full-screen view of the download log: scrolling by
+//! line or half page, `gg`/`G`, and `/` search with `n`/`N`.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Byte ranges of `query` in `line`, ignoring ASCII case.
+pub fn match_ranges(line: &str, query: &str) -> Vec<(usize, usize)> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let haystack = line.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
+    haystack
+        .match_indices(&needle)
+        .map(|(start, m)| (start, start + m.len()))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct Pager {
+    /// Index of the first visible line. Ignored while `follow` is set.
+    top: usize,
+    /// Stick to the end of the log as new lines arrive, like `tail -f`.
+    follow: bool,
+    /// Visible lines, set by the renderer each frame.
+    pub height: usize,
+    /// The last confirmed search, highlighted in the view.
+    pub query: Option<String>,
+    /// A search being typed after `/`.
+    pub typing: Option<String>,
+    /// "Pattern not found" and the like.
+    pub message: Option<String>,
+    pending_g: bool,
+}
+
+impl Pager {
+    pub fn new() -> Self {
+        Self {
+            follow: true,
+            ..Self::default()
+        }
+    }
+
+    /// First visible line for a log of `len` lines.
+    pub fn top(&self, len: usize) -> usize {
+        let last_page = len.saturating_sub(self.height);
+        if self.follow {
+            last_page
+        } else {
+            self.top.min(last_page)
+        }
+    }
+
+    fn scroll(&mut self, len: usize, by: isize) {
+        let top = self.top(len).saturating_add_signed(by);
+        let last_page = len.saturating_sub(self.height);
+        self.top = top.min(last_page);
+        self.follow = top >= last_page;
+    }
+
+    fn jump_to(&mut self, len: usize, line: usize) {
+        self.follow = false;
+        self.top = line.min(len.saturating_sub(self.height));
+    }
+
+    /// Finds the next (or previous) line containing the query, starting
+    /// just past the top of the view.
+    fn search(&mut self, lines: &[&str], forward: bool) {
+        let Some(query) = self.query.clone().filter(|q| !q.is_empty()) else {
+            return;
+        };
+        let top = self.top(lines.len());
+        let matches = |i: &usize| !match_ranges(lines[*i], &query).is_empty();
+        let found = if forward {
+            (top + 1..lines.len()).find(matches)
+        } else {
+            (0..top).rev().find(matches)
+        };
+        match found {
+            Some(line) => self.jump_to(lines.len(), line),
+            None => self.message = Some(format!("Pattern not found: {}", query)),
+        }
+    }
+
+    /// Handles a key press. Returns `false` when the pager should close.
+    pub fn handle_key(&mut self, key: KeyEvent, text: &str) -> bool {
+        let lines: Vec<&str> = text.lines().collect();
+        let len = lines.len();
+        self.message = None;
+
+        if let Some(typing) = self.typing.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => typing.push(c),
+                KeyCode::Backspace => {
+                    typing.pop();
+                }
+                KeyCode::Enter => {
+                    self.query = self.typing.take();
+                    // Search from the top of the view itself, not past it.
+                    self.top = self.top(len).saturating_sub(1);
+                    self.follow = false;
+                    self.search(&lines, true);
+                }
+                KeyCode::Esc => self.typing = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        let half = (self.height / 2).max(1) as isize;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let pending_g = std::mem::take(&mut self.pending_g);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('d') if ctrl => self.scroll(len, half),
+            KeyCode::Char('u') if ctrl => self.scroll(len, -half),
+            KeyCode::Char('j') | KeyCode::Down => self.scroll(len, 1),
+            KeyCode::Char('k') | KeyCode::Up => self.scroll(len, -1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll(len, self.height as isize),
+            KeyCode::PageUp => self.scroll(len, -(self.height as isize)),
+            KeyCode::Char('g') if pending_g => self.jump_to(len, 0),
+            KeyCode::Char('g') => self.pending_g = true,
+            KeyCode::Char('G') => self.follow = true,
+            KeyCode::Char('/') => self.typing = Some(String::new()),
+            KeyCode::Char('n') => self.search(&lines, true),
+            KeyCode::Char('N') => self.search(&lines, false),
+            _ => {}
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(pager: &mut Pager, code: KeyCode, text: &str) -> bool {
+        pager.handle_key(KeyEvent::new(code, KeyModifiers::NONE), text)
+    }
+
+    fn log(lines: usize) -> String {
+        (1..=lines).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn scrolling_stays_within_the_log() {
+        let text = log(20);
+        let mut pager = Pager::new();
+        pager.height = 5;
+        assert_eq!(pager.top(20), 15);
+
+        press(&mut pager, KeyCode::PageUp, &text);
+        assert_eq!(pager.top(20), 10);
+        for _ in 0..5 {
+            press(&mut pager, KeyCode::PageUp, &text);
+        }
+        assert_eq!(pager.top(20), 0);
+        press(&mut pager, KeyCode::Char('k'), &text);
+        assert_eq!(pager.top(20), 0);
+
+        for _ in 0..10 {
+            press(&mut pager, KeyCode::PageDown, &text);
+        }
+        assert_eq!(pager.top(20), 15);
+        // Back at the end, it follows new lines again.
+        assert_eq!(pager.top(30), 25);
+    }
+
+    #[test]
+    fn short_log_never_scrolls() {
+        let text = log(3);
+        let mut pager = Pager::new();
+        pager.height = 10;
+        press(&mut pager, KeyCode::Char('j'), &text);
+        assert_eq!(pager.top(3), 0);
+        press(&mut pager, KeyCode::Char('g'), &text);
+        press(&mut pager, KeyCode::Char('g'), &text);
+        assert_eq!(pager.top(3), 0);
+    }
+
+    #[test]
+    fn gg_and_g_jump_to_the_ends() {
+        let text = log(20);
+        let mut pager = Pager::new();
+        pager.height = 5;
+        press(&mut pager, KeyCode::Char('g'), &text);
+        assert_eq!(pager.top(20), 15);
+        press(&mut pager, KeyCode::Char('g'), &text);
+        assert_eq!(pager.top(20), 0);
+        assert_eq!(pager.top(30), 0);
+        press(&mut pager, KeyCode::Char('G'), &text);
+        assert_eq!(pager.top(30), 25);
+    }
+
+    #[test]
+    fn search_jumps_to_matches_and_reports_misses() {
+        let text = "start\nERROR one\nok\nerror two\nend\n";
+        let mut pager = Pager::new();
+        pager.height = 1;
+        press(&mut pager, KeyCode::Char('g'), text);
+        press(&mut pager, KeyCode::Char('g'), text);
+        press(&mut pager, KeyCode::Char('/'), text);
+        for c in "error".chars() {
+            press(&mut pager, KeyCode::Char(c), text);
+        }
+        press(&mut pager, KeyCode::Enter, text);
+        assert_eq!(pager.top(5), 1);
+        press(&mut pager, KeyCode::Char('n'), text);
+        assert_eq!(pager.top(5), 3);
+        press(&mut pager, KeyCode::Char('n'), text);
+        assert_eq!(pager.top(5), 3);
+        assert_eq!(pager.message.as_deref(), Some("Pattern not found: error"));
+        press(&mut pager, KeyCode::Char('N'), text);
+        assert_eq!(pager.top(5), 1);
+        assert_eq!(match_ranges("ERROR one", "error"), [(0, 5)]);
+    }
+
+    #[test]
+    fn q_and_esc_close_but_esc_first_cancels_a_search() {
+        let mut pager = Pager::new();
+        press(&mut pager, KeyCode::Char('/'), "");
+        assert!(press(&mut pager, KeyCode::Esc, ""));
+        assert!(pager.typing.is_none());
+        assert!(!press(&mut pager, KeyCode::Esc, ""));
+        assert!(!press(&mut pager, KeyCode::Char('q'), ""));
+    }
+}
@@ -0,0 +1,143 @@
+//! How long the paths a download writes can get, against what the system
+//! allows: 260 characters for a whole path on Windows, 255 bytes for a file
+//! name nearly everywhere.
+
+/// Windows' MAX_PATH, less the terminating NUL.
+const WINDOWS_MAX_PATH: usize = 259;
+
+/// The longest file name ext4, APFS and NTFS take.
+const NAME_MAX: usize = 255;
+
+/// What yt-dlp adds while it works, as in `Title.f251.webm.part`.
+const WORKING_SUFFIX: usize = ".f000.webm.part".len();
+
+/// YouTube titles are at most 100 characters.
+const TITLE_LENGTH: usize = 100;
+
+/// A guess at any other field, like an uploader or album name.
+const FIELD_LENGTH: usize = 40;
+
+/// `template` (a full `--output` path) with each `%(...)s` field stood in
+/// for by as much text as it's likely to expand to, so the result is about
+/// as long as the longest path the download will write.
+pub fn estimate_output_path(template: &str) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("%(") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(')') else {
+            rest = &rest[start..];
+            break;
+        };
+        let field = &rest[start + 2..start + end];
+        // The conversion after the `)`, e.g. `s` or `03d`.
+        let after = &rest[start + end + 1..];
+        let conversion = after
+            .find(|c: char| c.is_ascii_alphabetic())
+            .map_or(after.len(), |i| i + 1);
+        let name = field.split([',', '|', '>', '&']).next().unwrap_or("");
+        let length = match name {
+            "title" | "fulltitle" | "track" => TITLE_LENGTH,
+            "ext" => 4,
+            "id" => 11,
+            _ if field.contains('>') => 4,
+            _ => FIELD_LENGTH,
+        };
+        out.push_str(&"x".repeat(length));
+        rest = &after[conversion..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// What's wrong with paths like `estimate`, if anything. The whole-path
+/// limit only applies `on_windows`.
+pub fn path_length_warning(estimate: &str, on_windows: bool) -> Option<String> {
+    let path_length = estimate.chars().count() + WORKING_SUFFIX;
+    let name_length = estimate.rsplit(['/', '\\']).next().map_or(0, str::len) + WORKING_SUFFIX;
+    if on_windows && path_length > WINDOWS_MAX_PATH {
+        Some(format!(
+            "Paths may reach {} characters, past the 260 many Windows programs can open. Shorten the playlist name or template, or add --restrict-filenames --trim-filenames 100 to the playlist's yt-dlp.conf",
+            path_length
+        ))
+    } else if name_length > NAME_MAX {
+        Some(format!(
+            "File names may reach {} bytes, past the limit of 255. Shorten the template, or add --trim-filenames 100 to the playlist's yt-dlp.conf",
+            name_length
+        ))
+    } else {
+        None
+    }
+}
+
+/// On Windows, `template` with the `\\?\` prefix that lets yt-dlp write
+/// past the 260 character limit, when its paths are likely to go past it.
+/// Elsewhere, or when they fit, it's returned as it is.
+pub fn long_path_template(template: String) -> String {
+    let fits = path_length_warning(&estimate_output_path(&template), true).is_none();
+    if !cfg!(windows) || fits {
+        return template;
+    }
+    verbatim(&template)
+}
+
+/// `C:\Music\x` -> `\\?\C:\Music\x`, `\\server\share` ->
+/// `\\?\UNC\server\share`. A prefixed path isn't normalised any more, so
+/// forward slashes become backslashes first; yt-dlp normalises the
+/// template's own slashes itself. Relative paths can't take the prefix.
+fn verbatim(path: &str) -> String {
+    let path = path.replace('/', "\\");
+    if path.starts_with(r"\\?\") {
+        path
+    } else if let Some(unc) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
+    } else if path.as_bytes().get(1) == Some(&b':') {
+        format!(r"\\?\{}", path)
+    } else {
+        path
+    }
+}
+
+/// A path yt-dlp printed, without the `\\?\` prefix `long_path_template`
+/// may have given it, so it compares equal to the same path without one.
+pub fn strip_verbatim(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else {
+        path.strip_prefix(r"\\?\").unwrap_or(path).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_paths_are_estimated_and_warned_about() {
+        let estimate = estimate_output_path("/music/Mix/%(uploader)s/%(title)s.%(ext)s");
+        assert_eq!(
+            estimate,
+            format!("/music/Mix/{}/{}.xxxx", "x".repeat(40), "x".repeat(100))
+        );
+        let dated = estimate_output_path("%(upload_date>%Y|Unknown)s/%(playlist_index)03d.m4a");
+        assert_eq!(dated, format!("xxxx/{}.m4a", "x".repeat(40)));
+
+        assert_eq!(path_length_warning(&estimate, true), None);
+        let deep = format!("C:\\Users\\me\\{}\\{}", "d".repeat(80), estimate);
+        assert!(path_length_warning(&deep, true)
+            .unwrap()
+            .contains("Windows"));
+        assert_eq!(path_length_warning(&deep, false), None);
+        let long_name = format!("/music/{}.m4a", "n".repeat(250));
+        assert!(path_length_warning(&long_name, false)
+            .unwrap()
+            .contains("File names"));
+
+        assert_eq!(verbatim("C:/Music/a"), r"\\?\C:\Music\a");
+        assert_eq!(verbatim(r"\\nas\music\a"), r"\\?\UNC\nas\music\a");
+        assert_eq!(verbatim("Music/a"), r"Music\a");
+        assert_eq!(strip_verbatim(r"\\?\C:\Music\a.m4a"), r"C:\Music\a.m4a");
+        assert_eq!(strip_verbatim(r"\\?\UNC\nas\a.m4a"), r"\\nas\a.m4a");
+        assert_eq!(strip_verbatim("/music/a.m4a"), "/music/a.m4a");
+    }
+}
@@ -0,0 +1,77 @@
+//! Handing downloaded files to a media player.
+
+use crate::files::safe_file_name;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// The system's "open with the default app" command.
+fn default_opener() -> Command {
+    if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    }
+}
+
+/// Writes an extended M3U playlist of `files` to the temp dir, for openers
+/// that take a single path.
+fn write_m3u(name: &str, files: &[PathBuf]) -> Result<PathBuf, String> {
+    let path = std::env::temp_dir().join(format!("ytd-{}.m3u", safe_file_name(name)));
+    let mut contents = String::from("#EXTM3U\n");
+    for file in files {
+        contents.push_str(&file.display().to_string());
+        contents.push('\n');
+    }
+    std::fs::write(&path, contents)
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Starts playing `files`, in order, without waiting for the player.
+///
+/// `player` is a command line split on spaces, with the files appended. Without
+/// one the system opener is used; it only takes one path, so several files
+/// go through a temporary `.m3u` instead.
+pub fn play(player: Option<&str>, name: &str, files: &[PathBuf]) -> Result<(), String> {
+    let (mut command, program) = match player.map(str::split_whitespace) {
+        Some(mut parts) => {
+            let program = parts.next().ok_or("The player command is empty")?;
+            let mut command = Command::new(program);
+            command.args(parts).args(files);
+            (command, program.to_string())
+        }
+        None => {
+            let mut command = default_opener();
+            match files {
+                [file] => command.arg(file),
+                _ => command.arg(write_m3u(name, files)?),
+            };
+            let program = command.get_program().to_string_lossy().into_owned();
+            (command, program)
+        }
+    };
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Could not start {}: {}", program, e))?;
+    // Reap it when it exits so it doesn't linger as a zombie.
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// "Song.m4a" or "12 files", for status messages.
+pub fn describe(files: &[PathBuf]) -> String {
+    match files {
+        [file] => file
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        _ => format!("{} files", files.len()),
+    }
+}
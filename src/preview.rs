@@ -0,0 +1,64 @@
+//! Small, low-bitrate copies of downloaded tracks, made with ffmpeg from
+//! the downloaded file rather than fetched a second time.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const PREVIEW_SUFFIX: &str = ".preview.opus";
+
+/// `Song.m4a` -> `Song.preview.opus`, next to it.
+pub fn preview_path(src: &Path) -> PathBuf {
+    let stem = src.file_stem().unwrap_or_default().to_string_lossy();
+    src.with_file_name(format!("{}{}", stem, PREVIEW_SUFFIX))
+}
+
+pub fn is_preview(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(PREVIEW_SUFFIX)
+}
+
+/// Encodes the audio of `src` as opus at `bitrate` (e.g. `64k`) into
+/// `preview_path(src)`, using the `ffmpeg` binary given and replacing an
+/// older preview. Returns the preview's path.
+pub fn make_preview(ffmpeg: &Path, src: &Path, bitrate: &str) -> io::Result<PathBuf> {
+    let out = preview_path(src);
+    let status = Command::new(ffmpeg)
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(src)
+        .args([
+            "-map",
+            "0:a:0",
+            "-map_metadata",
+            "0",
+            "-c:a",
+            "libopus",
+            "-b:a",
+        ])
+        .arg(bitrate)
+        .arg(&out)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&out);
+        return Err(io::Error::other(format!("ffmpeg exited with {}", status)));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_sit_next_to_their_source() {
+        let preview = preview_path(Path::new("/music/Mix/Song (Live).m4a"));
+        assert_eq!(
+            preview,
+            PathBuf::from("/music/Mix/Song (Live).preview.opus")
+        );
+        assert!(is_preview(&preview));
+        assert!(!is_preview(Path::new("/music/Mix/Song.opus")));
+    }
+}
@@ -0,0 +1,168 @@
+//! What ffmpeg reports about a downloaded file: what a download actually
+//! turned out as, and the tags it carries.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The audio stream of a file: its codec and bitrate in kbit/s.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioInfo {
+    pub codec: String,
+    pub kbps: u32,
+}
+
+/// Runs `ffmpeg -i` on `file` and reads its audio stream from what it
+/// prints. `None` when ffmpeg can't run or reports no bitrate.
+pub fn audio_info(ffmpeg: &Path, file: &Path) -> Option<AudioInfo> {
+    // With no output file ffmpeg exits with an error, but it has printed
+    // what it found by then.
+    let output = Command::new(ffmpeg)
+        .args(["-hide_banner", "-i"])
+        .arg(file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output()
+        .ok()?;
+    parse_audio_info(&String::from_utf8_lossy(&output.stderr))
+}
+
+fn kbps_in(text: &str) -> Option<u32> {
+    let before = text.split(" kb/s").next()?;
+    before.rsplit([' ', ',']).next()?.parse().ok()
+}
+
+/// The first `Stream ... Audio:` line's codec, with the stream's own
+/// bitrate or else the whole file's from the `Duration:` line (opus in ogg
+/// only has the latter).
+fn parse_audio_info(text: &str) -> Option<AudioInfo> {
+    let stream = text.lines().find_map(|l| l.split_once(": Audio: "))?.1;
+    let codec = stream
+        .split([' ', ','])
+        .next()
+        .filter(|c| !c.is_empty())?
+        .to_string();
+    let kbps = Some(stream)
+        .filter(|s| s.contains(" kb/s"))
+        .and_then(kbps_in)
+        .or_else(|| {
+            let duration = text
+                .lines()
+                .find(|l| l.trim_start().starts_with("Duration:"))?;
+            kbps_in(duration.split_once("bitrate: ")?.1)
+        })?;
+    Some(AudioInfo { codec, kbps })
+}
+
+/// The file-level tags of `file`, keys lowercased, as ffmpeg reads them.
+pub fn read_tags(ffmpeg: &Path, file: &Path) -> io::Result<HashMap<String, String>> {
+    let output = Command::new(ffmpeg)
+        .args(["-loglevel", "error", "-i"])
+        .arg(file)
+        .args(["-f", "ffmetadata", "-"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "ffmpeg exited with {}",
+            output.status
+        )));
+    }
+    Ok(parse_ffmetadata(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses ffmpeg's `ffmetadata` format up to the first `[STREAM]` or
+/// `[CHAPTER]` section. `=`, `;`, `#`, `\` and line breaks in values are
+/// escaped with a backslash.
+fn parse_ffmetadata(text: &str) -> HashMap<String, String> {
+    let mut tags = HashMap::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with('[') {
+            break;
+        }
+        if line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        let mut entry = String::new();
+        let mut escaped = false;
+        let mut line = line;
+        loop {
+            for c in line.chars() {
+                if escaped {
+                    entry.push(c);
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else {
+                    entry.push(c);
+                }
+            }
+            // A trailing backslash escapes the line break.
+            if !escaped {
+                break;
+            }
+            escaped = false;
+            entry.push('\n');
+            match lines.next() {
+                Some(next) => line = next,
+                None => break,
+            }
+        }
+        if let Some((key, value)) = entry.split_once('=') {
+            tags.insert(key.to_lowercase(), value.to_string());
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_read_from_ffmetadata() {
+        let metadata = ";FFMETADATA1\n\
+                        TITLE=Kids\n\
+                        artist=MGMT\n\
+                        comment=one\\=two\\\n\
+                        three\n\
+                        [STREAM]\n\
+                        title=ignored\n";
+        let tags = parse_ffmetadata(metadata);
+        assert_eq!(tags["title"], "Kids");
+        assert_eq!(tags["artist"], "MGMT");
+        assert_eq!(tags["comment"], "one=two\nthree");
+        assert_eq!(tags.len(), 3);
+    }
+
+    #[test]
+    fn audio_bitrate_is_read_from_ffmpeg_output() {
+        let m4a = "Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'Song.m4a':\n  \
+                   Duration: 00:03:32.37, start: 0.000000, bitrate: 130 kb/s\n  \
+                   Stream #0:0[0x1](und): Audio: aac (LC) (mp4a / 0x6134706D), 44100 Hz, stereo, fltp, 127 kb/s (default)\n";
+        assert_eq!(
+            parse_audio_info(m4a),
+            Some(AudioInfo {
+                codec: "aac".to_string(),
+                kbps: 127
+            })
+        );
+        let opus = "Input #0, ogg, from 'Song.opus':\n  \
+                    Duration: 00:03:32.36, start: 0.007500, bitrate: 118 kb/s\n  \
+                    Stream #0:0(eng): Audio: opus, 48000 Hz, stereo, fltp\n";
+        assert_eq!(
+            parse_audio_info(opus),
+            Some(AudioInfo {
+                codec: "opus".to_string(),
+                kbps: 118
+            })
+        );
+        assert_eq!(
+            parse_audio_info("Song.m4a: No such file or directory"),
+            None
+        );
+    }
+}
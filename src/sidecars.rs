@@ -0,0 +1,323 @@
+//! Extra files written next to the downloaded audio, built from the
+//! `.info.json` and subtitle files yt-dlp leaves behind when asked.
+
+use serde_json::Value;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// `Song.info.json` -> `Song<suffix>`, in the same directory.
+pub fn sidecar_path(info_json: &Path, suffix: &str) -> PathBuf {
+    let name = info_json
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = name.strip_suffix(".info.json").unwrap_or(&name);
+    info_json.with_file_name(format!("{}{}", stem, suffix))
+}
+
+fn read_info_json(path: &Path) -> io::Result<Value> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(io::Error::other)
+}
+
+fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (h, m, s) = (total / 3600, (total / 60) % 60, total % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{:02}:{:02}", m, s)
+    }
+}
+
+/// Writes `<title>.chapters.txt` with one `MM:SS Title` line per chapter.
+/// Returns `Ok(false)` without writing anything when the video has no
+/// chapters.
+pub fn write_chapters_sidecar(info_json: &Path) -> io::Result<bool> {
+    let info = read_info_json(info_json)?;
+    let Some(chapters) = info["chapters"].as_array().filter(|c| !c.is_empty()) else {
+        return Ok(false);
+    };
+
+    let mut out = String::new();
+    for chapter in chapters {
+        let start = chapter["start_time"].as_f64().unwrap_or(0.0);
+        let title = chapter["title"].as_str().unwrap_or("");
+        out.push_str(&format!("{} {}\n", format_timestamp(start), title));
+    }
+    std::fs::write(sidecar_path(info_json, ".chapters.txt"), out)?;
+    Ok(true)
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Writes a Kodi/Jellyfin style `<musicvideo>` NFO to `out` with whatever
+/// of title, artist, album, date, length and source URL the video has.
+/// Missing fields are left out rather than written empty.
+pub fn write_nfo(info_json: &Path, out: &Path) -> io::Result<()> {
+    let info = read_info_json(info_json)?;
+    let text = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| info[*k].as_str().filter(|v| !v.trim().is_empty()))
+            .map(str::to_string)
+    };
+
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    if let Some(title) = text(&["track", "title"]) {
+        fields.push(("title", title));
+    }
+    if let Some(artist) = text(&["artist", "creator", "uploader", "channel"]) {
+        fields.push(("artist", artist));
+    }
+    if let Some(album) = text(&["album"]) {
+        fields.push(("album", album));
+    }
+    // yt-dlp dates are YYYYMMDD.
+    if let Some(date) = text(&["release_date", "upload_date"]).filter(|d| d.len() == 8) {
+        fields.push((
+            "premiered",
+            format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..]),
+        ));
+        fields.push(("year", date[..4].to_string()));
+    }
+    if let Some(duration) = info["duration"].as_f64() {
+        fields.push(("runtime", ((duration / 60.0).round() as u64).to_string()));
+    }
+    if let Some(description) = text(&["description"]) {
+        fields.push(("plot", description));
+    }
+    if let Some(url) = text(&["webpage_url", "original_url"]) {
+        fields.push(("source", url));
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    xml.push_str("<musicvideo>\n");
+    for (tag, value) in fields {
+        xml.push_str(&format!("  <{0}>{1}</{0}>\n", tag, escape_xml(&value)));
+    }
+    if let Some(id) = text(&["id"]) {
+        xml.push_str(&format!(
+            "  <uniqueid type=\"youtube\" default=\"true\">{}</uniqueid>\n",
+            escape_xml(&id)
+        ));
+    }
+    xml.push_str("</musicvideo>\n");
+    std::fs::write(out, xml)
+}
+
+/// `Song.en.vtt` -> `Song<suffix>`, next to the audio file it belongs to.
+fn subtitle_sidecar_path(subs: &Path, suffix: &str) -> PathBuf {
+    // yt-dlp names subtitles `<title>.<language>.vtt`.
+    let stem = subs.file_stem().map(Path::new).unwrap_or(subs);
+    let title = stem.file_stem().unwrap_or(stem.as_os_str());
+    subs.with_file_name(format!("{}{}", title.to_string_lossy(), suffix))
+}
+
+/// `Song.en.vtt` -> `Song.lrc`.
+pub fn lrc_path(subs: &Path) -> PathBuf {
+    subtitle_sidecar_path(subs, ".lrc")
+}
+
+/// `Song.en.vtt` -> `Song.transcript.txt`.
+pub fn transcript_path(subs: &Path) -> PathBuf {
+    subtitle_sidecar_path(subs, ".transcript.txt")
+}
+
+/// WebVTT `HH:MM:SS.mmm` or `MM:SS.mmm` in seconds.
+fn parse_vtt_timestamp(text: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in text.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+/// Drops inline markup such as `<c>`, `<i>` and `<00:00:01.500>` karaoke
+/// timings, keeping only the text.
+fn strip_vtt_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+}
+
+/// Converts WebVTT cues to LRC lines, `[mm:ss.xx]text`, one per cue. A cue
+/// spread over several lines is joined into one. Cues without text are
+/// dropped.
+pub fn vtt_to_lrc(vtt: &str) -> String {
+    let mut out = String::new();
+    let mut lines = vtt.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let Some((start, _)) = line.split_once("-->") else {
+            continue;
+        };
+        let Some(seconds) = parse_vtt_timestamp(start.trim()) else {
+            continue;
+        };
+        let text: Vec<String> = lines
+            .by_ref()
+            .take_while(|l| !l.is_empty())
+            .map(strip_vtt_tags)
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        if text.is_empty() {
+            continue;
+        }
+        let centis = (seconds * 100.0).round() as u64;
+        out.push_str(&format!(
+            "[{:02}:{:02}.{:02}]{}\n",
+            centis / 6000,
+            (centis / 100) % 60,
+            centis % 100,
+            text.join(" ").trim()
+        ));
+    }
+    out
+}
+
+/// A pause between cues at least this long, in seconds, starts a new
+/// paragraph of the transcript.
+const PARAGRAPH_PAUSE: f64 = 2.0;
+
+/// The words of WebVTT cues as running text, without timestamps, cue
+/// settings or markup. Auto-generated captions roll: each cue repeats the
+/// line before it, so a line the same as the last one kept is skipped.
+pub fn transcript_from_vtt(vtt: &str) -> String {
+    let mut paragraphs: Vec<Vec<String>> = Vec::new();
+    let mut last_line = String::new();
+    let mut last_end: Option<f64> = None;
+    // Not trimmed: a line of just a space (which rolling captions use as
+    // a placeholder) doesn't end a cue, only an empty one does.
+    let mut lines = vtt.lines();
+    while let Some(line) = lines.next() {
+        let Some((start, rest)) = line.trim().split_once("-->") else {
+            continue;
+        };
+        // Cue settings such as `align:start position:0%` follow the end.
+        let end = rest.split_whitespace().next().and_then(parse_vtt_timestamp);
+        let Some(start) = parse_vtt_timestamp(start.trim()) else {
+            continue;
+        };
+        let text: Vec<String> = lines
+            .by_ref()
+            .take_while(|l| !l.is_empty())
+            .map(|l| {
+                strip_vtt_tags(l)
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|l| !l.is_empty())
+            .collect();
+        if last_end.is_none_or(|end| start - end >= PARAGRAPH_PAUSE) {
+            paragraphs.push(Vec::new());
+        }
+        last_end = end.or(Some(start));
+        for line in text {
+            if line != last_line {
+                last_line = line.clone();
+                paragraphs.last_mut().unwrap().push(line);
+            }
+        }
+    }
+    let mut out = String::new();
+    for paragraph in paragraphs.iter().filter(|p| !p.is_empty()) {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&paragraph.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+/// Converts the subtitle file `vtt` into a plain-text transcript at `out`.
+/// Returns `Ok(false)` without writing anything when it has no text.
+pub fn vtt_to_transcript(vtt: &Path, out: &Path) -> io::Result<bool> {
+    let transcript = transcript_from_vtt(&std::fs::read_to_string(vtt)?);
+    if transcript.is_empty() {
+        return Ok(false);
+    }
+    std::fs::write(out, transcript)?;
+    Ok(true)
+}
+
+/// Converts the subtitle file `subs` into an LRC lyrics file at `out`.
+/// Returns `Ok(false)` without writing anything when it has no lyrics.
+pub fn write_lrc(subs: &Path, out: &Path) -> io::Result<bool> {
+    let lrc = vtt_to_lrc(&std::fs::read_to_string(subs)?);
+    if lrc.is_empty() {
+        return Ok(false);
+    }
+    std::fs::write(out, lrc)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vtt_cues_become_lrc_lines() {
+        let vtt = "WEBVTT\nKind: captions\nLanguage: en\n\n\
+                   1\n00:00:01.250 --> 00:00:04.000 align:start\n<i>First</i> line\nwraps here\n\n\
+                   01:05.5 --> 01:08.0\nSecond &amp; last\n\n\
+                   00:01:10.000 --> 00:01:12.000\n\n";
+        assert_eq!(
+            vtt_to_lrc(vtt),
+            "[00:01.25]First line wraps here\n[01:05.50]Second & last\n"
+        );
+    }
+
+    #[test]
+    fn rolling_auto_captions_become_paragraphs() {
+        let vtt = "WEBVTT\nKind: captions\nLanguage: en\n\n\
+                   00:00:00.000 --> 00:00:02.000 align:start position:0%\n \n\
+                   welcome<00:00:00.500><c> to</c><c>&nbsp;the talk</c>\n\n\
+                   00:00:02.000 --> 00:00:02.010 align:start position:0%\nwelcome to the talk\n \n\n\
+                   00:00:02.010 --> 00:00:04.000 align:start position:0%\nwelcome to the talk\n\
+                   it&#39;s about<c> caching</c>\n\n\
+                   00:00:09.000 --> 00:00:11.000\nQuestions?\n\n";
+        assert_eq!(
+            transcript_from_vtt(vtt),
+            "welcome to the talk it's about caching\n\nQuestions?\n"
+        );
+        assert_eq!(
+            transcript_path(Path::new("/talks/Intro.en-orig.vtt")),
+            PathBuf::from("/talks/Intro.transcript.txt")
+        );
+    }
+
+    #[test]
+    fn lrc_path_drops_the_language() {
+        assert_eq!(
+            lrc_path(Path::new("/music/Song v1.2.en.vtt")),
+            PathBuf::from("/music/Song v1.2.lrc")
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Cutting silence from the start and end of downloaded tracks with ffmpeg,
+//! re-encoding each one in the codec its container already holds.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Trims silence from both ends: `silenceremove` only works on the start,
+/// so the audio is reversed, trimmed again and reversed back.
+const TRIM_SILENCE_FILTER: &str = "silenceremove=start_periods=1:start_silence=0.2:start_threshold=-50dB,areverse,silenceremove=start_periods=1:start_silence=0.2:start_threshold=-50dB,areverse";
+
+/// ffmpeg encoder arguments for audio going back into a file with this
+/// extension. A filter means re-encoding, and the encoder has to be one
+/// the container takes, so it follows the file rather than a setting.
+fn codec_args(ext: &str) -> Option<&'static [&'static str]> {
+    Some(match ext.to_ascii_lowercase().as_str() {
+        "m4a" | "mp4" | "aac" => &["-c:a", "aac", "-b:a", "192k"],
+        "opus" | "webm" => &["-c:a", "libopus", "-b:a", "128k"],
+        "ogg" => &["-c:a", "libvorbis", "-q:a", "6"],
+        "mp3" => &["-c:a", "libmp3lame", "-q:a", "2"],
+        "flac" => &["-c:a", "flac"],
+        "wav" => &["-c:a", "pcm_s16le"],
+        _ => return None,
+    })
+}
+
+/// Cuts silence from both ends of `file`, using the `ffmpeg` binary given.
+/// Tags and cover art are copied over. ffmpeg writes a copy next to the
+/// file, which then replaces the original, so a failure leaves the
+/// original untouched.
+pub fn trim_silence(ffmpeg: &Path, file: &Path) -> io::Result<()> {
+    let ext = file.extension().unwrap_or_default().to_string_lossy();
+    let codec = codec_args(&ext).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("no encoder for .{} files", ext),
+        )
+    })?;
+    let tmp = file.with_extension(format!("trim.{}", ext));
+    let status = Command::new(ffmpeg)
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(file)
+        .args(["-map", "0", "-map_metadata", "0", "-c", "copy", "-af"])
+        .arg(TRIM_SILENCE_FILTER)
+        .args(codec)
+        .arg(&tmp)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(io::Error::other(format!("ffmpeg exited with {}", status)));
+    }
+    std::fs::rename(&tmp, file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoder_follows_the_container() {
+        assert_eq!(codec_args("M4A").unwrap()[1], "aac");
+        assert_eq!(codec_args("opus").unwrap()[1], "libopus");
+        assert_eq!(codec_args("mp3").unwrap()[1], "libmp3lame");
+        assert!(codec_args("mkv").is_none());
+    }
+}